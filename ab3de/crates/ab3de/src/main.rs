@@ -1,7 +1,12 @@
 #![feature(decl_macro)]
 
+use ab3de_engine::{
+    InstancesDescription, ModelDescription, ModelLoaderKind, MountDescription, SceneDescription,
+    TransformDescription, ZipFsAccessor,
+};
 use clap::Parser;
 
+mod render;
 mod ui;
 mod uiless;
 mod utils;
@@ -12,6 +17,28 @@ struct Args {
     /// Run without a UI.
     #[arg(long, action)]
     uiless: bool,
+
+    /// A scene description (`.json`) to load instead of the built-in demo
+    /// scene.
+    #[arg(long, global = true)]
+    scene: Option<std::path::PathBuf>,
+
+    /// A `.pmx`, `.obj`, `.gltf` or `.glb` file to add to the scene at the
+    /// origin. Its textures are read from the folder it is in, which they
+    /// may not leave, and it is reloaded when they change. For a `.zip`
+    /// archive, the first model inside it is added. Replaces the demo's
+    /// cubes when there is no `--scene`.
+    #[arg(long = "model", global = true)]
+    models: Vec<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Render a frame range offscreen, without opening a window.
+    Render(render::RenderArgs),
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,7 +46,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let args = Args::parse();
 
-    if args.uiless {
+    let mut scene = match &args.scene {
+        Some(path) => SceneDescription::from_json_str(&std::fs::read_to_string(path)?)?,
+        None => SceneDescription::demo(),
+    };
+    if !args.models.is_empty() && args.scene.is_none() {
+        scene.models.clear();
+    }
+    for (index, path) in args.models.iter().enumerate() {
+        let (mount, model) = model_description(index, path)?;
+        scene.mounts.push(mount);
+        scene.models.push(model);
+    }
+
+    if let Some(Command::Render(render_args)) = args.command {
+        Ok(render::run(render_args, scene)?)
+    } else if args.uiless {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
@@ -38,8 +80,72 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             trace: wgpu::Trace::Off,
         };
 
-        Ok((uiless::run(instance, request_adapter_options, device_descriptor))?)
+        Ok((uiless::run(instance, request_adapter_options, device_descriptor, scene))?)
     } else {
-        Ok(ui::run()?)
+        Ok(ui::run(scene)?)
     }
 }
+
+/// Mounts the folder or archive of the `index`th `--model` under
+/// `args://<index>/`.
+fn model_description(
+    index: usize,
+    path: &std::path::Path,
+) -> anyhow::Result<(MountDescription, ModelDescription)> {
+    let filename = path
+        .file_name()
+        .and_then(|filename| filename.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Invalid model path: {}", path.display()))?;
+    let prefix = format!("args://{}/", index);
+
+    let (mount, loader, model_filename) = if filename.to_ascii_lowercase().ends_with(".zip") {
+        let archive = ZipFsAccessor::open(path)?;
+        let mut model_filenames = archive.model_filenames();
+        let (model_filename, loader) = model_filenames
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No models in {}", path.display()))?;
+        for (other_filename, _) in model_filenames {
+            log::info!(
+                "[model_description] skipping {} in {}",
+                other_filename,
+                filename
+            );
+        }
+        let model_filename = model_filename.to_string();
+        let mount = MountDescription::Zip {
+            prefix: prefix.clone(),
+            archive: path.to_path_buf(),
+            opened: Some(archive.into()),
+        };
+        (mount, loader, model_filename)
+    } else {
+        let loader = ModelLoaderKind::from_path(path)
+            .ok_or_else(|| anyhow::anyhow!("Unknown model format: {}", path.display()))?;
+        let root = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        };
+        let mount = MountDescription::Directory {
+            prefix: prefix.clone(),
+            root,
+            watch: true,
+        };
+        (mount, loader, filename.to_string())
+    };
+
+    let model = ModelDescription {
+        name: Some(filename.to_string()),
+        loader,
+        resource: format!("{}{}", prefix, model_filename),
+        instances: InstancesDescription::Transforms {
+            transforms: vec![TransformDescription {
+                position: glam::Vec3::ZERO,
+                rotation_degrees: glam::Vec3::ZERO,
+                scale: glam::Vec3::ONE,
+            }],
+        },
+        motion: None,
+        fit_size: None,
+    };
+    Ok((mount, model))
+}
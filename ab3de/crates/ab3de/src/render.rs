@@ -0,0 +1,258 @@
+use std::io::Write;
+
+use ab3de_engine::{
+    CameraData, Engine, HeadlessDeviceOptions, OffscreenPixelFormat, OffscreenPixels,
+    OffscreenViewportConfiguration, SceneDescription,
+};
+
+#[derive(clap::Args)]
+pub struct RenderArgs {
+    /// Output width in pixels.
+    #[arg(long, default_value_t = 1920)]
+    width: u32,
+    /// Output height in pixels.
+    #[arg(long, default_value_t = 1080)]
+    height: u32,
+    /// Frames per second of the rendered sequence.
+    #[arg(long, default_value_t = 60)]
+    fps: u32,
+    /// Index of the first frame to render.
+    #[arg(long, default_value_t = 0)]
+    start_frame: u64,
+    /// Number of frames to render.
+    #[arg(long, default_value_t = 1)]
+    frame_count: u64,
+
+    /// Camera position as `x,y,z`.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    camera_position: Option<Vec<f32>>,
+    /// Camera yaw in degrees. `-90` looks towards `-Z`.
+    #[arg(long, allow_negative_numbers = true)]
+    camera_yaw_degrees: Option<f32>,
+    /// Camera pitch in degrees. Negative values look down.
+    #[arg(long, allow_negative_numbers = true)]
+    camera_pitch_degrees: Option<f32>,
+
+    #[arg(long, value_enum, default_value_t = RenderOutputFormat::Png)]
+    format: RenderOutputFormat,
+    /// Directory for numbered PNG files. Ignored for `y4m`, which is always
+    /// written to stdout.
+    #[arg(long, default_value = ".")]
+    output_dir: std::path::PathBuf,
+
+    /// Use a software adapter, for machines without a GPU.
+    #[arg(long, action)]
+    software: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum RenderOutputFormat {
+    /// Numbered PNG files (`frame_000000.png`, ...).
+    Png,
+    /// A YUV4MPEG2 (4:2:0) stream on stdout, for piping into an encoder.
+    Y4m,
+}
+
+pub fn run(args: RenderArgs, scene: SceneDescription) -> anyhow::Result<()> {
+    if args.width == 0 || args.height == 0 {
+        anyhow::bail!("width and height must be positive");
+    }
+    if args.fps == 0 {
+        anyhow::bail!("fps must be positive");
+    }
+    if args.camera_position.as_ref().is_some_and(|p| p.len() != 3) {
+        anyhow::bail!("camera position must have exactly three components");
+    }
+    if args.format == RenderOutputFormat::Y4m
+        && (!args.width.is_multiple_of(2) || !args.height.is_multiple_of(2))
+    {
+        anyhow::bail!("y4m output needs an even width and height for 4:2:0 chroma subsampling");
+    }
+
+    let (device, queue) = pollster::block_on(ab3de_engine::request_headless_device(
+        HeadlessDeviceOptions {
+            force_fallback_adapter: args.software,
+        },
+    ))?;
+
+    let mut engine = Engine::try_new_from_scene(device, queue.clone(), &scene)?;
+    engine.timeline_mut().set_fps(args.fps);
+
+    let mut viewport = engine.make_offscreen_viewport(OffscreenViewportConfiguration {
+        size: (args.width, args.height).into(),
+        pixel_format: OffscreenPixelFormat::Rgba8,
+    });
+    viewport.update_camera(&queue, |camera_data| {
+        *camera_data = camera_data_from_args(&args, scene.camera_data());
+    });
+
+    let mut sink: Box<dyn FrameSink> = match args.format {
+        RenderOutputFormat::Png => {
+            std::fs::create_dir_all(&args.output_dir)?;
+            Box::new(PngSequenceSink {
+                dir: args.output_dir.clone(),
+            })
+        }
+        RenderOutputFormat::Y4m => Box::new(Y4mSink::new(
+            std::io::stdout().lock(),
+            args.width,
+            args.height,
+            args.fps,
+        )?),
+    };
+
+    for frame_index in args.start_frame..args.start_frame + args.frame_count {
+        engine.timeline_mut().seek_frame(frame_index);
+        engine.update();
+
+        let pixels = engine.render_offscreen(&viewport)?;
+        sink.write_frame(frame_index, &pixels)?;
+
+        let stats = engine.frame_stats();
+        log::info!(
+            "[render::run] rendered frame {} ({} draw calls, {} instances, {} culled)",
+            frame_index,
+            stats.draw_call_count,
+            stats.instance_count,
+            stats.culled_instance_count
+        );
+    }
+
+    sink.finish()
+}
+
+/// Overrides the scene's camera with whatever is given on the command line.
+fn camera_data_from_args(args: &RenderArgs, mut camera_data: CameraData) -> CameraData {
+    if let Some(position) = &args.camera_position {
+        camera_data.position = glam::vec3(position[0], position[1], position[2]);
+    }
+    if let Some(yaw) = args.camera_yaw_degrees {
+        camera_data.yaw_radians = yaw.to_radians();
+    }
+    if let Some(pitch) = args.camera_pitch_degrees {
+        camera_data.pitch_radians = pitch.to_radians();
+    }
+    camera_data
+}
+
+trait FrameSink {
+    fn write_frame(&mut self, frame_index: u64, pixels: &OffscreenPixels) -> anyhow::Result<()>;
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+struct PngSequenceSink {
+    dir: std::path::PathBuf,
+}
+
+impl FrameSink for PngSequenceSink {
+    fn write_frame(&mut self, frame_index: u64, pixels: &OffscreenPixels) -> anyhow::Result<()> {
+        let path = self.dir.join(format!("frame_{:06}.png", frame_index));
+        image::save_buffer(
+            &path,
+            &pixels.data,
+            pixels.size.x,
+            pixels.size.y,
+            image::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes 4:2:0 frames in BT.709 limited range.
+struct Y4mSink<W: Write> {
+    writer: std::io::BufWriter<W>,
+    y_plane: Vec<u8>,
+    u_plane: Vec<u8>,
+    v_plane: Vec<u8>,
+}
+
+impl<W: Write> Y4mSink<W> {
+    fn new(writer: W, width: u32, height: u32, fps: u32) -> anyhow::Result<Self> {
+        let mut writer = std::io::BufWriter::new(writer);
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+            width, height, fps
+        )?;
+
+        let luma_len = (width * height) as usize;
+        Ok(Self {
+            writer,
+            y_plane: vec![0; luma_len],
+            u_plane: vec![0; luma_len / 4],
+            v_plane: vec![0; luma_len / 4],
+        })
+    }
+}
+
+impl<W: Write> FrameSink for Y4mSink<W> {
+    fn write_frame(&mut self, _frame_index: u64, pixels: &OffscreenPixels) -> anyhow::Result<()> {
+        let width = pixels.size.x as usize;
+        let height = pixels.size.y as usize;
+        let rgb_at = |x: usize, y: usize| {
+            let i = (y * width + x) * 4;
+            glam::vec3(
+                pixels.data[i] as f32,
+                pixels.data[i + 1] as f32,
+                pixels.data[i + 2] as f32,
+            ) / 255.0
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let rgb = rgb_at(x, y);
+                self.y_plane[y * width + x] = yuv::luma(rgb);
+            }
+        }
+
+        for cy in 0..height / 2 {
+            for cx in 0..width / 2 {
+                let (x, y) = (cx * 2, cy * 2);
+                let rgb =
+                    (rgb_at(x, y) + rgb_at(x + 1, y) + rgb_at(x, y + 1) + rgb_at(x + 1, y + 1))
+                        / 4.0;
+                let (u, v) = yuv::chroma(rgb);
+                self.u_plane[cy * (width / 2) + cx] = u;
+                self.v_plane[cy * (width / 2) + cx] = v;
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.y_plane)?;
+        self.writer.write_all(&self.u_plane)?;
+        self.writer.write_all(&self.v_plane)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// BT.709 coefficients, limited (16..=235 / 16..=240) range.
+mod yuv {
+    const KR: f32 = 0.2126;
+    const KB: f32 = 0.0722;
+    const KG: f32 = 1.0 - KR - KB;
+
+    fn luma_unit(rgb: glam::Vec3) -> f32 {
+        KR * rgb.x + KG * rgb.y + KB * rgb.z
+    }
+
+    pub fn luma(rgb: glam::Vec3) -> u8 {
+        (16.0 + 219.0 * luma_unit(rgb)).round().clamp(0.0, 255.0) as u8
+    }
+
+    pub fn chroma(rgb: glam::Vec3) -> (u8, u8) {
+        let y = luma_unit(rgb);
+        let u = (rgb.z - y) / (2.0 * (1.0 - KB));
+        let v = (rgb.x - y) / (2.0 * (1.0 - KR));
+        let to_byte = |c: f32| (128.0 + 224.0 * c).round().clamp(0.0, 255.0) as u8;
+        (to_byte(u), to_byte(v))
+    }
+}
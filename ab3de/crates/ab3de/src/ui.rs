@@ -1,15 +1,21 @@
-use ab3de_engine::Engine;
+use ab3de_engine::{Engine, SceneDescription};
 use snafu::ResultExt;
 
-pub fn run() -> Result<(), RunError> {
+pub fn run(scene: SceneDescription) -> Result<(), RunError> {
     let native_options = eframe::NativeOptions::default();
 
     match eframe::run_native(
         "ab3de",
         native_options,
-        Box::new(|cc| Ok(Box::new(App::try_new(cc).context(NewAppSnafu)?))),
+        Box::new(|cc| Ok(Box::new(App::try_new(cc, scene)?))),
     ) {
         Ok(()) => Ok(()),
+        Err(eframe::Error::AppCreation(e)) => match e.downcast::<NewAppError>() {
+            Ok(e) => Err(*e).context(NewAppSnafu),
+            Err(e) => Err(RunError::EframeRunNativeError {
+                source: eframe::Error::AppCreation(e),
+            }),
+        },
         Err(e) => Err(RunError::EframeRunNativeError { source: e }),
     }
 }
@@ -25,16 +31,23 @@ struct App {
 }
 
 impl App {
-    fn try_new(cc: &eframe::CreationContext<'_>) -> Result<App, NewAppError> {
+    fn try_new(
+        cc: &eframe::CreationContext<'_>,
+        scene: SceneDescription,
+    ) -> Result<App, NewAppError> {
         let Some(wgpu_render_state) = &cc.wgpu_render_state else {
             return Err(NewAppError::WgpuNotAvailable);
         };
 
+        let initial_camera_data = scene.camera_data();
+
         let oev = offthread::OffthreadEngineAndViewport::new(
             wgpu_render_state.device.clone(),
             wgpu_render_state.queue.clone(),
             wgpu_render_state.target_format,
-        );
+            scene,
+        )
+        .context(NewEngineSnafu)?;
 
         let type_map = &mut wgpu_render_state.renderer.write().callback_resources;
 
@@ -44,6 +57,7 @@ impl App {
                 wgpu_render_state.target_format,
                 type_map,
                 Box::new(oev),
+                initial_camera_data,
             ),
         })
     }
@@ -58,6 +72,10 @@ impl eframe::App for App {
 #[derive(Debug, snafu::Snafu)]
 pub enum NewAppError {
     WgpuNotAvailable,
+    NewEngineError {
+        #[snafu(source(from(anyhow::Error, Into::into)))]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 mod offthread {
@@ -89,15 +107,30 @@ mod offthread {
             device: wgpu::Device,
             queue: wgpu::Queue,
             target_format: wgpu::TextureFormat,
-        ) -> Self {
+            scene: SceneDescription,
+        ) -> anyhow::Result<Self> {
             let (command_tx, command_rx) = std::sync::mpsc::sync_channel::<Command>(0);
 
             let textures = Arc::new(Mutex::new(Textures::new(target_format)));
 
+            // The engine is not `Send`, so it is created on the render thread,
+            // which reports back whether that succeeded.
+            let (new_engine_tx, new_engine_rx) = std::sync::mpsc::sync_channel(1);
+
             std::thread::spawn({
                 let textures = textures.clone();
                 move || {
-                    let mut engine = Engine::try_new(device.clone(), queue.clone()).unwrap();
+                    let mut engine =
+                        match Engine::try_new_from_scene(device.clone(), queue.clone(), &scene) {
+                            Ok(engine) => {
+                                let _ = new_engine_tx.send(Ok(()));
+                                engine
+                            }
+                            Err(e) => {
+                                let _ = new_engine_tx.send(Err(e));
+                                return;
+                            }
+                        };
                     let mut viewport: Option<Viewport> = None;
 
                     let mut latest_size: Option<glam::UVec2> = None;
@@ -144,7 +177,11 @@ mod offthread {
                                     *camera_data_ref = camera_data;
                                 });
 
-                                engine.update(update_time_ms, dt_s);
+                                for e in engine.reload_changed() {
+                                    log::error!("{:#}", e);
+                                }
+                                engine.timeline_mut().advance(dt_s);
+                                engine.update();
 
                                 let current_view = {
                                     let mut textures = textures.lock().unwrap();
@@ -163,11 +200,13 @@ mod offthread {
                 }
             });
 
-            Self {
+            new_engine_rx.recv()??;
+
+            Ok(Self {
                 target_format,
                 command_tx,
                 textures,
-            }
+            })
         }
     }
 
@@ -7,7 +7,7 @@ use winit::{
     window::{Window, WindowId},
 };
 
-use ab3de_engine::Engine;
+use ab3de_engine::{Engine, SceneDescription};
 use ab3de_internal_shared::camera_controller::{CameraController, CameraControllerInput};
 
 use crate::utils;
@@ -16,24 +16,32 @@ pub fn run(
     instance: wgpu::Instance,
     request_adapter_options: wgpu::RequestAdapterOptions<'static, 'static>,
     device_descriptor: wgpu::DeviceDescriptor<'static>,
+    scene: SceneDescription,
 ) -> anyhow::Result<()> {
     let event_loop = winit::event_loop::EventLoop::with_user_event().build()?;
 
-    let mut handler = WinitWindowHandler::new(instance, request_adapter_options, device_descriptor);
+    let mut handler =
+        WinitWindowHandler::new(instance, request_adapter_options, device_descriptor, scene);
 
     event_loop.run_app(&mut handler)?;
 
-    Ok(())
+    match handler.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
 pub struct WinitWindowHandler {
     instance: wgpu::Instance,
     request_adapter_options: wgpu::RequestAdapterOptions<'static, 'static>,
     device_descriptor: wgpu::DeviceDescriptor<'static>,
+    scene: SceneDescription,
 
     state: State,
 
     window: Option<Arc<Window>>,
+    /// Set when the engine could not be created, which ends the event loop.
+    error: Option<anyhow::Error>,
 }
 
 enum State {
@@ -55,13 +63,16 @@ impl WinitWindowHandler {
         instance: wgpu::Instance,
         request_adapter_options: wgpu::RequestAdapterOptions<'static, 'static>,
         device_descriptor: wgpu::DeviceDescriptor<'static>,
+        scene: SceneDescription,
     ) -> Self {
         Self {
             instance,
             request_adapter_options,
             device_descriptor,
+            scene,
             state: State::Uninitialized,
             window: None,
+            error: None,
         }
     }
 }
@@ -93,15 +104,29 @@ impl ApplicationHandler for WinitWindowHandler {
             pollster::block_on(device_future).unwrap()
         };
 
-        let oev =
-            offthread::OffthreadEngineAndViewport::new(device, queue, surface, adapter, window);
+        let oev = offthread::OffthreadEngineAndViewport::new(
+            device,
+            queue,
+            surface,
+            adapter,
+            window,
+            self.scene.clone(),
+        );
+        let oev = match oev {
+            Ok(oev) => oev,
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+                return;
+            }
+        };
 
         self.state = State::Ready(StateReady {
             oev,
             camera_controller: CameraController::default(),
             update_time_ms: utils::now_ms(),
             new_size: None,
-            camera_data: ab3de_engine::CameraData::default(),
+            camera_data: self.scene.camera_data(),
         });
     }
 
@@ -291,7 +316,8 @@ mod offthread {
             surface: wgpu::Surface<'static>,
             adapter: wgpu::Adapter,
             window: Arc<Window>,
-        ) -> Self {
+            scene: SceneDescription,
+        ) -> anyhow::Result<Self> {
             let size = window.inner_size();
 
             let surface_caps = surface.get_capabilities(&adapter);
@@ -317,8 +343,22 @@ mod offthread {
 
             let (command_tx, command_rx) = std::sync::mpsc::sync_channel::<Command>(0);
 
+            // The engine is not `Send`, so it is created on the render thread,
+            // which reports back whether that succeeded.
+            let (new_engine_tx, new_engine_rx) = std::sync::mpsc::sync_channel(1);
+
             std::thread::spawn(move || {
-                let mut engine = Engine::try_new(device.clone(), queue.clone()).unwrap();
+                let mut engine =
+                    match Engine::try_new_from_scene(device.clone(), queue.clone(), &scene) {
+                        Ok(engine) => {
+                            let _ = new_engine_tx.send(Ok(()));
+                            engine
+                        }
+                        Err(e) => {
+                            let _ = new_engine_tx.send(Err(e));
+                            return;
+                        }
+                    };
                 let mut viewport = engine.make_viewport(ViewportConfiguration {
                     size: (size.width, size.height).into(),
                     color_format: surface_format,
@@ -348,7 +388,11 @@ mod offthread {
                                 *camera_data_ref = camera_data;
                             });
 
-                            engine.update(update_time_ms, dt_s);
+                            for e in engine.reload_changed() {
+                                log::error!("{:#}", e);
+                            }
+                            engine.timeline_mut().advance(dt_s);
+                            engine.update();
 
                             match surface.get_current_texture() {
                                 Ok(output_texture) => {
@@ -377,7 +421,9 @@ mod offthread {
                 }
             });
 
-            Self { command_tx }
+            new_engine_rx.recv()??;
+
+            Ok(Self { command_tx })
         }
 
         pub fn try_update_and_render(
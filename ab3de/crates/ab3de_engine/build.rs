@@ -5,6 +5,14 @@ fn main() {
         &"package::render::model_demo".parse().unwrap(),
         "render_model_demo",
     );
+    wesl.build_artifact(
+        &"package::render::model_toon".parse().unwrap(),
+        "render_model_toon",
+    );
+    wesl.build_artifact(
+        &"package::render::model_toon_edge".parse().unwrap(),
+        "render_model_toon_edge",
+    );
     wesl.build_artifact(
         &"package::render::depth_debug".parse().unwrap(),
         "render_depth_debug",
@@ -25,4 +33,8 @@ fn main() {
         &"package::compute::equirectangular".parse().unwrap(),
         "compute_equirectangular",
     );
+    wesl.build_artifact(
+        &"package::compute::skinning".parse().unwrap(),
+        "compute_skinning",
+    );
 }
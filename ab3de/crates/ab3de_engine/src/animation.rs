@@ -0,0 +1,4 @@
+pub mod morph;
+pub mod motion;
+pub mod physics;
+pub mod skeleton;
@@ -0,0 +1,295 @@
+use crate::{
+    animation::skeleton::BoneTransform,
+    drawing::models::MaterialUniform,
+    model_loaders::model_data::{MorphData, MorphKind},
+};
+
+/// Group morphs nested deeper than this are ignored, which also stops
+/// groups that contain themselves.
+const MAX_GROUP_DEPTH: usize = 8;
+
+/// Resolves group morphs into the weights of the morphs they drive, which
+/// add up with the weights those morphs were set to directly. Group morphs
+/// themselves end up with a weight of `0`.
+pub fn effective_weights(morphs: &[MorphData], weights: &[f32], effective: &mut Vec<f32>) {
+    effective.clear();
+    effective.resize(morphs.len(), 0.0);
+
+    for (morph_index, &weight) in weights.iter().enumerate() {
+        add_weight(morphs, morph_index, weight, 0, effective);
+    }
+}
+
+fn add_weight(
+    morphs: &[MorphData],
+    morph_index: usize,
+    weight: f32,
+    depth: usize,
+    effective: &mut [f32],
+) {
+    if weight == 0.0 {
+        return;
+    }
+
+    match &morphs[morph_index].kind {
+        MorphKind::Group(offsets) => {
+            if depth >= MAX_GROUP_DEPTH {
+                return;
+            }
+            for offset in offsets {
+                add_weight(
+                    morphs,
+                    offset.morph,
+                    weight * offset.weight,
+                    depth + 1,
+                    effective,
+                );
+            }
+        }
+        _ => effective[morph_index] += weight,
+    }
+}
+
+/// Moves and rotates the bones of `pose` by the bone morphs, on top of
+/// whatever they were posed to.
+pub fn apply_bone_morphs(morphs: &[MorphData], effective: &[f32], pose: &mut [BoneTransform]) {
+    for (morph, &weight) in morphs.iter().zip(effective) {
+        let MorphKind::Bone(offsets) = &morph.kind else {
+            continue;
+        };
+        if weight == 0.0 {
+            continue;
+        }
+
+        for offset in offsets {
+            let Some(transform) = pose.get_mut(offset.bone) else {
+                continue;
+            };
+            transform.translation += offset.translation * weight;
+            transform.rotation = (glam::Quat::IDENTITY.slerp(offset.rotation, weight)
+                * transform.rotation)
+                .normalize();
+        }
+    }
+}
+
+/// Applies the material morphs to `uniforms`, which has one uniform per
+/// material, starting from the material's own colors.
+pub fn apply_material_morphs(
+    morphs: &[MorphData],
+    effective: &[f32],
+    uniforms: &mut [MaterialUniform],
+) {
+    for (morph, &weight) in morphs.iter().zip(effective) {
+        let MorphKind::Material(offsets) = &morph.kind else {
+            continue;
+        };
+        if weight == 0.0 {
+            continue;
+        }
+
+        for offset in offsets {
+            match offset.material {
+                Some(material_index) => {
+                    if let Some(uniform) = uniforms.get_mut(material_index) {
+                        uniform.apply_morph(offset, weight);
+                    }
+                }
+                None => {
+                    for uniform in uniforms.iter_mut() {
+                        uniform.apply_morph(offset, weight);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn has_material_morphs(morphs: &[MorphData]) -> bool {
+    morphs
+        .iter()
+        .any(|morph| matches!(morph.kind, MorphKind::Material(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::{
+        BoneMorphOffsetData, GroupMorphOffsetData, MaterialData, MaterialMorphOffsetData,
+        MaterialMorphOperation,
+    };
+
+    fn morph(kind: MorphKind) -> MorphData {
+        MorphData {
+            name: String::new(),
+            kind,
+        }
+    }
+
+    fn group(offsets: &[(usize, f32)]) -> MorphData {
+        morph(MorphKind::Group(
+            offsets
+                .iter()
+                .map(|&(morph, weight)| GroupMorphOffsetData { morph, weight })
+                .collect(),
+        ))
+    }
+
+    fn effective(morphs: &[MorphData], weights: &[f32]) -> Vec<f32> {
+        let mut effective = vec![];
+        effective_weights(morphs, weights, &mut effective);
+        effective
+    }
+
+    #[test]
+    fn group_morphs_drive_nested_groups() {
+        let morphs = [
+            morph(MorphKind::Vertex(vec![])),
+            morph(MorphKind::Vertex(vec![])),
+            group(&[(0, 0.5), (1, 1.0)]),
+            group(&[(2, 0.5), (0, 1.0)]),
+        ];
+
+        assert_eq!(
+            effective(&morphs, &[0.25, 0.0, 1.0, 0.5]),
+            [
+                0.25 + 1.0 * 0.5 + 0.5 * 0.5 * 0.5 + 0.5,
+                1.0 + 0.5 * 0.5,
+                0.0,
+                0.0
+            ]
+        );
+        assert_eq!(effective(&morphs, &[]), [0.0; 4]);
+    }
+
+    #[test]
+    fn group_morphs_that_contain_themselves_stop() {
+        let morphs = [
+            group(&[(0, 1.0), (1, 1.0)]),
+            morph(MorphKind::Vertex(vec![])),
+        ];
+        // once for each level of nesting that is still followed.
+        assert_eq!(
+            effective(&morphs, &[1.0, 0.0]),
+            [0.0, MAX_GROUP_DEPTH as f32]
+        );
+
+        let morphs = [
+            group(&[(1, 1.0)]),
+            group(&[(0, 1.0), (2, 0.5)]),
+            morph(MorphKind::Vertex(vec![])),
+        ];
+        assert_eq!(
+            effective(&morphs, &[1.0, 0.0, 0.0]),
+            [0.0, 0.0, (MAX_GROUP_DEPTH / 2) as f32 * 0.5]
+        );
+    }
+
+    #[test]
+    fn bone_morphs_apply_on_top_of_the_pose() {
+        let morphs = [
+            morph(MorphKind::Bone(vec![
+                BoneMorphOffsetData {
+                    bone: 0,
+                    translation: glam::vec3(0.0, 2.0, 0.0),
+                    rotation: glam::Quat::from_rotation_z(1.0),
+                },
+                BoneMorphOffsetData {
+                    bone: 5,
+                    translation: glam::Vec3::ONE,
+                    rotation: glam::Quat::IDENTITY,
+                },
+            ])),
+            morph(MorphKind::Bone(vec![BoneMorphOffsetData {
+                bone: 1,
+                translation: glam::Vec3::ONE,
+                rotation: glam::Quat::from_rotation_x(1.0),
+            }])),
+            group(&[(0, 0.5)]),
+        ];
+        let posed = BoneTransform {
+            translation: glam::vec3(1.0, 0.0, 0.0),
+            rotation: glam::Quat::from_rotation_z(0.5),
+        };
+        let mut pose = [posed, BoneTransform::IDENTITY];
+
+        apply_bone_morphs(&morphs, &effective(&morphs, &[0.0, 0.0, 1.0]), &mut pose);
+
+        assert!(
+            pose[0]
+                .translation
+                .abs_diff_eq(glam::vec3(1.0, 1.0, 0.0), 1e-6)
+        );
+        assert!(
+            pose[0]
+                .rotation
+                .abs_diff_eq(glam::Quat::from_rotation_z(1.0), 1e-6)
+        );
+        assert_eq!(pose[1], BoneTransform::IDENTITY);
+    }
+
+    #[test]
+    fn material_morphs_multiply_then_add() {
+        let multiply = MaterialMorphOffsetData {
+            material: Some(1),
+            operation: MaterialMorphOperation::Multiply,
+            diffuse_color: glam::vec4(0.5, 0.5, 0.5, 1.0),
+            specular_color: glam::Vec3::ONE,
+            specular_power: 1.0,
+            ambient_color: glam::Vec3::ONE,
+            edge_color: glam::Vec4::ONE,
+            edge_size: 1.0,
+            texture_tint: glam::Vec4::ONE,
+            sphere_tint: glam::Vec4::ONE,
+            toon_tint: glam::Vec4::ONE,
+        };
+        let add = MaterialMorphOffsetData {
+            material: None,
+            operation: MaterialMorphOperation::Add,
+            diffuse_color: glam::vec4(0.0, 0.0, 0.25, 0.0),
+            specular_color: glam::Vec3::ZERO,
+            specular_power: 0.0,
+            ambient_color: glam::vec3(0.5, 0.0, 0.0),
+            edge_color: glam::Vec4::ZERO,
+            edge_size: 0.0,
+            texture_tint: glam::Vec4::ZERO,
+            sphere_tint: glam::Vec4::ZERO,
+            toon_tint: glam::Vec4::ZERO,
+        };
+        let morphs = [
+            morph(MorphKind::Material(vec![
+                multiply,
+                MaterialMorphOffsetData {
+                    material: Some(2),
+                    ..add
+                },
+            ])),
+            morph(MorphKind::Material(vec![add])),
+        ];
+        let material = |diffuse_color, ambient_color| {
+            MaterialUniform::from(&MaterialData {
+                diffuse_color,
+                ambient_color,
+                ..Default::default()
+            })
+        };
+        let mut uniforms = [
+            material(glam::Vec4::ONE, glam::Vec3::ZERO),
+            material(glam::Vec4::ONE, glam::Vec3::ZERO),
+        ];
+
+        apply_material_morphs(&morphs, &[0.5, 0.5], &mut uniforms);
+
+        let expected = [
+            material(glam::vec4(1.0, 1.0, 1.125, 1.0), glam::vec3(0.25, 0.0, 0.0)),
+            material(
+                glam::vec4(0.75, 0.75, 0.875, 1.0),
+                glam::vec3(0.25, 0.0, 0.0),
+            ),
+        ];
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&uniforms),
+            bytemuck::cast_slice::<_, u8>(&expected)
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    animation::skeleton::BoneTransform,
+    formats::vmd::{VMD_FPS, VmdBoneInterpolation, VmdMotion, VmdName},
+    model_loaders::{
+        model_data::{BoneData, MorphData},
+        source_space::SourceSpace,
+    },
+};
+
+/// Bone and morph keyframes, not tied to any model. Tracks find their bones
+/// and morphs by name when bound with [`MotionPlayer::new`]. In engine space,
+/// like the models loaded for it.
+#[derive(Debug, Clone)]
+pub struct Motion {
+    bone_tracks: Vec<BoneTrack>,
+    morph_tracks: Vec<MorphTrack>,
+    ik_tracks: Vec<IkTrack>,
+    /// Of the last keyframe.
+    last_frame: u32,
+}
+
+#[derive(Debug, Clone)]
+struct BoneTrack {
+    bone_name: VmdName,
+    /// Sorted by frame, one per frame.
+    keyframes: Vec<BoneKeyframe>,
+}
+
+#[derive(Debug, Clone)]
+struct BoneKeyframe {
+    frame: u32,
+    transform: BoneTransform,
+    interpolation: VmdBoneInterpolation,
+}
+
+#[derive(Debug, Clone)]
+struct MorphTrack {
+    morph_name: VmdName,
+    /// Sorted by frame, one per frame.
+    keyframes: Vec<MorphKeyframe>,
+}
+
+#[derive(Debug, Clone)]
+struct MorphKeyframe {
+    frame: u32,
+    weight: f32,
+}
+
+/// Switches an IK bone on or off. Not interpolated.
+#[derive(Debug, Clone)]
+struct IkTrack {
+    bone_name: VmdName,
+    /// Sorted by frame, one per frame.
+    keyframes: Vec<IkKeyframe>,
+}
+
+#[derive(Debug, Clone)]
+struct IkKeyframe {
+    frame: u32,
+    is_enabled: bool,
+}
+
+impl Motion {
+    pub fn from_vmd(vmd: &VmdMotion) -> Self {
+        let conversion = SourceSpace::MMD.conversion();
+
+        let mut bone_tracks = Vec::<BoneTrack>::new();
+        let mut bone_track_indices = HashMap::new();
+        for keyframe in &vmd.bone_keyframes {
+            let track_index = *bone_track_indices
+                .entry(&keyframe.bone_name)
+                .or_insert_with(|| {
+                    bone_tracks.push(BoneTrack {
+                        bone_name: keyframe.bone_name.clone(),
+                        keyframes: vec![],
+                    });
+                    bone_tracks.len() - 1
+                });
+            bone_tracks[track_index].keyframes.push(BoneKeyframe {
+                frame: keyframe.frame,
+                transform: BoneTransform {
+                    translation: conversion.vector(keyframe.translation),
+                    rotation: conversion.rotation(keyframe.rotation.normalize()),
+                },
+                interpolation: keyframe.interpolation,
+            });
+        }
+
+        let mut morph_tracks = Vec::<MorphTrack>::new();
+        let mut morph_track_indices = HashMap::new();
+        for keyframe in &vmd.morph_keyframes {
+            let track_index = *morph_track_indices
+                .entry(&keyframe.morph_name)
+                .or_insert_with(|| {
+                    morph_tracks.push(MorphTrack {
+                        morph_name: keyframe.morph_name.clone(),
+                        keyframes: vec![],
+                    });
+                    morph_tracks.len() - 1
+                });
+            morph_tracks[track_index].keyframes.push(MorphKeyframe {
+                frame: keyframe.frame,
+                weight: keyframe.weight,
+            });
+        }
+
+        let mut ik_tracks = Vec::<IkTrack>::new();
+        let mut ik_track_indices = HashMap::new();
+        for keyframe in &vmd.ik_keyframes {
+            for ik_state in &keyframe.ik_states {
+                let track_index =
+                    *ik_track_indices
+                        .entry(&ik_state.bone_name)
+                        .or_insert_with(|| {
+                            ik_tracks.push(IkTrack {
+                                bone_name: ik_state.bone_name.clone(),
+                                keyframes: vec![],
+                            });
+                            ik_tracks.len() - 1
+                        });
+                ik_tracks[track_index].keyframes.push(IkKeyframe {
+                    frame: keyframe.frame,
+                    is_enabled: ik_state.is_enabled,
+                });
+            }
+        }
+
+        // when a frame is keyed twice, the later keyframe in the file wins.
+        for track in &mut bone_tracks {
+            track.keyframes.reverse();
+            track.keyframes.sort_by_key(|keyframe| keyframe.frame);
+            track.keyframes.dedup_by_key(|keyframe| keyframe.frame);
+        }
+        for track in &mut morph_tracks {
+            track.keyframes.reverse();
+            track.keyframes.sort_by_key(|keyframe| keyframe.frame);
+            track.keyframes.dedup_by_key(|keyframe| keyframe.frame);
+        }
+        for track in &mut ik_tracks {
+            track.keyframes.reverse();
+            track.keyframes.sort_by_key(|keyframe| keyframe.frame);
+            track.keyframes.dedup_by_key(|keyframe| keyframe.frame);
+        }
+
+        let last_frame = bone_tracks
+            .iter()
+            .flat_map(|track| track.keyframes.last().map(|keyframe| keyframe.frame))
+            .chain(
+                morph_tracks
+                    .iter()
+                    .flat_map(|track| track.keyframes.last().map(|keyframe| keyframe.frame)),
+            )
+            .max()
+            .unwrap_or(0);
+
+        Self {
+            bone_tracks,
+            morph_tracks,
+            ik_tracks,
+            last_frame,
+        }
+    }
+
+    /// Up to the last keyframe. The motion holds its last pose after that.
+    pub fn duration_s(&self) -> f64 {
+        self.last_frame as f64 / VMD_FPS
+    }
+}
+
+impl BoneTrack {
+    fn sample(&self, frame: f64) -> BoneTransform {
+        let next_index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame as f64 <= frame);
+        let (prev, next) = match (
+            next_index.checked_sub(1).map(|i| &self.keyframes[i]),
+            self.keyframes.get(next_index),
+        ) {
+            (Some(prev), Some(next)) => (prev, next),
+            (Some(keyframe), None) | (None, Some(keyframe)) => return keyframe.transform,
+            (None, None) => return BoneTransform::IDENTITY,
+        };
+
+        let x = ((frame - prev.frame as f64) / (next.frame - prev.frame) as f64) as f32;
+        let curves = &next.interpolation;
+        let weights = glam::vec3(
+            curves.translation_x.evaluate(x),
+            curves.translation_y.evaluate(x),
+            curves.translation_z.evaluate(x),
+        );
+
+        BoneTransform {
+            translation: prev.transform.translation
+                + (next.transform.translation - prev.transform.translation) * weights,
+            rotation: prev
+                .transform
+                .rotation
+                .slerp(next.transform.rotation, curves.rotation.evaluate(x)),
+        }
+    }
+}
+
+impl MorphTrack {
+    fn sample(&self, frame: f64) -> f32 {
+        let next_index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame as f64 <= frame);
+        let (prev, next) = match (
+            next_index.checked_sub(1).map(|i| &self.keyframes[i]),
+            self.keyframes.get(next_index),
+        ) {
+            (Some(prev), Some(next)) => (prev, next),
+            (Some(keyframe), None) | (None, Some(keyframe)) => return keyframe.weight,
+            (None, None) => return 0.0,
+        };
+
+        let x = ((frame - prev.frame as f64) / (next.frame - prev.frame) as f64) as f32;
+        prev.weight + (next.weight - prev.weight) * x
+    }
+}
+
+impl IkTrack {
+    /// `None` before the first keyframe.
+    fn sample(&self, frame: f64) -> Option<bool> {
+        let next_index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame as f64 <= frame);
+        let keyframe = self.keyframes.get(next_index.checked_sub(1)?)?;
+        Some(keyframe.is_enabled)
+    }
+}
+
+/// A [`Motion`] bound to the bones and morphs of one model.
+pub struct MotionPlayer {
+    motion: Arc<Motion>,
+    /// Per bone of the model.
+    bone_tracks: Vec<Option<usize>>,
+    /// Per bone of the model.
+    ik_tracks: Vec<Option<usize>>,
+    /// Per morph of the model.
+    morph_tracks: Vec<Option<usize>>,
+    /// See [`ModelData::motion_scale`](crate::ModelData::motion_scale).
+    translation_scale: f32,
+}
+
+impl MotionPlayer {
+    pub fn new(
+        motion: Arc<Motion>,
+        bones: &[BoneData],
+        morphs: &[MorphData],
+        translation_scale: f32,
+    ) -> Self {
+        let bone_tracks = bones
+            .iter()
+            .map(|bone| {
+                motion
+                    .bone_tracks
+                    .iter()
+                    .position(|track| track.bone_name.matches(&bone.name))
+            })
+            .collect::<Vec<_>>();
+        let ik_tracks = bones
+            .iter()
+            .map(|bone| {
+                motion
+                    .ik_tracks
+                    .iter()
+                    .position(|track| track.bone_name.matches(&bone.name))
+            })
+            .collect::<Vec<_>>();
+        let morph_tracks = morphs
+            .iter()
+            .map(|morph| {
+                motion
+                    .morph_tracks
+                    .iter()
+                    .position(|track| track.morph_name.matches(&morph.name))
+            })
+            .collect::<Vec<_>>();
+
+        let bound_bone_track_count = bone_tracks.iter().flatten().count();
+        let bound_morph_track_count = morph_tracks.iter().flatten().count();
+        log::debug!(
+            "[MotionPlayer::new] bound {}/{} bone tracks and {}/{} morph tracks",
+            bound_bone_track_count,
+            motion.bone_tracks.len(),
+            bound_morph_track_count,
+            motion.morph_tracks.len()
+        );
+
+        Self {
+            motion,
+            bone_tracks,
+            ik_tracks,
+            morph_tracks,
+            translation_scale,
+        }
+    }
+
+    pub fn motion(&self) -> &Arc<Motion> {
+        &self.motion
+    }
+
+    /// Overwrites the bones, IK switches and morphs the motion has tracks
+    /// for. Others keep whatever they were set to.
+    pub fn sample(
+        &self,
+        time_s: f64,
+        pose: &mut [BoneTransform],
+        ik_enabled: &mut [bool],
+        morph_weights: &mut [f32],
+    ) {
+        let frame = time_s.max(0.0) * VMD_FPS;
+
+        for (transform, track_index) in pose.iter_mut().zip(&self.bone_tracks) {
+            if let Some(track_index) = *track_index {
+                *transform = self.motion.bone_tracks[track_index].sample(frame);
+                transform.translation *= self.translation_scale;
+            }
+        }
+        for (is_enabled, track_index) in ik_enabled.iter_mut().zip(&self.ik_tracks) {
+            if let Some(track_index) = *track_index
+                && let Some(is_track_enabled) = self.motion.ik_tracks[track_index].sample(frame)
+            {
+                *is_enabled = is_track_enabled;
+            }
+        }
+        for (weight, track_index) in morph_weights.iter_mut().zip(&self.morph_tracks) {
+            if let Some(track_index) = *track_index {
+                *weight = self.motion.morph_tracks[track_index].sample(frame);
+            }
+        }
+    }
+}
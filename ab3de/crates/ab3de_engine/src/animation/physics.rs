@@ -0,0 +1,504 @@
+use rapier3d::{
+    na,
+    prelude::{
+        CCDSolver, ColliderBuilder, ColliderSet, DefaultBroadPhase, GenericJoint, Group,
+        ImpulseJointSet, IntegrationParameters, InteractionGroups, IslandManager, Isometry,
+        JointAxesMask, JointAxis, MotorModel, MultibodyJointSet, NarrowPhase, PhysicsPipeline,
+        Real, RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
+    },
+};
+
+use crate::{
+    animation::skeleton::{BoneTransform, Skeleton, SkeletonState},
+    model_loaders::model_data::{JointData, RigidBodyData, RigidBodyMode, RigidBodyShape},
+};
+
+/// Physics always advances by this much, whatever the frame rate, so that
+/// the same motion moves hair and cloth the same way on every run.
+pub const PHYSICS_STEP_S: f64 = 1.0 / 60.0;
+
+/// Engine space is in meters.
+const GRAVITY: glam::Vec3 = glam::vec3(0.0, -9.8, 0.0);
+
+/// Simulated bodies need some mass, even when the file says `0`.
+const MIN_MASS: f32 = 1e-3;
+
+/// The rigid bodies and joints of one model entry, simulated from the time
+/// they were created.
+pub struct Physics {
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+
+    /// Per rigid body of the model.
+    rigid_bodies: Vec<PhysicsBody>,
+    /// Per bone, where physics puts it. Reused between calls.
+    bone_matrices: Vec<Option<glam::Mat4>>,
+    step_count: u64,
+}
+
+struct PhysicsBody {
+    handle: RigidBodyHandle,
+    bone: Option<usize>,
+    mode: RigidBodyMode,
+    /// Where the body is relative to its bone, or in model space if it has
+    /// none.
+    bone_offset: glam::Mat4,
+}
+
+impl Physics {
+    /// Starts with every body where `state` puts its bone, at rest.
+    pub fn new(
+        rigid_bodies: &[RigidBodyData],
+        joints: &[JointData],
+        skeleton: &Skeleton,
+        state: &SkeletonState,
+    ) -> Self {
+        let integration_parameters = IntegrationParameters {
+            dt: PHYSICS_STEP_S as Real,
+            ..Default::default()
+        };
+
+        let mut bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        let mut impulse_joints = ImpulseJointSet::new();
+
+        let rest_matrices = rigid_bodies
+            .iter()
+            .map(|body| glam::Mat4::from_rotation_translation(body.rotation, body.position))
+            .collect::<Vec<_>>();
+
+        let rigid_bodies = rigid_bodies
+            .iter()
+            .zip(&rest_matrices)
+            .map(|(body, rest_matrix)| {
+                let bone = body.bone.filter(|&bone| bone < skeleton.bones().len());
+                let bone_offset = match bone {
+                    Some(bone) => {
+                        glam::Mat4::from_translation(-skeleton.bones()[bone].rest_position)
+                            * *rest_matrix
+                    }
+                    None => *rest_matrix,
+                };
+                let world_matrix = match bone {
+                    Some(bone) => state.world_matrices[bone] * bone_offset,
+                    None => bone_offset,
+                };
+
+                let builder = match body.mode {
+                    RigidBodyMode::FollowBone => RigidBodyBuilder::kinematic_position_based(),
+                    RigidBodyMode::Physics | RigidBodyMode::PhysicsWithBonePosition => {
+                        RigidBodyBuilder::dynamic()
+                    }
+                };
+                let handle = bodies.insert(
+                    builder
+                        .position(to_isometry(&world_matrix))
+                        .linear_damping(to_rapier_damping(body.linear_damping))
+                        .angular_damping(to_rapier_damping(body.angular_damping))
+                        .can_sleep(false),
+                );
+
+                let collider = match body.shape {
+                    RigidBodyShape::Sphere { radius } => ColliderBuilder::ball(radius),
+                    RigidBodyShape::Box { half_extents } => {
+                        ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z)
+                    }
+                    RigidBodyShape::Capsule { radius, height } => {
+                        ColliderBuilder::capsule_y(height * 0.5, radius)
+                    }
+                };
+                colliders.insert_with_parent(
+                    collider
+                        .mass(body.mass.max(MIN_MASS))
+                        .restitution(body.restitution)
+                        .friction(body.friction)
+                        .collision_groups(InteractionGroups::new(
+                            Group::from_bits_truncate(1 << body.group),
+                            Group::from_bits_truncate(body.collision_mask as u32),
+                        )),
+                    handle,
+                    &mut bodies,
+                );
+
+                PhysicsBody {
+                    handle,
+                    bone,
+                    mode: body.mode,
+                    bone_offset,
+                }
+            })
+            .collect::<Vec<PhysicsBody>>();
+
+        for joint in joints {
+            let [a, b] = joint.rigid_bodies;
+            let (Some(body_a), Some(body_b)) = (rigid_bodies.get(a), rigid_bodies.get(b)) else {
+                continue;
+            };
+
+            let joint_matrix =
+                glam::Mat4::from_rotation_translation(joint.rotation, joint.position);
+            let mut generic_joint = make_joint(joint);
+            generic_joint
+                .set_local_frame1(to_isometry(&(rest_matrices[a].inverse() * joint_matrix)))
+                .set_local_frame2(to_isometry(&(rest_matrices[b].inverse() * joint_matrix)))
+                .set_contacts_enabled(false);
+            impulse_joints.insert(body_a.handle, body_b.handle, generic_joint, true);
+        }
+
+        Self {
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters,
+            islands: IslandManager::new(),
+            broad_phase: DefaultBroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies,
+            colliders,
+            impulse_joints,
+            multibody_joints: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+            rigid_bodies,
+            bone_matrices: vec![None; skeleton.bones().len()],
+            step_count: 0,
+        }
+    }
+
+    /// Since [`Self::new`], each [`PHYSICS_STEP_S`] long.
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// Moves the bodies that follow their bones to where `state` puts them,
+    /// then simulates one step.
+    pub fn step(&mut self, state: &SkeletonState) {
+        for body in &self.rigid_bodies {
+            if body.mode != RigidBodyMode::FollowBone {
+                continue;
+            }
+            let Some(bone) = body.bone else {
+                continue;
+            };
+            if let Some(rigid_body) = self.bodies.get_mut(body.handle) {
+                rigid_body.set_next_kinematic_position(to_isometry(
+                    &(state.world_matrices[bone] * body.bone_offset),
+                ));
+            }
+        }
+
+        self.pipeline.step(
+            &na::Vector3::new(GRAVITY.x, GRAVITY.y, GRAVITY.z),
+            &self.integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            None,
+            &(),
+            &(),
+        );
+        self.step_count += 1;
+    }
+
+    /// Moves the bones of simulated bodies to where the bodies are, see
+    /// [`Skeleton::apply_physics`].
+    pub fn apply(
+        &mut self,
+        skeleton: &Skeleton,
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        self.bone_matrices.fill(None);
+        for body in &self.rigid_bodies {
+            let Some(bone) = body.bone else {
+                continue;
+            };
+            let Some(rigid_body) = self.bodies.get(body.handle) else {
+                continue;
+            };
+
+            let bone_matrix = from_isometry(rigid_body.position()) * body.bone_offset.inverse();
+            self.bone_matrices[bone] = match body.mode {
+                RigidBodyMode::FollowBone => continue,
+                RigidBodyMode::Physics => Some(bone_matrix),
+                RigidBodyMode::PhysicsWithBonePosition => {
+                    let (_, rotation, _) = bone_matrix.to_scale_rotation_translation();
+                    let translation = state.world_matrices[bone].w_axis.truncate();
+                    Some(glam::Mat4::from_rotation_translation(rotation, translation))
+                }
+            };
+        }
+
+        skeleton.apply_physics(&self.bone_matrices, pose, state);
+    }
+}
+
+/// Steps `physics` up to `time_s`, creating it first if there is none or it
+/// is already past that. `evaluate_at` evaluates the skeleton before physics
+/// into the given state, with the pose at the given time. Each step uses the
+/// pose at its own time, so where the bodies end up only depends on `time_s`.
+pub fn simulate_until<'a>(
+    physics: &'a mut Option<Physics>,
+    time_s: f64,
+    rigid_bodies: &[RigidBodyData],
+    joints: &[JointData],
+    skeleton: &Skeleton,
+    state: &mut SkeletonState,
+    mut evaluate_at: impl FnMut(f64, &mut SkeletonState),
+) -> &'a mut Physics {
+    let step_count = (time_s.max(0.0) / PHYSICS_STEP_S) as u64;
+    if physics
+        .as_ref()
+        .is_some_and(|physics| physics.step_count() > step_count)
+    {
+        *physics = None;
+    }
+    let physics = match physics {
+        Some(physics) => physics,
+        None => {
+            evaluate_at(0.0, state);
+            physics.insert(Physics::new(rigid_bodies, joints, skeleton, state))
+        }
+    };
+
+    while physics.step_count() < step_count {
+        evaluate_at((physics.step_count() + 1) as f64 * PHYSICS_STEP_S, state);
+        physics.step(state);
+    }
+
+    physics
+}
+
+/// An axis is locked when its limits are equal, limited when the minimum is
+/// smaller and free otherwise. Springs pull back to the joint's frame.
+fn make_joint(joint: &JointData) -> GenericJoint {
+    let mut generic_joint = GenericJoint::new(JointAxesMask::empty());
+    let mut locked_axes = JointAxesMask::empty();
+
+    let axes = [
+        (JointAxis::LinX, JointAxis::AngX),
+        (JointAxis::LinY, JointAxis::AngY),
+        (JointAxis::LinZ, JointAxis::AngZ),
+    ];
+    for (i, (linear_axis, angular_axis)) in axes.into_iter().enumerate() {
+        for (axis, min, max, spring) in [
+            (
+                linear_axis,
+                joint.linear_min[i],
+                joint.linear_max[i],
+                joint.linear_spring[i],
+            ),
+            (
+                angular_axis,
+                joint.angular_min[i],
+                joint.angular_max[i],
+                joint.angular_spring[i],
+            ),
+        ] {
+            if min == max {
+                locked_axes |= axis.into();
+                continue;
+            }
+            if min < max {
+                generic_joint.set_limits(axis, [min, max]);
+            }
+            if spring != 0.0 {
+                generic_joint
+                    .set_motor_model(axis, MotorModel::ForceBased)
+                    .set_motor(axis, 0.0, 0.0, spring, 0.0);
+            }
+        }
+    }
+
+    generic_joint.lock_axes(locked_axes);
+    generic_joint
+}
+
+/// Bullet keeps `(1 - damping)^dt` of the velocity each step, rapier
+/// `1 / (1 + dt * damping)`.
+fn to_rapier_damping(bullet_damping: f32) -> Real {
+    let dt = PHYSICS_STEP_S as f32;
+    let kept = (1.0 - bullet_damping.clamp(0.0, 0.999)).powf(dt);
+    (1.0 / kept - 1.0) / dt
+}
+
+fn to_isometry(matrix: &glam::Mat4) -> Isometry<Real> {
+    let (_, rotation, translation) = matrix.to_scale_rotation_translation();
+    Isometry::from_parts(
+        na::Translation3::new(translation.x, translation.y, translation.z),
+        na::UnitQuaternion::new_normalize(na::Quaternion::new(
+            rotation.w, rotation.x, rotation.y, rotation.z,
+        )),
+    )
+}
+
+fn from_isometry(isometry: &Isometry<Real>) -> glam::Mat4 {
+    let translation = isometry.translation.vector;
+    let rotation = isometry.rotation.coords;
+    glam::Mat4::from_rotation_translation(
+        glam::Quat::from_xyzw(rotation.x, rotation.y, rotation.z, rotation.w),
+        glam::vec3(translation.x, translation.y, translation.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::BoneData;
+
+    /// A root bone that the pose swings around, and a bone below it hanging
+    /// from it on a simulated body.
+    fn pendulum() -> (Skeleton, Vec<RigidBodyData>, Vec<JointData>) {
+        let bone = |parent, rest_position| BoneData {
+            name: String::new(),
+            parent,
+            rest_position,
+            layer: 0,
+            deforms_after_physics: false,
+            ik: None,
+            inherit: None,
+            fixed_axis: None,
+            local_axes: None,
+        };
+        let skeleton = Skeleton::new(vec![
+            bone(None, glam::vec3(0.0, 2.0, 0.0)),
+            bone(Some(0), glam::vec3(0.0, 1.0, 0.0)),
+        ]);
+
+        let body = |bone, position, mode| RigidBodyData {
+            name: String::new(),
+            bone: Some(bone),
+            group: 0,
+            collision_mask: 0,
+            shape: RigidBodyShape::Sphere { radius: 0.2 },
+            position,
+            rotation: glam::Quat::IDENTITY,
+            mass: 1.0,
+            linear_damping: 0.5,
+            angular_damping: 0.5,
+            restitution: 0.0,
+            friction: 0.5,
+            mode,
+        };
+        let rigid_bodies = vec![
+            body(0, glam::vec3(0.0, 2.0, 0.0), RigidBodyMode::FollowBone),
+            body(1, glam::vec3(0.0, 1.0, 0.0), RigidBodyMode::Physics),
+        ];
+
+        let joints = vec![JointData {
+            name: String::new(),
+            rigid_bodies: [0, 1],
+            position: glam::vec3(0.0, 2.0, 0.0),
+            rotation: glam::Quat::IDENTITY,
+            linear_min: glam::Vec3::ZERO,
+            linear_max: glam::Vec3::ZERO,
+            angular_min: glam::Vec3::splat(-1.0),
+            angular_max: glam::Vec3::splat(1.0),
+            linear_spring: glam::Vec3::ZERO,
+            angular_spring: glam::Vec3::splat(5.0),
+        }];
+
+        (skeleton, rigid_bodies, joints)
+    }
+
+    fn pose_at(time_s: f64) -> Vec<BoneTransform> {
+        let time_s = time_s as f32;
+        vec![
+            BoneTransform {
+                translation: glam::vec3((time_s * 2.0).sin(), 0.0, 0.0),
+                rotation: glam::Quat::from_rotation_z((time_s * 3.0).sin() * 0.5),
+            },
+            BoneTransform::IDENTITY,
+        ]
+    }
+
+    /// What a model entry keeps between frames.
+    #[derive(Default)]
+    struct Entry {
+        physics: Option<Physics>,
+        state: SkeletonState,
+    }
+
+    impl Entry {
+        /// Evaluates the skeleton at `time_s` as the model system does.
+        fn evaluate(
+            &mut self,
+            (skeleton, rigid_bodies, joints): &(Skeleton, Vec<RigidBodyData>, Vec<JointData>),
+            time_s: f64,
+        ) -> Vec<glam::Mat4> {
+            let physics = simulate_until(
+                &mut self.physics,
+                time_s,
+                rigid_bodies,
+                joints,
+                skeleton,
+                &mut self.state,
+                |time_s, state| skeleton.evaluate_before_physics(&pose_at(time_s), &[], state),
+            );
+
+            let pose = pose_at(time_s);
+            skeleton.evaluate_before_physics(&pose, &[], &mut self.state);
+            physics.apply(skeleton, &pose, &mut self.state);
+            skeleton.evaluate_after_physics(&pose, &[], &mut self.state);
+            self.state.world_matrices.clone()
+        }
+    }
+
+    #[test]
+    fn fresh_simulations_agree() {
+        let model = pendulum();
+
+        let mut entry_a = Entry::default();
+        let mut entry_b = Entry::default();
+        for frame in 0..=45 {
+            let time_s = frame as f64 / 30.0;
+            assert_eq!(
+                entry_a.evaluate(&model, time_s),
+                entry_b.evaluate(&model, time_s),
+                "at {}s",
+                time_s
+            );
+        }
+
+        // the body swung away from where its bone would be without physics.
+        let mut rigid = SkeletonState::default();
+        model.0.evaluate(&pose_at(1.5), &[], &mut rigid);
+        let simulated = entry_a.evaluate(&model, 1.5);
+        assert!(!simulated[1].abs_diff_eq(rigid.world_matrices[1], 1e-3));
+        assert_eq!(simulated[0], rigid.world_matrices[0]);
+    }
+
+    #[test]
+    fn seeking_back_and_forth_replays_the_same_steps() {
+        let model = pendulum();
+
+        let mut seeking = Entry::default();
+        seeking.evaluate(&model, 2.0);
+        assert_eq!(
+            seeking.evaluate(&model, 0.5),
+            Entry::default().evaluate(&model, 0.5)
+        );
+        assert_eq!(
+            seeking.evaluate(&model, 1.5),
+            Entry::default().evaluate(&model, 1.5)
+        );
+        assert_eq!(seeking.physics.as_ref().unwrap().step_count(), 90);
+
+        // frame by frame gets to the same place as a single jump.
+        let mut playing = Entry::default();
+        for frame in 0..45 {
+            playing.evaluate(&model, frame as f64 / 30.0);
+        }
+        assert_eq!(playing.evaluate(&model, 1.5), seeking.evaluate(&model, 1.5));
+    }
+}
@@ -0,0 +1,846 @@
+use crate::model_loaders::model_data::{BoneData, IkData, IkLimitsData};
+
+/// A bone's pose, relative to its rest pose and in its parent's space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneTransform {
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+}
+
+impl BoneTransform {
+    pub const IDENTITY: Self = Self {
+        translation: glam::Vec3::ZERO,
+        rotation: glam::Quat::IDENTITY,
+    };
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// The bone hierarchy of a model, shared by every instance of it.
+pub struct Skeleton {
+    bones: Vec<BoneData>,
+    /// Like [`BoneData::parent`], but without the cycles a broken file may
+    /// have. Those bones are treated as roots.
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    /// By deform layer, then by index, as in MMD. Bones that deform after
+    /// physics come last.
+    evaluation_order: Vec<usize>,
+}
+
+/// Everything computed from a pose, kept between frames to reuse the
+/// allocations.
+#[derive(Debug, Default)]
+pub struct SkeletonState {
+    /// In model space, per bone.
+    pub world_matrices: Vec<glam::Mat4>,
+    /// Added on top of the posed rotation by IK, per bone.
+    ik_rotations: Vec<glam::Quat>,
+    /// What each bone got from [`BoneData::inherit`].
+    inherited_rotations: Vec<glam::Quat>,
+    inherited_translations: Vec<glam::Vec3>,
+    /// Euler angles of limited IK links as of the previous iteration, per
+    /// bone.
+    ik_link_angles: Vec<glam::Vec3>,
+}
+
+impl Skeleton {
+    pub fn new(bones: Vec<BoneData>) -> Self {
+        let depth = |mut bone: usize| {
+            let mut depth = 0;
+            while let Some(parent) = bones[bone].parent {
+                depth += 1;
+                if depth > bones.len() {
+                    return None;
+                }
+                bone = parent;
+            }
+            Some(depth)
+        };
+        let depths = (0..bones.len()).map(depth).collect::<Vec<_>>();
+
+        let parents = bones
+            .iter()
+            .zip(&depths)
+            .map(|(bone, depth)| depth.and(bone.parent))
+            .collect::<Vec<_>>();
+        let mut children = vec![vec![]; bones.len()];
+        for (bone_index, parent) in parents.iter().enumerate() {
+            if let Some(parent) = *parent {
+                children[parent].push(bone_index);
+            }
+        }
+
+        // a parent that comes later than its child is the file's problem; the
+        // child then follows the parent's previous frame, like in MMD.
+        let mut evaluation_order = (0..bones.len()).collect::<Vec<_>>();
+        evaluation_order
+            .sort_by_key(|&bone| (bones[bone].deforms_after_physics, bones[bone].layer));
+
+        Self {
+            bones,
+            parents,
+            children,
+            evaluation_order,
+        }
+    }
+
+    pub fn bones(&self) -> &[BoneData] {
+        &self.bones
+    }
+
+    /// Computes each bone's model space matrix for `pose`, which has one
+    /// transform per bone. IK chains are solved when their IK bone comes up,
+    /// if it is enabled in `ik_enabled`.
+    pub fn evaluate(&self, pose: &[BoneTransform], ik_enabled: &[bool], state: &mut SkeletonState) {
+        self.evaluate_before_physics(pose, ik_enabled, state);
+        self.evaluate_after_physics(pose, ik_enabled, state);
+    }
+
+    /// Like [`Self::evaluate`], but only for bones that don't deform after
+    /// physics.
+    pub fn evaluate_before_physics(
+        &self,
+        pose: &[BoneTransform],
+        ik_enabled: &[bool],
+        state: &mut SkeletonState,
+    ) {
+        let bone_count = self.bones.len();
+        // kept from the previous frame, see `evaluation_order`.
+        state
+            .world_matrices
+            .resize(bone_count, glam::Mat4::IDENTITY);
+        state.ik_rotations.clear();
+        state.ik_rotations.resize(bone_count, glam::Quat::IDENTITY);
+        state.ik_link_angles.clear();
+        state.ik_link_angles.resize(bone_count, glam::Vec3::ZERO);
+        state.inherited_rotations.clear();
+        state
+            .inherited_rotations
+            .resize(bone_count, glam::Quat::IDENTITY);
+        state.inherited_translations.clear();
+        state
+            .inherited_translations
+            .resize(bone_count, glam::Vec3::ZERO);
+
+        for &bone_index in &self.evaluation_order {
+            if !self.bones[bone_index].deforms_after_physics {
+                self.evaluate_bone(bone_index, pose, ik_enabled, state);
+            }
+        }
+    }
+
+    /// Puts the bones that have a matrix in `physics_matrices` there, in
+    /// model space, and moves the bones below them along.
+    pub fn apply_physics(
+        &self,
+        physics_matrices: &[Option<glam::Mat4>],
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        let is_followed = |bone_index: &&usize| {
+            physics_matrices
+                .get(**bone_index)
+                .is_none_or(|matrix| matrix.is_none())
+        };
+
+        let mut stack = vec![];
+        for (bone_index, matrix) in physics_matrices.iter().enumerate() {
+            if let Some(matrix) = matrix {
+                state.world_matrices[bone_index] = *matrix;
+                stack.extend(self.children[bone_index].iter().filter(is_followed));
+            }
+        }
+        while let Some(bone_index) = stack.pop() {
+            self.update_world_matrix(bone_index, pose, state);
+            stack.extend(self.children[bone_index].iter().filter(is_followed));
+        }
+    }
+
+    /// Evaluates the bones [`Self::evaluate_before_physics`] left out.
+    pub fn evaluate_after_physics(
+        &self,
+        pose: &[BoneTransform],
+        ik_enabled: &[bool],
+        state: &mut SkeletonState,
+    ) {
+        for &bone_index in &self.evaluation_order {
+            if self.bones[bone_index].deforms_after_physics {
+                self.evaluate_bone(bone_index, pose, ik_enabled, state);
+            }
+        }
+    }
+
+    fn evaluate_bone(
+        &self,
+        bone_index: usize,
+        pose: &[BoneTransform],
+        ik_enabled: &[bool],
+        state: &mut SkeletonState,
+    ) {
+        self.update_inherited_transform(bone_index, pose, state);
+        self.update_world_matrix(bone_index, pose, state);
+
+        if self.bones[bone_index].ik.is_some()
+            && ik_enabled.get(bone_index).copied().unwrap_or(true)
+        {
+            self.solve_ik(bone_index, pose, state);
+        }
+    }
+
+    /// Moves vertices from the rest pose to where `world_matrix` puts the
+    /// bone.
+    pub fn skinning_matrix(&self, bone_index: usize, world_matrix: &glam::Mat4) -> glam::Mat4 {
+        *world_matrix * glam::Mat4::from_translation(-self.bones[bone_index].rest_position)
+    }
+
+    /// The posed rotation, restricted to [`BoneData::fixed_axis`].
+    fn posed_rotation(&self, bone_index: usize, pose: &[BoneTransform]) -> glam::Quat {
+        let rotation = pose[bone_index].rotation;
+        let Some(axis) = self.bones[bone_index].fixed_axis else {
+            return rotation;
+        };
+
+        let twist = (axis * rotation.xyz().dot(axis)).extend(rotation.w);
+        match twist.try_normalize() {
+            Some(twist) => glam::Quat::from_vec4(twist),
+            None => glam::Quat::IDENTITY,
+        }
+    }
+
+    fn update_inherited_transform(
+        &self,
+        bone_index: usize,
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        let Some(inherit) = &self.bones[bone_index].inherit else {
+            return;
+        };
+        let source = inherit.source;
+        // unless local, a source that inherits itself passes on only that.
+        let passes_on_inherited = !inherit.is_local && self.bones[source].inherit.is_some();
+
+        if inherit.rotation {
+            let rotation = if passes_on_inherited {
+                state.inherited_rotations[source]
+            } else {
+                self.posed_rotation(source, pose)
+            };
+            let rotation = state.ik_rotations[source] * rotation;
+            state.inherited_rotations[bone_index] =
+                glam::Quat::IDENTITY.slerp(rotation, inherit.weight);
+        }
+        if inherit.translation {
+            let translation = if passes_on_inherited {
+                state.inherited_translations[source]
+            } else {
+                pose[source].translation
+            };
+            state.inherited_translations[bone_index] = translation * inherit.weight;
+        }
+    }
+
+    fn local_matrix(
+        &self,
+        bone_index: usize,
+        pose: &[BoneTransform],
+        state: &SkeletonState,
+    ) -> glam::Mat4 {
+        let bone = &self.bones[bone_index];
+        let parent_rest_position = self.parents[bone_index]
+            .map_or(glam::Vec3::ZERO, |parent| self.bones[parent].rest_position);
+
+        glam::Mat4::from_rotation_translation(
+            state.ik_rotations[bone_index]
+                * self.posed_rotation(bone_index, pose)
+                * state.inherited_rotations[bone_index],
+            bone.rest_position - parent_rest_position
+                + pose[bone_index].translation
+                + state.inherited_translations[bone_index],
+        )
+    }
+
+    fn update_world_matrix(
+        &self,
+        bone_index: usize,
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        let parent_matrix = self.parents[bone_index]
+            .map_or(glam::Mat4::IDENTITY, |parent| state.world_matrices[parent]);
+        state.world_matrices[bone_index] =
+            parent_matrix * self.local_matrix(bone_index, pose, state);
+    }
+
+    fn update_world_matrices_from(
+        &self,
+        bone_index: usize,
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        let mut stack = vec![bone_index];
+        while let Some(bone_index) = stack.pop() {
+            self.update_world_matrix(bone_index, pose, state);
+            stack.extend_from_slice(&self.children[bone_index]);
+        }
+    }
+
+    fn world_position(state: &SkeletonState, bone_index: usize) -> glam::Vec3 {
+        state.world_matrices[bone_index].w_axis.truncate()
+    }
+
+    /// Cyclic coordinate descent as MMD does it: each iteration turns every
+    /// link towards the IK bone, and the best iteration is kept.
+    fn solve_ik(&self, ik_bone: usize, pose: &[BoneTransform], state: &mut SkeletonState) {
+        let Some(ik) = &self.bones[ik_bone].ik else {
+            return;
+        };
+
+        for link in &ik.links {
+            state.ik_rotations[link.bone] = glam::Quat::IDENTITY;
+            state.ik_link_angles[link.bone] = glam::Vec3::ZERO;
+        }
+        if let Some(root_link) = ik.links.last() {
+            self.update_world_matrices_from(root_link.bone, pose, state);
+        }
+
+        let mut best_distance = f32::INFINITY;
+        let mut best_rotations = vec![glam::Quat::IDENTITY; ik.links.len()];
+
+        for iteration in 0..ik.loop_count {
+            self.solve_ik_iteration(ik_bone, ik, iteration, pose, state);
+
+            let distance = Self::world_position(state, ik.target)
+                .distance(Self::world_position(state, ik_bone));
+            if distance < best_distance {
+                best_distance = distance;
+                for (best_rotation, link) in best_rotations.iter_mut().zip(&ik.links) {
+                    *best_rotation = state.ik_rotations[link.bone];
+                }
+            } else {
+                for (best_rotation, link) in best_rotations.iter().zip(&ik.links) {
+                    state.ik_rotations[link.bone] = *best_rotation;
+                }
+                if let Some(root_link) = ik.links.last() {
+                    self.update_world_matrices_from(root_link.bone, pose, state);
+                }
+                break;
+            }
+        }
+    }
+
+    fn solve_ik_iteration(
+        &self,
+        ik_bone: usize,
+        ik: &IkData,
+        iteration: u32,
+        pose: &[BoneTransform],
+        state: &mut SkeletonState,
+    ) {
+        let ik_position = Self::world_position(state, ik_bone);
+
+        for link in &ik.links {
+            if link.bone == ik.target {
+                continue;
+            }
+
+            let inverse_link_matrix = state.world_matrices[link.bone].inverse();
+            let to_ik = inverse_link_matrix
+                .transform_point3(ik_position)
+                .normalize_or_zero();
+            let to_target = inverse_link_matrix
+                .transform_point3(Self::world_position(state, ik.target))
+                .normalize_or_zero();
+
+            let angle = to_target.dot(to_ik).clamp(-1.0, 1.0).acos();
+
+            // knees and elbows bend around a single axis.
+            if let Some(limits) = &link.limits
+                && let Some(axis) = single_limited_axis(limits)
+            {
+                let plane_angle = ik_plane_angle(
+                    state.ik_link_angles[link.bone][axis],
+                    angle.min(ik.limit_angle),
+                    to_ik,
+                    to_target,
+                    limits,
+                    axis,
+                    iteration,
+                );
+                state.ik_link_angles[link.bone][axis] = plane_angle;
+                state.ik_rotations[link.bone] =
+                    glam::Quat::from_axis_angle(glam::Vec3::AXES[axis], plane_angle)
+                        * self.posed_rotation(link.bone, pose).inverse();
+                self.update_world_matrices_from(link.bone, pose, state);
+                continue;
+            }
+
+            if angle.to_degrees() < 1e-3 {
+                continue;
+            }
+            let angle = angle.min(ik.limit_angle);
+            let axis = to_target.cross(to_ik).normalize_or_zero();
+            if axis == glam::Vec3::ZERO {
+                continue;
+            }
+
+            let posed_rotation = self.posed_rotation(link.bone, pose);
+            let mut rotation = state.ik_rotations[link.bone]
+                * posed_rotation
+                * glam::Quat::from_axis_angle(axis, angle);
+
+            if let Some(limits) = &link.limits {
+                let previous_angles = state.ik_link_angles[link.bone];
+                let angles = to_euler_xyz(rotation, previous_angles).clamp(limits.min, limits.max)
+                    - previous_angles;
+                let angles = angles.clamp(
+                    glam::Vec3::splat(-ik.limit_angle),
+                    glam::Vec3::splat(ik.limit_angle),
+                ) + previous_angles;
+                rotation = from_euler_xyz(angles);
+                state.ik_link_angles[link.bone] = angles;
+            }
+
+            state.ik_rotations[link.bone] = rotation * posed_rotation.inverse();
+            self.update_world_matrices_from(link.bone, pose, state);
+        }
+    }
+}
+
+/// The axis, if only one of them has a non-empty range.
+fn single_limited_axis(limits: &IkLimitsData) -> Option<usize> {
+    let is_limited = |axis: usize| limits.min[axis] != 0.0 || limits.max[axis] != 0.0;
+    match [is_limited(0), is_limited(1), is_limited(2)] {
+        [true, false, false] => Some(0),
+        [false, true, false] => Some(1),
+        [false, false, true] => Some(2),
+        _ => None,
+    }
+}
+
+/// The link's new angle around `axis`, turned by up to `angle` from
+/// `previous_angle` in whichever direction brings the target closer.
+fn ik_plane_angle(
+    previous_angle: f32,
+    angle: f32,
+    to_ik: glam::Vec3,
+    to_target: glam::Vec3,
+    limits: &IkLimitsData,
+    axis: usize,
+    iteration: u32,
+) -> f32 {
+    let rotation_axis = glam::Vec3::AXES[axis];
+    let dot_positive = (glam::Quat::from_axis_angle(rotation_axis, angle) * to_target).dot(to_ik);
+    let dot_negative = (glam::Quat::from_axis_angle(rotation_axis, -angle) * to_target).dot(to_ik);
+
+    let mut new_angle = if dot_positive > dot_negative {
+        previous_angle + angle
+    } else {
+        previous_angle - angle
+    };
+
+    // the first iteration may bend the wrong way, e.g. a knee starting out
+    // straight. Flip into the allowed range if that helps.
+    let (min, max) = (limits.min[axis], limits.max[axis]);
+    if iteration == 0 && (new_angle < min || new_angle > max) {
+        if -new_angle > min && -new_angle < max {
+            new_angle = -new_angle;
+        } else {
+            let half = (min + max) * 0.5;
+            if (half - new_angle).abs() > (half + new_angle).abs() {
+                new_angle = -new_angle;
+            }
+        }
+    }
+
+    new_angle.clamp(min, max)
+}
+
+/// Angles for X, then Y, then Z. Of the two possible solutions, the one
+/// closer to `previous` is returned, so that limits don't make links flip.
+fn to_euler_xyz(rotation: glam::Quat, previous: glam::Vec3) -> glam::Vec3 {
+    let (z, y, x) = rotation.to_euler(glam::EulerRot::ZYX);
+    let first = glam::vec3(x, y, z);
+
+    let wrap = |angle: f32| {
+        let angle = angle.rem_euclid(std::f32::consts::TAU);
+        if angle > std::f32::consts::PI {
+            angle - std::f32::consts::TAU
+        } else {
+            angle
+        }
+    };
+    let pi = std::f32::consts::PI;
+    let second = glam::vec3(wrap(x + pi), wrap(pi - y), wrap(z + pi));
+
+    let distance = |angles: glam::Vec3| (angles - previous).abs().element_sum();
+    if distance(second) < distance(first) {
+        second
+    } else {
+        first
+    }
+}
+
+fn from_euler_xyz(angles: glam::Vec3) -> glam::Quat {
+    glam::Quat::from_euler(glam::EulerRot::ZYX, angles.z, angles.y, angles.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::{IkLinkData, InheritData};
+
+    fn bone(parent: Option<usize>, inherit: Option<InheritData>) -> BoneData {
+        BoneData {
+            name: String::new(),
+            parent,
+            rest_position: glam::Vec3::ZERO,
+            layer: 0,
+            deforms_after_physics: false,
+            ik: None,
+            inherit,
+            fixed_axis: None,
+            local_axes: None,
+        }
+    }
+
+    fn inherit(source: usize, weight: f32, is_local: bool) -> Option<InheritData> {
+        Some(InheritData {
+            source,
+            weight,
+            rotation: true,
+            translation: true,
+            is_local,
+        })
+    }
+
+    fn evaluate(skeleton: &Skeleton, pose: &[BoneTransform]) -> SkeletonState {
+        let mut state = SkeletonState::default();
+        skeleton.evaluate(pose, &[], &mut state);
+        state
+    }
+
+    fn world_rotation(state: &SkeletonState, bone_index: usize) -> glam::Quat {
+        state.world_matrices[bone_index]
+            .to_scale_rotation_translation()
+            .1
+    }
+
+    fn world_position(state: &SkeletonState, bone_index: usize) -> glam::Vec3 {
+        Skeleton::world_position(state, bone_index)
+    }
+
+    #[test]
+    fn inherits_weighted_rotation_and_translation() {
+        let skeleton = Skeleton::new(vec![bone(None, None), bone(None, inherit(0, 0.5, false))]);
+        let pose = [
+            BoneTransform {
+                translation: glam::vec3(2.0, 0.0, 0.0),
+                rotation: glam::Quat::from_rotation_z(1.0),
+            },
+            BoneTransform::IDENTITY,
+        ];
+        let state = evaluate(&skeleton, &pose);
+
+        assert!(world_rotation(&state, 1).abs_diff_eq(glam::Quat::from_rotation_z(0.5), 1e-6));
+        assert!(world_position(&state, 1).abs_diff_eq(glam::vec3(1.0, 0.0, 0.0), 1e-6));
+    }
+
+    /// Bone 1 inherits from 0 and is posed itself. Bone 2 inherits from 1
+    /// as usual, bone 3 locally. As in saba, local inherits only the
+    /// source's own pose.
+    #[test]
+    fn local_inherit_takes_the_sources_own_pose() {
+        let skeleton = Skeleton::new(vec![
+            bone(None, None),
+            bone(None, inherit(0, 1.0, false)),
+            bone(None, inherit(1, 1.0, false)),
+            bone(None, inherit(1, 1.0, true)),
+        ]);
+        let pose = [
+            BoneTransform {
+                translation: glam::vec3(1.0, 0.0, 0.0),
+                rotation: glam::Quat::from_rotation_z(0.5),
+            },
+            BoneTransform {
+                translation: glam::vec3(0.0, 1.0, 0.0),
+                rotation: glam::Quat::from_rotation_x(0.25),
+            },
+            BoneTransform::IDENTITY,
+            BoneTransform::IDENTITY,
+        ];
+        let state = evaluate(&skeleton, &pose);
+
+        let source_local_rotation =
+            glam::Quat::from_rotation_x(0.25) * glam::Quat::from_rotation_z(0.5);
+        assert!(world_rotation(&state, 1).abs_diff_eq(source_local_rotation, 1e-6));
+
+        // only what bone 1 inherited itself is passed on.
+        assert!(world_rotation(&state, 2).abs_diff_eq(glam::Quat::from_rotation_z(0.5), 1e-6));
+        assert!(world_position(&state, 2).abs_diff_eq(glam::vec3(1.0, 0.0, 0.0), 1e-6));
+
+        // only bone 1's own pose, without what it inherited.
+        assert!(world_rotation(&state, 3).abs_diff_eq(glam::Quat::from_rotation_x(0.25), 1e-6));
+        assert!(world_position(&state, 3).abs_diff_eq(glam::vec3(0.0, 1.0, 0.0), 1e-6));
+    }
+
+    #[test]
+    fn rotations_around_local_axes_follow_the_bone() {
+        let diagonal = glam::vec3(1.0, 1.0, 0.0);
+        let x = diagonal.normalize();
+        let mut arm = bone(None, None);
+        arm.local_axes = Some(glam::Mat3::from_cols(
+            x,
+            glam::Vec3::Z.cross(x),
+            glam::Vec3::Z,
+        ));
+        let mut hand = bone(Some(0), None);
+        hand.rest_position = diagonal;
+        let skeleton = Skeleton::new(vec![arm, hand]);
+
+        // twisting around the arm leaves the hand where it is.
+        let twist = skeleton.bones()[0].rotation_in_local_axes(glam::Quat::from_rotation_x(1.0));
+        let pose = [
+            BoneTransform {
+                translation: glam::Vec3::ZERO,
+                rotation: twist,
+            },
+            BoneTransform::IDENTITY,
+        ];
+        let state = evaluate(&skeleton, &pose);
+        assert!(world_position(&state, 1).abs_diff_eq(diagonal, 1e-5));
+        assert!(twist.xyz().normalize().abs_diff_eq(x, 1e-5));
+
+        // bending around local Z, which is also the parent's Z here, raises it.
+        let bend = skeleton.bones()[0].rotation_in_local_axes(glam::Quat::from_rotation_z(0.5));
+        assert!(bend.abs_diff_eq(glam::Quat::from_rotation_z(0.5), 1e-6));
+
+        let mut unaligned = bone(None, None);
+        assert_eq!(unaligned.rotation_in_local_axes(twist), twist);
+        unaligned.local_axes = Some(glam::Mat3::IDENTITY);
+        assert!(
+            unaligned
+                .rotation_in_local_axes(twist)
+                .abs_diff_eq(twist, 1e-6)
+        );
+    }
+
+    #[test]
+    fn local_inherit_ignores_the_sources_parent() {
+        let skeleton = Skeleton::new(vec![
+            bone(None, None),
+            bone(Some(0), None),
+            bone(None, inherit(1, 1.0, true)),
+        ]);
+        let pose = [
+            BoneTransform {
+                translation: glam::Vec3::ZERO,
+                rotation: glam::Quat::from_rotation_y(1.0),
+            },
+            BoneTransform {
+                translation: glam::Vec3::ZERO,
+                rotation: glam::Quat::from_rotation_x(0.5),
+            },
+            BoneTransform::IDENTITY,
+        ];
+        let state = evaluate(&skeleton, &pose);
+
+        assert!(world_rotation(&state, 2).abs_diff_eq(glam::Quat::from_rotation_x(0.5), 1e-6));
+    }
+
+    #[test]
+    fn fixed_axis_keeps_only_the_twist() {
+        let mut twisted = bone(None, None);
+        twisted.fixed_axis = Some(glam::Vec3::Y);
+        let skeleton = Skeleton::new(vec![twisted]);
+        let pose = [BoneTransform {
+            translation: glam::Vec3::ZERO,
+            rotation: glam::Quat::from_rotation_y(0.5) * glam::Quat::from_rotation_x(0.3),
+        }];
+        let state = evaluate(&skeleton, &pose);
+
+        let (axis, angle) = world_rotation(&state, 0).to_axis_angle();
+        assert!(axis.abs_diff_eq(glam::Vec3::Y, 1e-5));
+        assert!(angle > 0.0);
+    }
+
+    #[test]
+    fn evaluates_by_layer_then_index() {
+        let mut late = bone(None, inherit(1, 1.0, false));
+        late.layer = 1;
+        let skeleton = Skeleton::new(vec![late, bone(None, None)]);
+        assert_eq!(skeleton.evaluation_order, [1, 0]);
+
+        let pose = [
+            BoneTransform::IDENTITY,
+            BoneTransform {
+                translation: glam::Vec3::ZERO,
+                rotation: glam::Quat::from_rotation_z(0.5),
+            },
+        ];
+        let state = evaluate(&skeleton, &pose);
+        assert!(world_rotation(&state, 0).abs_diff_eq(glam::Quat::from_rotation_z(0.5), 1e-6));
+    }
+
+    /// A leg standing on the origin: hip, knee, ankle, and an IK bone for
+    /// the ankle. The knee bends around X only, with its shin going towards
+    /// +Z, within `knee_max_bend` radians.
+    fn leg(knee_max_bend: f32, hip_limits: Option<IkLimitsData>) -> Skeleton {
+        let mut hip = bone(None, None);
+        hip.rest_position = glam::vec3(0.0, 2.0, 0.0);
+        let mut knee = bone(Some(0), None);
+        knee.rest_position = glam::vec3(0.0, 1.0, 0.0);
+        let ankle = bone(Some(1), None);
+        let mut ik_bone = bone(None, None);
+        ik_bone.ik = Some(IkData {
+            target: 2,
+            loop_count: 40,
+            limit_angle: 1.0,
+            links: vec![
+                IkLinkData {
+                    bone: 1,
+                    limits: Some(IkLimitsData {
+                        min: glam::vec3(-knee_max_bend, 0.0, 0.0),
+                        max: glam::vec3(-0.008, 0.0, 0.0),
+                    }),
+                },
+                IkLinkData {
+                    bone: 0,
+                    limits: hip_limits,
+                },
+            ],
+        });
+        Skeleton::new(vec![hip, knee, ankle, ik_bone])
+    }
+
+    fn leg_pose(ik_position: glam::Vec3) -> [BoneTransform; 4] {
+        let mut pose = [BoneTransform::IDENTITY; 4];
+        pose[3].translation = ik_position;
+        pose
+    }
+
+    /// The knee's rotation relative to the hip, as X, Y, Z angles.
+    fn knee_angles(state: &SkeletonState) -> glam::Vec3 {
+        let relative = state.world_matrices[0].inverse() * state.world_matrices[1];
+        let rotation = relative.to_scale_rotation_translation().1;
+        to_euler_xyz(rotation, glam::Vec3::ZERO)
+    }
+
+    #[test]
+    fn ik_moves_the_target_onto_a_reachable_ik_bone() {
+        let skeleton = leg(std::f32::consts::PI, None);
+        for ik_position in [
+            glam::vec3(0.0, 0.5, 0.5),
+            glam::vec3(0.3, 0.8, -0.2),
+            glam::vec3(0.0, 1.0, 0.0),
+        ] {
+            let state = evaluate(&skeleton, &leg_pose(ik_position));
+            let reached = world_position(&state, 2);
+            assert!(
+                reached.distance(ik_position) < 1e-2,
+                "{} {}",
+                ik_position,
+                reached
+            );
+            // the hip stays where it is.
+            assert!(world_position(&state, 0).abs_diff_eq(glam::vec3(0.0, 2.0, 0.0), 1e-6));
+        }
+    }
+
+    #[test]
+    fn knees_bend_the_right_way_within_their_limits() {
+        // straight below the hip, where a knee could bend either way.
+        let state = evaluate(
+            &leg(std::f32::consts::PI, None),
+            &leg_pose(glam::vec3(0.0, 0.5, 0.0)),
+        );
+        assert!(world_position(&state, 2).distance(glam::vec3(0.0, 0.5, 0.0)) < 1e-2);
+        let angles = knee_angles(&state);
+        assert!(angles.x < -0.008, "{}", angles);
+        assert!(angles.y.abs() < 1e-4 && angles.z.abs() < 1e-4, "{}", angles);
+        // the shin goes back, so the knee comes forward.
+        assert!(world_position(&state, 1).z < 0.0);
+
+        // too far for the knee to bend.
+        let state = evaluate(&leg(0.3, None), &leg_pose(glam::vec3(0.0, 0.5, 0.0)));
+        let angles = knee_angles(&state);
+        assert!(
+            angles.x >= -0.3 - 1e-4 && angles.x <= -0.008 + 1e-4,
+            "{}",
+            angles
+        );
+        assert!(world_position(&state, 2).distance(glam::vec3(0.0, 0.5, 0.0)) > 0.1);
+    }
+
+    #[test]
+    fn ik_links_stay_within_their_limits_on_every_axis() {
+        let hip_limits = IkLimitsData {
+            min: glam::vec3(-0.2, -0.1, -0.3),
+            max: glam::vec3(0.2, 0.1, 0.3),
+        };
+        let skeleton = leg(std::f32::consts::PI, Some(hip_limits));
+        for ik_position in [
+            glam::vec3(1.5, 1.0, -1.0),
+            glam::vec3(-1.5, 1.5, 1.0),
+            glam::vec3(0.0, 1.0, 1.5),
+        ] {
+            let state = evaluate(&skeleton, &leg_pose(ik_position));
+            let hip_rotation = state.world_matrices[0].to_scale_rotation_translation().1;
+            let angles = to_euler_xyz(hip_rotation, glam::Vec3::ZERO);
+            assert!(
+                angles.cmpge(hip_limits.min - 1e-4).all()
+                    && angles.cmple(hip_limits.max + 1e-4).all(),
+                "{} {}",
+                ik_position,
+                angles
+            );
+        }
+    }
+
+    #[test]
+    fn disabled_ik_leaves_the_pose_alone() {
+        let skeleton = leg(std::f32::consts::PI, None);
+        let mut pose = leg_pose(glam::vec3(0.0, 0.5, 0.5));
+        pose[1].rotation = glam::Quat::from_rotation_x(-0.5);
+
+        let mut state = SkeletonState::default();
+        skeleton.evaluate(&pose, &[true, true, true, false], &mut state);
+        let mut solved_state = SkeletonState::default();
+        skeleton.evaluate(&pose, &[true, true, true, true], &mut solved_state);
+        assert_ne!(state.world_matrices[2], solved_state.world_matrices[2]);
+
+        let without_ik = Skeleton::new(
+            skeleton
+                .bones()
+                .iter()
+                .cloned()
+                .map(|mut bone| {
+                    bone.ik = None;
+                    bone
+                })
+                .collect(),
+        );
+        assert_eq!(
+            state.world_matrices,
+            evaluate(&without_ik, &pose).world_matrices
+        );
+    }
+
+    #[test]
+    fn euler_angles_round_trip_and_pick_the_closer_solution() {
+        let angles = glam::vec3(0.3, -0.2, 1.1);
+        let rotation = from_euler_xyz(angles);
+        assert!(to_euler_xyz(rotation, glam::Vec3::ZERO).abs_diff_eq(angles, 1e-5));
+
+        // the same rotation, with every angle on the other side.
+        let pi = std::f32::consts::PI;
+        let other = glam::vec3(0.3 - pi, 0.2 - pi, 1.1 - pi);
+        assert!(from_euler_xyz(other).dot(rotation).abs() > 1.0 - 1e-6);
+        assert!(to_euler_xyz(rotation, other).abs_diff_eq(other, 1e-4));
+    }
+}
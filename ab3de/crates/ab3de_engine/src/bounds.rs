@@ -0,0 +1,546 @@
+use crate::model_loaders::model_data::{ModelData, MorphKind};
+
+/// An axis-aligned bounding box. [`Aabb::EMPTY`] contains nothing, and is
+/// what a box around no points is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+/// A sphere containing everything its [`Aabb`] does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: glam::Vec3,
+    /// Negative for empty spheres.
+    pub radius: f32,
+}
+
+/// Bounds of a skinned model in any pose. Skinning matrices only rotate and
+/// translate, so a vertex stays as far from each bone it follows as it is in
+/// the rest pose, and blending between bones keeps it within the farthest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkinnedBounds {
+    /// Of the vertices of meshes without skin.
+    unskinned: Aabb,
+    /// Per bone, how far the vertices it moves are from it at rest.
+    /// Negative for bones that move none.
+    bone_reaches: Vec<f32>,
+    /// Per morph, the longest vertex offset it applies at a weight of `1`.
+    morph_reaches: Vec<f32>,
+}
+
+/// The six planes bounding what a camera sees, facing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// `xyz` is the normal and `w` the distance from the origin, so that
+    /// points inside have `normal.dot(point) + w >= 0` for every plane.
+    planes: [glam::Vec4; 6],
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self {
+        min: glam::Vec3::INFINITY,
+        max: glam::Vec3::NEG_INFINITY,
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| Self {
+            min: aabb.min.min(point),
+            max: aabb.max.max(point),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Meaningless for empty boxes, as is [`Self::size`].
+    pub fn center(&self) -> glam::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> glam::Vec3 {
+        self.max - self.min
+    }
+
+    /// The box around this one after `matrix`. Grows with rotations, since
+    /// it stays axis-aligned.
+    pub fn transformed(&self, matrix: &glam::Mat4) -> Self {
+        if self.is_empty() {
+            return Self::EMPTY;
+        }
+
+        let center = matrix.transform_point3(self.center());
+        let half_size = self.size() * 0.5;
+        let half_size = glam::Mat3::from_mat4(*matrix).abs() * half_size;
+        Self {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        if self.is_empty() {
+            return BoundingSphere {
+                center: glam::Vec3::ZERO,
+                radius: -1.0,
+            };
+        }
+
+        BoundingSphere {
+            center: self.center(),
+            radius: self.size().length() * 0.5,
+        }
+    }
+}
+
+impl BoundingSphere {
+    /// The sphere around this one after `matrix`, scaled by the matrix's
+    /// largest scale.
+    pub fn transformed(&self, matrix: &glam::Mat4) -> Self {
+        if self.radius < 0.0 {
+            return *self;
+        }
+
+        let scale = matrix
+            .x_axis
+            .truncate()
+            .length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+impl SkinnedBounds {
+    pub fn new(model_data: &ModelData) -> Self {
+        let mut unskinned = Aabb::EMPTY;
+        let mut bone_reaches = vec![-1.0_f32; model_data.bones.len()];
+        for mesh in &model_data.meshes {
+            let Some(skin) = &mesh.skin else {
+                unskinned = unskinned.union(&Aabb::from_points(
+                    mesh.vertices.iter().map(|vertex| vertex.position),
+                ));
+                continue;
+            };
+
+            for (vertex, skin) in mesh.vertices.iter().zip(skin) {
+                for (slot, (&bone, &weight)) in skin.bones.iter().zip(&skin.weights).enumerate() {
+                    let Some(bone_data) = model_data.bones.get(bone as usize) else {
+                        continue;
+                    };
+                    if weight == 0.0 {
+                        continue;
+                    }
+                    // spherical deform turns the vertex around `c`, which
+                    // itself follows the bones.
+                    let reach = match &skin.sdef {
+                        Some(sdef) if slot < 2 => {
+                            vertex.position.distance(sdef.c)
+                                + sdef.c.distance(bone_data.rest_position)
+                        }
+                        _ => vertex.position.distance(bone_data.rest_position),
+                    };
+                    let bone_reach = &mut bone_reaches[bone as usize];
+                    *bone_reach = bone_reach.max(reach);
+                }
+            }
+        }
+
+        let morph_reaches = model_data
+            .morphs
+            .iter()
+            .map(|morph| match &morph.kind {
+                MorphKind::Vertex(offsets) => offsets
+                    .iter()
+                    .map(|offset| offset.position.length())
+                    .fold(0.0, f32::max),
+                _ => 0.0,
+            })
+            .collect();
+
+        Self {
+            unskinned,
+            bone_reaches,
+            morph_reaches,
+        }
+    }
+
+    /// The bounds for a pose's model space bone matrices, see
+    /// [`SkeletonState::world_matrices`](crate::animation::skeleton::SkeletonState::world_matrices),
+    /// with morphs at `morph_weights`.
+    pub fn posed(&self, world_matrices: &[glam::Mat4], morph_weights: &[f32]) -> Aabb {
+        let morph_reach: f32 = self
+            .morph_reaches
+            .iter()
+            .zip(morph_weights)
+            .map(|(reach, weight)| reach * weight.abs())
+            .sum();
+
+        let mut aabb = self.unskinned;
+        for (&reach, matrix) in self.bone_reaches.iter().zip(world_matrices) {
+            if reach >= 0.0 {
+                let position = matrix.w_axis.truncate();
+                aabb = aabb.union(&Aabb {
+                    min: position - reach,
+                    max: position + reach,
+                });
+            }
+        }
+        if aabb.is_empty() {
+            return aabb;
+        }
+        Aabb {
+            min: aabb.min - morph_reach,
+            max: aabb.max + morph_reach,
+        }
+    }
+}
+
+impl Frustum {
+    /// Extracts the planes of a view-projection matrix with wgpu's `0..1`
+    /// depth range.
+    pub fn from_view_proj(view_proj: &glam::Mat4) -> Self {
+        let [r0, r1, r2, r3] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+            let length = plane.truncate().length();
+            if length > 0.0 { plane / length } else { plane }
+        });
+        Self { planes }
+    }
+
+    /// Conservative: boxes near a corner of the frustum may intersect even
+    /// though they are outside.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // the corner furthest along the normal.
+            let corner = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        animation::skeleton::{BoneTransform, Skeleton, SkeletonState},
+        model_loaders::model_data::{
+            BoneData, MeshData, MorphData, VertexData, VertexMorphOffsetData, VertexSkinData,
+        },
+    };
+
+    fn cube(center: glam::Vec3, half_size: f32) -> Aabb {
+        Aabb {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    fn corners(aabb: &Aabb) -> impl Iterator<Item = glam::Vec3> {
+        let [min, max] = [aabb.min, aabb.max];
+        (0..8).map(move |i| {
+            glam::vec3(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        })
+    }
+
+    #[test]
+    fn transformed_boxes_fit_around_the_transformed_box() {
+        let aabb = Aabb {
+            min: glam::vec3(0.0, -1.0, 0.0),
+            max: glam::vec3(2.0, 1.0, 4.0),
+        };
+
+        // scaled along x to 0..4, then turned so that x becomes y.
+        let matrix = glam::Mat4::from_scale_rotation_translation(
+            glam::vec3(2.0, 1.0, 1.0),
+            glam::Quat::from_rotation_z(90.0_f32.to_radians()),
+            glam::vec3(10.0, 0.0, 0.0),
+        );
+        let transformed = aabb.transformed(&matrix);
+        assert!(transformed.min.abs_diff_eq(glam::vec3(9.0, 0.0, 0.0), 1e-5));
+        assert!(
+            transformed
+                .max
+                .abs_diff_eq(glam::vec3(11.0, 4.0, 4.0), 1e-5)
+        );
+
+        // turned by 45°, the box grows to fit the corners, and no further.
+        let matrix = glam::Mat4::from_scale_rotation_translation(
+            glam::vec3(1.0, 3.0, 0.5),
+            glam::Quat::from_rotation_x(45.0_f32.to_radians()),
+            glam::vec3(0.0, -2.0, 1.0),
+        );
+        let transformed = aabb.transformed(&matrix);
+        let fitted = Aabb::from_points(corners(&aabb).map(|c| matrix.transform_point3(c)));
+        assert!(
+            transformed.min.abs_diff_eq(fitted.min, 1e-5),
+            "{:?}",
+            transformed
+        );
+        assert!(
+            transformed.max.abs_diff_eq(fitted.max, 1e-5),
+            "{:?}",
+            transformed
+        );
+    }
+
+    #[test]
+    fn transformed_spheres_contain_the_transformed_box() {
+        let aabb = cube(glam::vec3(1.0, 0.0, 0.0), 1.0);
+        let sphere = aabb.bounding_sphere();
+        assert_eq!(sphere.center, glam::vec3(1.0, 0.0, 0.0));
+        assert!((sphere.radius - 3.0_f32.sqrt()).abs() < 1e-6);
+
+        let matrix = glam::Mat4::from_scale_rotation_translation(
+            glam::vec3(1.0, 3.0, 2.0),
+            glam::Quat::from_rotation_y(1.0),
+            glam::vec3(0.0, 5.0, 0.0),
+        );
+        let transformed = sphere.transformed(&matrix);
+        assert!(
+            transformed
+                .center
+                .abs_diff_eq(matrix.transform_point3(sphere.center), 1e-5)
+        );
+        assert!((transformed.radius - 3.0 * 3.0_f32.sqrt()).abs() < 1e-5);
+        for corner in corners(&aabb) {
+            let distance = matrix.transform_point3(corner).distance(transformed.center);
+            assert!(distance <= transformed.radius + 1e-5, "{}", corner);
+        }
+    }
+
+    #[test]
+    fn empty_bounds_stay_empty_when_transformed() {
+        let matrix = glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::splat(2.0),
+            glam::Quat::from_rotation_z(1.0),
+            glam::vec3(1.0, 2.0, 3.0),
+        );
+
+        assert_eq!(Aabb::EMPTY.transformed(&matrix), Aabb::EMPTY);
+        assert_eq!(
+            Aabb::from_points([])
+                .union(&Aabb::EMPTY)
+                .transformed(&matrix),
+            Aabb::EMPTY
+        );
+
+        let sphere = Aabb::EMPTY.bounding_sphere();
+        assert!(sphere.radius < 0.0);
+        assert_eq!(sphere.transformed(&matrix), sphere);
+    }
+
+    /// Looking down -Z from the origin with a 90° field of view, from 1 to
+    /// 10 units away.
+    fn frustum() -> Frustum {
+        let proj = glam::Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 1.0, 10.0);
+        let view = glam::Mat4::look_at_rh(glam::Vec3::ZERO, -glam::Vec3::Z, glam::Vec3::Y);
+        Frustum::from_view_proj(&(proj * view))
+    }
+
+    #[test]
+    fn frustum_planes_are_where_the_projection_puts_them() {
+        let frustum = frustum();
+        let distance =
+            |plane: usize, point: glam::Vec3| frustum.planes[plane].dot(point.extend(1.0));
+
+        // left, right, bottom and top meet at the eye, at 45°.
+        for plane in 0..4 {
+            assert!(distance(plane, glam::Vec3::ZERO).abs() < 1e-5);
+            assert!((distance(plane, glam::vec3(0.0, 0.0, -2.0)) - 2.0_f32.sqrt()).abs() < 1e-5);
+        }
+        // near and far are at 1 and 10, with depth going from 0 to 1.
+        assert!((distance(4, glam::vec3(0.0, 0.0, -3.0)) - 2.0).abs() < 1e-5);
+        assert!((distance(5, glam::vec3(0.0, 0.0, -3.0)) - 7.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn frustum_intersects_boxes_inside_or_crossing_it() {
+        let frustum = frustum();
+        assert!(frustum.intersects_aabb(&cube(glam::vec3(0.0, 0.0, -5.0), 0.5)));
+        // crossing the left plane, and the near plane.
+        assert!(frustum.intersects_aabb(&cube(glam::vec3(-5.5, 0.0, -5.0), 1.0)));
+        assert!(frustum.intersects_aabb(&cube(glam::vec3(0.0, 0.0, -0.9), 0.2)));
+
+        // behind, in front of the near plane, beyond the far plane, and to
+        // each side.
+        for center in [
+            glam::vec3(0.0, 0.0, 5.0),
+            glam::vec3(0.0, 0.0, -0.5),
+            glam::vec3(0.0, 0.0, -11.0),
+            glam::vec3(-7.0, 0.0, -5.0),
+            glam::vec3(7.0, 0.0, -5.0),
+            glam::vec3(0.0, -7.0, -5.0),
+            glam::vec3(0.0, 7.0, -5.0),
+        ] {
+            assert!(!frustum.intersects_aabb(&cube(center, 0.4)), "{}", center);
+        }
+        assert!(!frustum.intersects_aabb(&Aabb::EMPTY));
+    }
+
+    fn bone(parent: Option<usize>, rest_position: glam::Vec3) -> BoneData {
+        BoneData {
+            name: String::new(),
+            parent,
+            rest_position,
+            layer: 0,
+            deforms_after_physics: false,
+            ik: None,
+            inherit: None,
+            fixed_axis: None,
+            local_axes: None,
+        }
+    }
+
+    fn vertex(position: glam::Vec3) -> VertexData {
+        VertexData {
+            position,
+            tex_coords: glam::Vec2::ZERO,
+            tex_coords_1: glam::Vec2::ZERO,
+            normal: glam::Vec3::Z,
+            tangent: glam::Vec3::X,
+            bitangent: glam::Vec3::Y,
+            edge_scale: 1.0,
+        }
+    }
+
+    fn skin(bones: [u32; 2], weights: [f32; 2]) -> VertexSkinData {
+        VertexSkinData {
+            bones: [bones[0], bones[1], 0, 0],
+            weights: [weights[0], weights[1], 0.0, 0.0],
+            sdef: None,
+        }
+    }
+
+    /// An arm along +X with a shoulder and an elbow, a bone far away that
+    /// moves nothing, and a vertex morph pushing the hand out by 1.
+    fn arm() -> ModelData {
+        let vertices = vec![
+            vertex(glam::vec3(0.5, 0.1, 0.0)),
+            vertex(glam::vec3(1.0, 0.0, 0.2)),
+            vertex(glam::vec3(1.5, 0.0, 0.0)),
+        ];
+        ModelData {
+            name: "arm".to_string(),
+            meshes: vec![
+                MeshData {
+                    name: "arm".to_string(),
+                    vertices,
+                    indices: vec![0, 1, 2],
+                    material_index: 0,
+                    skin: Some(vec![
+                        skin([0, 0], [1.0, 0.0]),
+                        skin([0, 1], [0.5, 0.5]),
+                        skin([1, 0], [1.0, 0.0]),
+                    ]),
+                },
+                MeshData {
+                    name: "pedestal".to_string(),
+                    vertices: vec![vertex(glam::vec3(0.0, -1.0, 0.0))],
+                    indices: vec![],
+                    material_index: 0,
+                    skin: None,
+                },
+            ],
+            materials: vec![],
+            bones: vec![
+                bone(None, glam::Vec3::ZERO),
+                bone(Some(0), glam::vec3(1.0, 0.0, 0.0)),
+                bone(None, glam::vec3(100.0, 0.0, 0.0)),
+            ],
+            morphs: vec![MorphData {
+                name: "reach".to_string(),
+                kind: MorphKind::Vertex(vec![VertexMorphOffsetData {
+                    mesh: 0,
+                    vertex: 2,
+                    position: glam::vec3(1.0, 0.0, 0.0),
+                }]),
+            }],
+            rigid_bodies: vec![],
+            joints: vec![],
+            motion_scale: 1.0,
+        }
+    }
+
+    fn contains(aabb: &Aabb, point: glam::Vec3) -> bool {
+        point.cmpge(aabb.min - 1e-5).all() && point.cmple(aabb.max + 1e-5).all()
+    }
+
+    #[test]
+    fn skinned_bounds_contain_every_posed_vertex() {
+        let model_data = arm();
+        let skinned_bounds = SkinnedBounds::new(&model_data);
+        let skeleton = Skeleton::new(model_data.bones.clone());
+        let mesh = &model_data.meshes[0];
+
+        for (shoulder, elbow) in [(0.0, 0.0), (1.5, 0.0), (1.5, -2.0), (-0.7, 2.5)] {
+            let mut pose = vec![BoneTransform::IDENTITY; 3];
+            pose[0].rotation = glam::Quat::from_rotation_z(shoulder);
+            pose[1].rotation = glam::Quat::from_rotation_y(elbow);
+            pose[1].translation = glam::vec3(0.0, 0.3, 0.0);
+            let mut state = SkeletonState::default();
+            skeleton.evaluate(&pose, &[], &mut state);
+
+            for morph_weight in [0.0, 1.0] {
+                let bounds = skinned_bounds.posed(&state.world_matrices, &[morph_weight]);
+                assert!(contains(&bounds, glam::vec3(0.0, -1.0, 0.0)));
+                for (index, (vertex, skin)) in mesh
+                    .vertices
+                    .iter()
+                    .zip(mesh.skin.as_ref().unwrap())
+                    .enumerate()
+                {
+                    let morphed = match index {
+                        2 => vertex.position + glam::vec3(morph_weight, 0.0, 0.0),
+                        _ => vertex.position,
+                    };
+                    let posed: glam::Vec3 = skin
+                        .bones
+                        .iter()
+                        .zip(skin.weights)
+                        .map(|(&bone, weight)| {
+                            let matrix = skeleton.skinning_matrix(
+                                bone as usize,
+                                &state.world_matrices[bone as usize],
+                            );
+                            matrix.transform_point3(morphed) * weight
+                        })
+                        .sum();
+                    assert!(contains(&bounds, posed), "{:?} {}", bounds, posed);
+                }
+                // the far bone moves nothing, so it isn't included.
+                assert!(bounds.max.x < 5.0, "{:?}", bounds);
+            }
+        }
+    }
+
+    #[test]
+    fn skinned_bounds_without_vertices_are_empty() {
+        let mut model_data = arm();
+        model_data.meshes.clear();
+        let skinned_bounds = SkinnedBounds::new(&model_data);
+        let bounds = skinned_bounds.posed(&[glam::Mat4::IDENTITY; 3], &[1.0]);
+        assert!(bounds.is_empty());
+    }
+}
@@ -2,21 +2,112 @@ use std::sync::Arc;
 
 use wgpu::util::DeviceExt;
 
-use crate::drawing::textures;
+use crate::{
+    animation::skeleton::Skeleton,
+    bounds::{Aabb, SkinnedBounds},
+    drawing::textures,
+    model_loaders::model_data::{
+        BlendMode, EdgeData, JointData, MaterialData, MaterialMorphOffsetData,
+        MaterialMorphOperation, MaterialShading, MeshData, ModelData, MorphData, MorphKind,
+        RigidBodyData, SphereMapMode, TextureData, VertexData, VertexSkinData,
+    },
+};
 
 pub struct Model {
     meshes: Arc<Vec<Mesh>>,
     materials: Vec<Material>,
+    skeleton: Option<Arc<Skeleton>>,
+    morphs: Vec<MorphData>,
+    rigid_bodies: Vec<RigidBodyData>,
+    joints: Vec<JointData>,
+    motion_scale: f32,
+    /// Of all meshes.
+    bounds: Aabb,
+    /// For models with a skeleton, set by [`Self::upload`].
+    skinned_bounds: Option<SkinnedBounds>,
 }
 
 impl Model {
-    pub fn new(meshes: Vec<Mesh>, materials: Vec<Material>) -> Self {
+    pub fn new(
+        meshes: Vec<Mesh>,
+        materials: Vec<Material>,
+        skeleton: Option<Skeleton>,
+        morphs: Vec<MorphData>,
+        rigid_bodies: Vec<RigidBodyData>,
+        joints: Vec<JointData>,
+        motion_scale: f32,
+    ) -> Self {
+        let bounds = meshes
+            .iter()
+            .fold(Aabb::EMPTY, |bounds, mesh| bounds.union(mesh.bounds()));
         Self {
+            bounds,
             meshes: Arc::new(meshes),
             materials,
+            skeleton: skeleton.map(Arc::new),
+            morphs,
+            rigid_bodies,
+            joints,
+            motion_scale,
+            skinned_bounds: None,
         }
     }
 
+    /// Creates the GPU resources for `model_data`.
+    pub fn upload(
+        model_data: &ModelData,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let mesh_morphs = MeshMorphs::collect(model_data);
+        let meshes = model_data
+            .meshes
+            .iter()
+            .zip(&mesh_morphs)
+            .map(|(mesh_data, morphs)| {
+                Mesh::upload(
+                    &format!("{}/{}", model_data.name, mesh_data.name),
+                    device,
+                    mesh_data,
+                    morphs.as_ref(),
+                )
+            })
+            .collect();
+
+        let materials = model_data
+            .materials
+            .iter()
+            .map(|material_data| {
+                Material::upload(
+                    &format!("{}/{}", model_data.name, material_data.name),
+                    device,
+                    queue,
+                    material_data,
+                    material_bind_group_layout,
+                )
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let skeleton =
+            (!model_data.bones.is_empty()).then(|| Skeleton::new(model_data.bones.clone()));
+
+        let skinned_bounds = skeleton.is_some().then(|| SkinnedBounds::new(model_data));
+
+        Ok(Self {
+            skinned_bounds,
+            ..Self::new(
+                meshes,
+                materials,
+                skeleton,
+                model_data.morphs.clone(),
+                model_data.rigid_bodies.clone(),
+                model_data.joints.clone(),
+                model_data.motion_scale,
+            )
+        })
+    }
+
     pub fn meshes(&self) -> Arc<Vec<Mesh>> {
         self.meshes.clone()
     }
@@ -24,13 +115,55 @@ impl Model {
     pub fn materials(&self) -> &Vec<Material> {
         &self.materials
     }
+
+    pub fn skeleton(&self) -> Option<&Arc<Skeleton>> {
+        self.skeleton.as_ref()
+    }
+
+    pub fn morphs(&self) -> &[MorphData] {
+        &self.morphs
+    }
+
+    pub fn rigid_bodies(&self) -> &[RigidBodyData] {
+        &self.rigid_bodies
+    }
+
+    pub fn joints(&self) -> &[JointData] {
+        &self.joints
+    }
+
+    /// See [`ModelData::motion_scale`].
+    pub fn motion_scale(&self) -> f32 {
+        self.motion_scale
+    }
+
+    /// See [`Mesh::bounds`].
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    /// Where the model can be once posed, see [`SkinnedBounds::posed`].
+    pub fn skinned_bounds(&self) -> Option<&SkinnedBounds> {
+        self.skinned_bounds.as_ref()
+    }
 }
 
 pub struct Mesh {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     index_count: u32,
+    vertex_count: u32,
     material_index: usize,
+    /// One [`SkinVertex`] per vertex, for skinned meshes.
+    skin_buffer: Option<wgpu::Buffer>,
+    /// For skinned meshes with vertex or UV morphs, see [`MeshMorphs`].
+    morph_buffers: Option<MeshMorphBuffers>,
+    bounds: Aabb,
+}
+
+pub struct MeshMorphBuffers {
+    pub range_buffer: wgpu::Buffer,
+    pub offset_buffer: wgpu::Buffer,
 }
 
 impl Mesh {
@@ -40,26 +173,86 @@ impl Mesh {
         vertices: &[ModelVertex],
         indices: &[u32],
         material_index: usize,
+        skin: Option<&[SkinVertex]>,
+        morphs: Option<&MeshMorphs>,
     ) -> Self {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("[Mesh::new] vertex buffer for {}", name)),
             contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            // skinning reads the rest pose from here.
+            usage: if skin.is_some() {
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+            } else {
+                wgpu::BufferUsages::VERTEX
+            },
         });
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("[Mesh::new] index buffer for {}", name)),
             contents: bytemuck::cast_slice(&indices),
             usage: wgpu::BufferUsages::INDEX,
         });
+        let skin_buffer = skin.map(|skin| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("[Mesh::new] skin buffer for {}", name)),
+                contents: bytemuck::cast_slice(skin),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        });
+        // morphs are applied while skinning, so they need a skin.
+        let morph_buffers = morphs
+            .filter(|_| skin.is_some())
+            .map(|morphs| MeshMorphBuffers {
+                range_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("[Mesh::new] morph range buffer for {}", name)),
+                    contents: bytemuck::cast_slice(&morphs.ranges),
+                    usage: wgpu::BufferUsages::STORAGE,
+                }),
+                offset_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("[Mesh::new] morph offset buffer for {}", name)),
+                    contents: bytemuck::cast_slice(&morphs.offsets),
+                    usage: wgpu::BufferUsages::STORAGE,
+                }),
+            });
 
         Self {
             vertex_buffer,
             index_buffer,
             index_count: indices.len() as u32,
+            vertex_count: vertices.len() as u32,
             material_index,
+            skin_buffer,
+            morph_buffers,
+            bounds: Aabb::from_points(vertices.iter().map(|vertex| vertex.position)),
         }
     }
 
+    pub fn upload(
+        name: &str,
+        device: &wgpu::Device,
+        mesh_data: &MeshData,
+        morphs: Option<&MeshMorphs>,
+    ) -> Self {
+        let vertices = mesh_data
+            .vertices
+            .iter()
+            .map(ModelVertex::from)
+            .collect::<Vec<_>>();
+        let skin = mesh_data
+            .skin
+            .as_ref()
+            .map(|skin| skin.iter().map(SkinVertex::from).collect::<Vec<_>>());
+
+        Self::new(
+            name,
+            device,
+            &vertices,
+            &mesh_data.indices,
+            mesh_data.material_index,
+            skin.as_deref(),
+            morphs,
+        )
+    }
+
     pub fn vertex_buffer(&self) -> &wgpu::Buffer {
         &self.vertex_buffer
     }
@@ -72,12 +265,137 @@ impl Mesh {
         self.index_count
     }
 
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
     pub fn material_index(&self) -> usize {
         self.material_index
     }
+
+    pub fn skin_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.skin_buffer.as_ref()
+    }
+
+    pub fn morph_buffers(&self) -> Option<&MeshMorphBuffers> {
+        self.morph_buffers.as_ref()
+    }
+
+    /// In model space, in the rest pose and without morphs. Animated meshes
+    /// may leave it.
+    pub fn bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+}
+
+/// The vertex and UV morph offsets of one mesh, grouped by vertex so that
+/// the skinning shader can add up each vertex's offsets on its own.
+pub struct MeshMorphs {
+    /// Per vertex, the start and length of its run in `offsets`.
+    pub ranges: Vec<[u32; 2]>,
+    pub offsets: Vec<MorphOffset>,
+}
+
+impl MeshMorphs {
+    /// Per mesh of `model_data`; `None` for meshes without vertex or UV
+    /// morphs. Offsets of vertices that don't exist are skipped.
+    pub fn collect(model_data: &ModelData) -> Vec<Option<Self>> {
+        let mut mesh_offsets = vec![Vec::<(u32, MorphOffset)>::new(); model_data.meshes.len()];
+        for (morph_index, morph) in model_data.morphs.iter().enumerate() {
+            let mut push = |mesh: usize, vertex: u32, offset: MorphOffset| {
+                let exists = model_data
+                    .meshes
+                    .get(mesh)
+                    .is_some_and(|mesh_data| (vertex as usize) < mesh_data.vertices.len());
+                if exists {
+                    mesh_offsets[mesh].push((vertex, offset));
+                } else {
+                    log::warn!(
+                        "[MeshMorphs::collect] skipping offset of {:?} for missing vertex {} of mesh {}",
+                        morph.name,
+                        vertex,
+                        mesh
+                    );
+                }
+            };
+            let morph_index = morph_index as u32;
+            match &morph.kind {
+                MorphKind::Vertex(offsets) => {
+                    for offset in offsets {
+                        push(
+                            offset.mesh,
+                            offset.vertex,
+                            MorphOffset {
+                                position: offset.position,
+                                morph: morph_index,
+                                tex_coords: glam::Vec2::ZERO,
+                                tex_coords_1: glam::Vec2::ZERO,
+                            },
+                        );
+                    }
+                }
+                MorphKind::Uv(offsets) => {
+                    for offset in offsets {
+                        push(
+                            offset.mesh,
+                            offset.vertex,
+                            MorphOffset {
+                                position: glam::Vec3::ZERO,
+                                morph: morph_index,
+                                tex_coords: offset.tex_coords,
+                                tex_coords_1: offset.tex_coords_1,
+                            },
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        model_data
+            .meshes
+            .iter()
+            .zip(mesh_offsets)
+            .map(|(mesh_data, mut offsets)| {
+                if offsets.is_empty() {
+                    return None;
+                }
+
+                offsets.sort_by_key(|(vertex, _)| *vertex);
+                let mut ranges = vec![[0, 0]; mesh_data.vertices.len()];
+                for (i, (vertex, _)) in offsets.iter().enumerate() {
+                    let range = &mut ranges[*vertex as usize];
+                    if range[1] == 0 {
+                        range[0] = i as u32;
+                    }
+                    range[1] += 1;
+                }
+
+                Some(Self {
+                    ranges,
+                    offsets: offsets.into_iter().map(|(_, offset)| offset).collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// See `MorphOffset` in `skinning.wesl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MorphOffset {
+    position: glam::Vec3,
+    /// Index into the model's morphs.
+    morph: u32,
+    tex_coords: glam::Vec2,
+    tex_coords_1: glam::Vec2,
 }
 
 pub struct Material {
+    textures: MaterialTextures,
+    uniform: MaterialUniform,
+    state: MaterialState,
+    bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
 }
 
@@ -85,38 +403,312 @@ impl Material {
     pub fn new(
         device: &wgpu::Device,
         name: &str,
-        diffuse_texture: textures::D2DiffuseTexture,
-        normal_texture: textures::D2NormalTexture,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        textures: MaterialTextures,
+        uniform: MaterialUniform,
+        state: MaterialState,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: texture_bind_group_layout,
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("[Material::new] uniform buffer for {}", name)),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = Self::make_bind_group(
+            &format!("[Material::new] bind group for {}", name),
+            device,
+            &textures,
+            &uniform_buffer,
+            material_bind_group_layout,
+        );
+
+        Self {
+            textures,
+            uniform,
+            state,
+            bind_group_layout: material_bind_group_layout.clone(),
+            bind_group,
+        }
+    }
+
+    pub fn upload(
+        name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        material_data: &MaterialData,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let color_texture = |kind: &str, texture_data: Option<&TextureData>, fallback| {
+            anyhow::Ok(match texture_data {
+                Some(texture_data) => textures::D2DiffuseTexture::from_image(
+                    &format!("{} {}", name, kind),
+                    device,
+                    queue,
+                    &texture_data.decode()?,
+                ),
+                None => textures::D2DiffuseTexture::from_image(
+                    &format!("memory:solid-{}-texture", kind),
+                    device,
+                    queue,
+                    &new_solid_image(fallback),
+                ),
+            })
+        };
+
+        let diffuse = color_texture(
+            "diffuse",
+            material_data.diffuse_texture.as_ref(),
+            [255, 255, 255, 255],
+        )?;
+        let toon = color_texture(
+            "toon",
+            material_data.toon_texture.as_ref(),
+            [255, 255, 255, 255],
+        )?;
+        let sphere = color_texture(
+            "sphere",
+            material_data
+                .sphere_map
+                .as_ref()
+                .map(|sphere_map| &sphere_map.texture),
+            [0, 0, 0, 255],
+        )?;
+
+        let normal = match &material_data.normal_texture {
+            Some(texture_data) => textures::D2NormalTexture::from_image(
+                &format!("{} normal", name),
+                device,
+                queue,
+                &texture_data.decode()?,
+            ),
+            None => textures::D2NormalTexture::from_image(
+                "memory:flat-normal-texture",
+                device,
+                queue,
+                &new_solid_image([128, 128, 255, 255]),
+            ),
+        };
+
+        Ok(Self::new(
+            device,
+            name,
+            MaterialTextures {
+                diffuse,
+                normal,
+                toon,
+                sphere,
+            },
+            MaterialUniform::from(material_data),
+            MaterialState::from(material_data),
+            material_bind_group_layout,
+        ))
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn state(&self) -> &MaterialState {
+        &self.state
+    }
+
+    /// The colors the material was created with.
+    pub fn uniform(&self) -> &MaterialUniform {
+        &self.uniform
+    }
+
+    /// A bind group with the material's textures, but with colors from
+    /// `uniform_buffer`, for drawing it differently from other entries.
+    pub fn bind_group_with_uniform(
+        &self,
+        label: &str,
+        device: &wgpu::Device,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        Self::make_bind_group(
+            label,
+            device,
+            &self.textures,
+            uniform_buffer,
+            &self.bind_group_layout,
+        )
+    }
+
+    fn make_bind_group(
+        label: &str,
+        device: &wgpu::Device,
+        textures: &MaterialTextures,
+        uniform_buffer: &wgpu::Buffer,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: material_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(diffuse_texture.view()),
+                    resource: wgpu::BindingResource::TextureView(textures.diffuse.view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(diffuse_texture.sampler()),
+                    resource: wgpu::BindingResource::Sampler(textures.diffuse.sampler()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(normal_texture.view()),
+                    resource: wgpu::BindingResource::TextureView(textures.normal.view()),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::Sampler(normal_texture.sampler()),
+                    resource: wgpu::BindingResource::Sampler(textures.normal.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(textures.toon.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(textures.toon.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(textures.sphere.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(textures.sphere.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: uniform_buffer.as_entire_binding(),
                 },
             ],
-            label: Some(&format!("[Material::new] bind group for {}", name)),
-        });
+            label: Some(label),
+        })
+    }
+}
 
-        Self { bind_group }
+/// How a [`Material`] is drawn, besides its textures and colors. Each
+/// combination has its own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialState {
+    pub shading: MaterialShading,
+    pub is_double_sided: bool,
+    pub blend_mode: BlendMode,
+    pub writes_depth: bool,
+}
+
+impl From<&MaterialData> for MaterialState {
+    fn from(value: &MaterialData) -> Self {
+        Self {
+            shading: value.shading,
+            is_double_sided: value.is_double_sided,
+            blend_mode: value.blend_mode,
+            writes_depth: value.writes_depth,
+        }
     }
+}
 
-    pub fn bind_group(&self) -> &wgpu::BindGroup {
-        &self.bind_group
+pub struct MaterialTextures {
+    pub diffuse: textures::D2DiffuseTexture,
+    pub normal: textures::D2NormalTexture,
+    pub toon: textures::D2DiffuseTexture,
+    pub sphere: textures::D2DiffuseTexture,
+}
+
+/// See `material.wesl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    diffuse_color: glam::Vec4,
+    specular_color: glam::Vec3,
+    specular_power: f32,
+    ambient_color: glam::Vec3,
+    /// 0: none, 1: multiply, 2: add, 3: sub-texture.
+    sphere_mode: u32,
+    has_toon_texture: u32,
+    edge_size: f32,
+    /// Fragments with less alpha are discarded. `0` for materials without
+    /// an alpha test.
+    alpha_cutoff: f32,
+    _padding: u32,
+    /// Tints from material morphs, see [`MaterialUniform::apply_morph`].
+    texture_tint_multiply: glam::Vec4,
+    texture_tint_add: glam::Vec4,
+    sphere_tint_multiply: glam::Vec4,
+    sphere_tint_add: glam::Vec4,
+    toon_tint_multiply: glam::Vec4,
+    toon_tint_add: glam::Vec4,
+    edge_color: glam::Vec4,
+}
+
+impl MaterialUniform {
+    /// Adds one material morph offset at `weight`. Offsets are applied in
+    /// order, on top of what earlier ones did.
+    pub fn apply_morph(&mut self, offset: &MaterialMorphOffsetData, weight: f32) {
+        match offset.operation {
+            MaterialMorphOperation::Multiply => {
+                let factor4 = |v: glam::Vec4| glam::Vec4::ONE + (v - glam::Vec4::ONE) * weight;
+                let factor3 = |v: glam::Vec3| glam::Vec3::ONE + (v - glam::Vec3::ONE) * weight;
+                self.diffuse_color *= factor4(offset.diffuse_color);
+                self.specular_color *= factor3(offset.specular_color);
+                self.specular_power *= 1.0 + (offset.specular_power - 1.0) * weight;
+                self.ambient_color *= factor3(offset.ambient_color);
+                self.edge_color *= factor4(offset.edge_color);
+                self.edge_size *= 1.0 + (offset.edge_size - 1.0) * weight;
+                self.texture_tint_multiply *= factor4(offset.texture_tint);
+                self.sphere_tint_multiply *= factor4(offset.sphere_tint);
+                self.toon_tint_multiply *= factor4(offset.toon_tint);
+            }
+            MaterialMorphOperation::Add => {
+                self.diffuse_color += offset.diffuse_color * weight;
+                self.specular_color += offset.specular_color * weight;
+                self.specular_power += offset.specular_power * weight;
+                self.ambient_color += offset.ambient_color * weight;
+                self.edge_color += offset.edge_color * weight;
+                self.edge_size += offset.edge_size * weight;
+                self.texture_tint_add += offset.texture_tint * weight;
+                self.sphere_tint_add += offset.sphere_tint * weight;
+                self.toon_tint_add += offset.toon_tint * weight;
+            }
+        }
+    }
+}
+
+impl From<&MaterialData> for MaterialUniform {
+    fn from(value: &MaterialData) -> Self {
+        let edge = match value.shading {
+            MaterialShading::Toon { edge: Some(edge) } => edge,
+            _ => EdgeData {
+                color: glam::Vec4::ZERO,
+                size: 0.0,
+            },
+        };
+
+        Self {
+            diffuse_color: value.diffuse_color,
+            specular_color: value.specular_color,
+            specular_power: value.specular_power,
+            ambient_color: value.ambient_color,
+            sphere_mode: match value.sphere_map.as_ref().map(|sphere_map| sphere_map.mode) {
+                None => 0,
+                Some(SphereMapMode::Multiply) => 1,
+                Some(SphereMapMode::Add) => 2,
+                Some(SphereMapMode::SubTexture) => 3,
+            },
+            has_toon_texture: value.toon_texture.is_some() as u32,
+            edge_size: edge.size,
+            alpha_cutoff: match value.blend_mode {
+                BlendMode::AlphaTest { cutoff } => cutoff,
+                BlendMode::Opaque | BlendMode::AlphaBlend => 0.0,
+            },
+            _padding: 0,
+            texture_tint_multiply: glam::Vec4::ONE,
+            texture_tint_add: glam::Vec4::ZERO,
+            sphere_tint_multiply: glam::Vec4::ONE,
+            sphere_tint_add: glam::Vec4::ZERO,
+            toon_tint_multiply: glam::Vec4::ONE,
+            toon_tint_add: glam::Vec4::ZERO,
+            edge_color: edge.color,
+        }
     }
 }
 
@@ -129,10 +721,29 @@ pub struct ModelVertex {
 
     pub tangent: glam::Vec3,
     pub bitangent: glam::Vec3,
+
+    pub tex_coords_1: glam::Vec2,
+    pub edge_scale: f32,
+}
+
+impl From<&VertexData> for ModelVertex {
+    fn from(value: &VertexData) -> Self {
+        Self {
+            position: value.position,
+            tex_coords: value.tex_coords,
+            tex_coords_1: value.tex_coords_1,
+            normal: value.normal,
+            tangent: value.tangent,
+            bitangent: value.bitangent,
+            edge_scale: value.edge_scale,
+        }
+    }
 }
 
 impl ModelVertex {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x3, 4 => Float32x3];
+    /// Instance attributes take the locations in between, see
+    /// `SimpleInstanceData`.
+    const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x3, 4 => Float32x3, 5 => Float32x2, 14 => Float32];
 
     pub const fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -144,6 +755,49 @@ impl ModelVertex {
     }
 }
 
+/// See `skinning.wesl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinVertex {
+    bones: [u32; 4],
+    weights: [f32; 4],
+    sdef_c: glam::Vec3,
+    is_sdef: u32,
+    /// Where `bones[0]` moves the vertex around, see [`SkinVertex::from`].
+    sdef_cr0: glam::Vec3,
+    _padding_0: u32,
+    sdef_cr1: glam::Vec3,
+    _padding_1: u32,
+}
+
+impl From<&VertexSkinData> for SkinVertex {
+    /// Precomputes the SDEF centers the same way MMD does, so that the
+    /// shader only has to blend them.
+    fn from(value: &VertexSkinData) -> Self {
+        let (sdef_c, sdef_cr0, sdef_cr1) = match &value.sdef {
+            Some(sdef) => {
+                let [w0, w1, ..] = value.weights;
+                let rw = sdef.r0 * w0 + sdef.r1 * w1;
+                let r0 = sdef.c + sdef.r0 - rw;
+                let r1 = sdef.c + sdef.r1 - rw;
+                (sdef.c, (sdef.c + r0) * 0.5, (sdef.c + r1) * 0.5)
+            }
+            None => (glam::Vec3::ZERO, glam::Vec3::ZERO, glam::Vec3::ZERO),
+        };
+
+        Self {
+            bones: value.bones,
+            weights: value.weights,
+            sdef_c,
+            is_sdef: value.sdef.is_some() as u32,
+            sdef_cr0,
+            _padding_0: 0,
+            sdef_cr1,
+            _padding_1: 0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ShapeVertex {
@@ -163,3 +817,98 @@ impl ShapeVertex {
         }
     }
 }
+
+/// A 1x1 image, for materials without a texture.
+fn new_solid_image(rgba: [u8; 4]) -> image::DynamicImage {
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::{UvMorphOffsetData, VertexMorphOffsetData};
+
+    fn mesh(vertex_count: usize) -> MeshData {
+        MeshData {
+            name: String::new(),
+            vertices: vec![
+                VertexData {
+                    position: glam::Vec3::ZERO,
+                    tex_coords: glam::Vec2::ZERO,
+                    tex_coords_1: glam::Vec2::ZERO,
+                    normal: glam::Vec3::Z,
+                    tangent: glam::Vec3::X,
+                    bitangent: glam::Vec3::Y,
+                    edge_scale: 1.0,
+                };
+                vertex_count
+            ],
+            indices: vec![],
+            material_index: 0,
+            skin: None,
+        }
+    }
+
+    fn vertex_offset(mesh: usize, vertex: u32, x: f32) -> VertexMorphOffsetData {
+        VertexMorphOffsetData {
+            mesh,
+            vertex,
+            position: glam::vec3(x, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn morph_offsets_are_grouped_by_vertex_skipping_missing_ones() {
+        let model_data = ModelData {
+            name: String::new(),
+            meshes: vec![mesh(3), mesh(1), mesh(1)],
+            materials: vec![],
+            bones: vec![],
+            morphs: vec![
+                MorphData {
+                    name: "a".to_string(),
+                    kind: MorphKind::Vertex(vec![
+                        vertex_offset(0, 2, 1.0),
+                        // past the end of the mesh, and a mesh that doesn't
+                        // exist.
+                        vertex_offset(0, 3, 2.0),
+                        vertex_offset(3, 0, 3.0),
+                        vertex_offset(1, 0, 4.0),
+                    ]),
+                },
+                MorphData {
+                    name: "b".to_string(),
+                    kind: MorphKind::Uv(vec![UvMorphOffsetData {
+                        mesh: 0,
+                        vertex: 0,
+                        tex_coords: glam::vec2(0.5, 0.0),
+                        tex_coords_1: glam::Vec2::ZERO,
+                    }]),
+                },
+            ],
+            rigid_bodies: vec![],
+            joints: vec![],
+            motion_scale: 1.0,
+        };
+
+        let mesh_morphs = MeshMorphs::collect(&model_data);
+        assert_eq!(mesh_morphs.len(), 3);
+
+        let first = mesh_morphs[0].as_ref().unwrap();
+        assert_eq!(first.ranges, [[0, 1], [0, 0], [1, 1]]);
+        assert_eq!(
+            first
+                .offsets
+                .iter()
+                .map(|offset| (offset.morph, offset.position.x, offset.tex_coords.x))
+                .collect::<Vec<_>>(),
+            [(1, 0.0, 0.5), (0, 1.0, 0.0)]
+        );
+
+        let second = mesh_morphs[1].as_ref().unwrap();
+        assert_eq!(second.ranges, [[0, 1]]);
+        assert_eq!(second.offsets[0].position.x, 4.0);
+
+        assert!(mesh_morphs[2].is_none());
+    }
+}
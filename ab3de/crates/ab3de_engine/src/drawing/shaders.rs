@@ -2,6 +2,14 @@ pub fn r_model_demo(device: &wgpu::Device) -> RenderShader {
     RenderShader(device.create_shader_module(include_wesl_desc!("render_model_demo")))
 }
 
+pub fn r_model_toon(device: &wgpu::Device) -> RenderShader {
+    RenderShader(device.create_shader_module(include_wesl_desc!("render_model_toon")))
+}
+
+pub fn r_model_toon_edge(device: &wgpu::Device) -> RenderShader {
+    RenderShader(device.create_shader_module(include_wesl_desc!("render_model_toon_edge")))
+}
+
 pub fn r_depth_debug(device: &wgpu::Device) -> RenderShader {
     RenderShader(device.create_shader_module(include_wesl_desc!("render_depth_debug")))
 }
@@ -26,6 +34,10 @@ pub fn c_equirectangular(device: &wgpu::Device) -> ComputeShaderComputeEquirectT
     )
 }
 
+pub fn c_skinning(device: &wgpu::Device) -> ComputeShaderSkinning {
+    ComputeShaderSkinning(device.create_shader_module(include_wesl_desc!("compute_skinning")))
+}
+
 pub struct RenderShader(wgpu::ShaderModule);
 
 impl RenderShader {
@@ -79,6 +91,24 @@ impl ComputeShaderComputeEquirectToCubemap {
     }
 }
 
+pub struct ComputeShaderSkinning(wgpu::ShaderModule);
+
+impl ComputeShaderSkinning {
+    pub fn compute_pipeline_descriptor<'a>(
+        &'a self,
+        opts: ComputePipelineDescriptorPartial<'a>,
+    ) -> wgpu::ComputePipelineDescriptor<'a> {
+        wgpu::ComputePipelineDescriptor {
+            label: Some(opts.label),
+            layout: opts.layout,
+            module: &self.0,
+            entry_point: Some("compute_skinning"),
+            compilation_options: opts.compilation_options,
+            cache: opts.cache,
+        }
+    }
+}
+
 pub struct ComputePipelineDescriptorPartial<'a> {
     pub label: &'a str,
     pub layout: Option<&'a wgpu::PipelineLayout>,
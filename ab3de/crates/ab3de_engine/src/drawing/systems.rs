@@ -3,4 +3,5 @@ pub mod canvas_system;
 pub mod depth_system;
 pub mod light_system;
 pub mod model_system;
+pub mod offscreen_system;
 pub mod skybox_system;
@@ -1,5 +1,7 @@
 use wgpu::util::DeviceExt;
 
+use crate::bounds::Frustum;
+
 pub struct CameraSystem {
     bind_group_layout: wgpu::BindGroupLayout,
 }
@@ -27,8 +29,13 @@ impl CameraSystem {
         &self.bind_group_layout
     }
 
-    pub fn make_entry(&self, device: &wgpu::Device, size: glam::UVec2) -> CameraEntry {
-        CameraEntry::new(device, size, &self.bind_group_layout)
+    pub fn make_entry(
+        &self,
+        device: &wgpu::Device,
+        size: glam::UVec2,
+        camera_data: CameraData,
+    ) -> CameraEntry {
+        CameraEntry::new(device, size, camera_data, &self.bind_group_layout)
     }
 }
 
@@ -48,9 +55,10 @@ impl CameraEntry {
     fn new(
         device: &wgpu::Device,
         size: glam::UVec2,
+        camera_data: CameraData,
         bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let camera = Camera::new(CameraData::default());
+        let camera = Camera::new(camera_data);
         let projection = Projection::new(size, 45.0_f32.to_radians(), 0.1, 100.0);
 
         let mut uniform = CameraUniform::new();
@@ -80,9 +88,13 @@ impl CameraEntry {
         }
     }
 
-    // pub fn camera(&self) -> &Camera {
-    //     &self.camera
-    // }
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(&self.uniform.view_proj)
+    }
 
     pub fn update_camera(&mut self, queue: &wgpu::Queue, f: impl FnOnce(&mut CameraData)) {
         self.camera.update(f);
@@ -1,4 +1,7 @@
-use crate::drawing::{shaders, textures, utils::make_render_pipeline};
+use crate::drawing::{
+    shaders, textures,
+    utils::{RenderState, make_render_pipeline},
+};
 
 pub const CANVAS_COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
@@ -98,7 +101,7 @@ impl HdrToneMappingCanvas {
             config.color_format,
             None,
             &[],
-            wgpu::PrimitiveTopology::TriangleList,
+            RenderState::default(),
             &shaders::r_hdr_tonemapping(device),
         );
 
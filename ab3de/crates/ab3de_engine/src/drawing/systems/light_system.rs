@@ -1,72 +1,56 @@
 use wgpu::util::DeviceExt;
 
-pub struct LightSystem {
-    entry_demo: LightEntryDemo,
-    bind_group_layout_demo: wgpu::BindGroupLayout,
-}
-
-impl LightSystem {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let bind_group_layout_demo =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("[LightSystem::new] bind group layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-            });
-
-        let entry_demo = LightEntryDemo::new(device, &bind_group_layout_demo);
-
-        Self {
-            entry_demo,
-            bind_group_layout_demo,
-        }
-    }
-
-    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
-        &self.bind_group_layout_demo
-    }
+use crate::timeline::FrameTime;
 
-    pub fn update(&mut self, queue: &wgpu::Queue, dt_s: f32) {
-        self.entry_demo.update(queue, dt_s);
-    }
+/// Must match `MAX_LIGHTS` in `definitions/light.wesl`.
+pub const MAX_LIGHTS: usize = 8;
 
-    pub fn entry_demo(&self) -> &LightEntryDemo {
-        &self.entry_demo
-    }
-}
+pub struct LightSystem {
+    entries: Vec<LightEntry>,
 
-pub struct LightEntryDemo {
-    uniform: LightUniform,
+    uniform: LightsUniform,
     buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
 }
 
-impl LightEntryDemo {
-    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
-        let uniform = LightUniform {
-            position: glam::vec3(2.0, 2.0, 2.0),
-            _padding: 0,
-            color: glam::vec3(1.0, 1.0, 1.0),
-            _padding2: 0,
-        };
+impl LightSystem {
+    pub fn new(device: &wgpu::Device, mut entries: Vec<LightEntry>) -> Self {
+        if entries.len() > MAX_LIGHTS {
+            log::warn!(
+                "[LightSystem::new] {} lights given, only the first {} are used",
+                entries.len(),
+                MAX_LIGHTS
+            );
+            entries.truncate(MAX_LIGHTS);
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("[LightSystem::new] bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let mut uniform = LightsUniform::zeroed();
+        Self::fill_uniform(&mut uniform, &entries, &FrameTime::ZERO);
 
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("[LightEntryDemo::new] uniform buffer"),
+            label: Some("[LightSystem::new] uniform buffer"),
             contents: bytemuck::cast_slice(&[uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("[LightEntryDemo::new] bind group"),
-            layout,
+            label: Some("[LightSystem::new] bind group"),
+            layout: &bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: buffer.as_entire_binding(),
@@ -74,24 +58,60 @@ impl LightEntryDemo {
         });
 
         Self {
+            entries,
             uniform,
             buffer,
             bind_group,
+            bind_group_layout,
         }
     }
 
-    fn update(&mut self, queue: &wgpu::Queue, dt_s: f32) {
-        let old_position = self.uniform.position;
-        self.uniform.position =
-            (glam::Quat::from_axis_angle(glam::Vec3::Y, dt_s * std::f32::consts::TAU)
-                * old_position)
-                .into();
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
     }
 
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+
+    pub fn light_count(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, frame_time: &FrameTime) {
+        Self::fill_uniform(&mut self.uniform, &self.entries, frame_time);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    fn fill_uniform(uniform: &mut LightsUniform, entries: &[LightEntry], frame_time: &FrameTime) {
+        for (item, entry) in uniform.items.iter_mut().zip(entries) {
+            item.position = entry.position_at(frame_time);
+            item.color = entry.color;
+        }
+        uniform.count = entries.len() as u32;
+    }
+}
+
+pub struct LightEntry {
+    pub initial_position: glam::Vec3,
+    pub color: glam::Vec3,
+    /// If set, the light orbits the Y axis once per this many seconds.
+    pub orbit_period_s: Option<f32>,
+}
+
+impl LightEntry {
+    /// Only depends on the timeline time, so the same frame is always lit the
+    /// same way.
+    fn position_at(&self, frame_time: &FrameTime) -> glam::Vec3 {
+        match self.orbit_period_s {
+            Some(period_s) if period_s > 0.0 => {
+                let revolutions = (frame_time.time_s / period_s as f64).fract() as f32;
+                glam::Quat::from_axis_angle(glam::Vec3::Y, revolutions * std::f32::consts::TAU)
+                    * self.initial_position
+            }
+            _ => self.initial_position,
+        }
+    }
 }
 
 #[repr(C)]
@@ -102,3 +122,17 @@ struct LightUniform {
     color: glam::Vec3,
     _padding2: u32,
 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightsUniform {
+    items: [LightUniform; MAX_LIGHTS],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+impl LightsUniform {
+    fn zeroed() -> Self {
+        bytemuck::Zeroable::zeroed()
+    }
+}
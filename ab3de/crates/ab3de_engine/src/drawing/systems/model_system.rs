@@ -1,57 +1,136 @@
-use std::{ops::Range, sync::Arc};
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
 
-use wgpu::util::DeviceExt;
-
-use crate::drawing::{
-    models::{Material, Mesh, Model, ModelVertex},
-    shaders,
-    systems::{
-        camera_system::{CameraEntry, CameraSystem},
-        light_system::LightSystem,
-        skybox_system::SkyboxSystem,
+use crate::{
+    animation::{
+        morph,
+        motion::{Motion, MotionPlayer},
+        physics::{self, Physics},
+        skeleton::{BoneTransform, Skeleton, SkeletonState},
+    },
+    bounds::{Aabb, Frustum},
+    drawing::{
+        models::{MaterialState, MaterialUniform, Mesh, Model, ModelVertex, MorphOffset},
+        shaders,
+        systems::{
+            camera_system::{CameraEntry, CameraSystem},
+            light_system::LightSystem,
+            skybox_system::SkyboxSystem,
+        },
+        textures,
+        utils::{RenderState, make_render_pipeline},
     },
-    textures,
-    utils::make_render_pipeline,
+    handles::{InstanceId, ModelId},
+    model_loaders::model_data::{BlendMode, MaterialShading, MorphData},
+    timeline::FrameTime,
 };
 
 pub struct ModelSystem {
-    entries_simple: Vec<ModelEntrySimple>,
-    pipeline_simple: wgpu::RenderPipeline,
+    /// Keyed by id so that draw order follows insertion order.
+    entries_simple: BTreeMap<ModelId, ModelEntrySimple>,
+    pipelines_simple: SimplePipelines,
 
     entry_light_source_indicator: Option<ModelEntryLightSourceIndicator>,
     pipeline_light_source_indicator: wgpu::RenderPipeline,
+
+    skinning_bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_skinning: wgpu::ComputePipeline,
+
+    frame_stats: FrameStats,
+}
+
+/// What was drawn in a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub draw_call_count: u32,
+    /// Instances of all models that were inside the camera's frustum, or
+    /// could not be culled.
+    pub instance_count: u32,
+    pub culled_instance_count: u32,
 }
 
 impl ModelSystem {
     pub fn new(
         device: &wgpu::Device,
         color_format: wgpu::TextureFormat,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
         camera_sys: &CameraSystem,
         light_sys: &LightSystem,
         skybox_sys: &SkyboxSystem,
     ) -> Self {
-        let pipeline_simple = {
+        let pipelines_simple = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("[ModelSystem::new] render pipeline layout for simple models"),
                 bind_group_layouts: &[
-                    texture_bind_group_layout,
+                    material_bind_group_layout,
                     camera_sys.bind_group_layout(),
                     light_sys.bind_group_layout(),
                     skybox_sys.environment_bind_group_layout(),
                 ],
                 push_constant_ranges: &[],
             });
-            make_render_pipeline(
-                "[ModelSystem::new] render pipeline for simple models",
-                device,
-                &layout,
-                color_format,
-                Some(textures::DEPTH_FORMAT),
-                &[ModelVertex::desc(), SimpleInstanceData::desc()],
-                wgpu::PrimitiveTopology::TriangleList,
-                &shaders::r_model_demo(device),
-            )
+            let make_pipeline = |label: &str, state, shader| {
+                make_render_pipeline(
+                    label,
+                    device,
+                    &layout,
+                    color_format,
+                    Some(textures::DEPTH_FORMAT),
+                    &[ModelVertex::desc(), SimpleInstanceData::desc()],
+                    state,
+                    shader,
+                )
+            };
+
+            let standard_shader = shaders::r_model_demo(device);
+            let toon_shader = shaders::r_model_toon(device);
+            let pipelines = (0..SimplePipelines::COUNT)
+                .map(|index| {
+                    let is_toon = index & 0b1000 != 0;
+                    let is_double_sided = index & 0b100 != 0;
+                    let is_blended = index & 0b10 != 0;
+                    let writes_depth = index & 0b1 != 0;
+                    make_pipeline(
+                        &format!(
+                            "[ModelSystem::new] render pipeline for {} models \
+                             (double-sided: {}, blended: {}, writes depth: {})",
+                            if is_toon { "toon shaded" } else { "simple" },
+                            is_double_sided,
+                            is_blended,
+                            writes_depth,
+                        ),
+                        RenderState {
+                            cull_mode: (!is_double_sided).then_some(wgpu::Face::Back),
+                            blend: if is_blended {
+                                wgpu::BlendState::ALPHA_BLENDING
+                            } else {
+                                wgpu::BlendState::REPLACE
+                            },
+                            depth_write_enabled: writes_depth,
+                            ..Default::default()
+                        },
+                        if is_toon {
+                            &toon_shader
+                        } else {
+                            &standard_shader
+                        },
+                    )
+                })
+                .collect();
+
+            SimplePipelines {
+                pipelines,
+                // the inverted hull: only the back faces of the pushed out mesh
+                // show, around the front faces of the mesh itself.
+                toon_edge: make_pipeline(
+                    "[ModelSystem::new] render pipeline for toon edges",
+                    RenderState {
+                        cull_mode: Some(wgpu::Face::Front),
+                        blend: wgpu::BlendState::ALPHA_BLENDING,
+                        ..Default::default()
+                    },
+                    &shaders::r_model_toon_edge(device),
+                ),
+            }
         };
 
         let pipeline_light_source_indicator = {
@@ -72,22 +151,111 @@ impl ModelSystem {
                 color_format,
                 Some(textures::DEPTH_FORMAT),
                 &[ModelVertex::desc()],
-                wgpu::PrimitiveTopology::TriangleList,
+                RenderState::default(),
                 &shaders::r_model_light_source_indicator(device),
             )
         };
 
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let skinning_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("[ModelSystem::new] bind group layout for skinning"),
+                entries: &[
+                    // bones
+                    storage_entry(0, true),
+                    // skin
+                    storage_entry(1, true),
+                    // rest pose vertices
+                    storage_entry(2, true),
+                    // skinned vertices
+                    storage_entry(3, false),
+                    // morph ranges
+                    storage_entry(4, true),
+                    // morph offsets
+                    storage_entry(5, true),
+                    // morph weights
+                    storage_entry(6, true),
+                ],
+            });
+
+        let pipeline_skinning = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("[ModelSystem::new] compute pipeline layout for skinning"),
+                bind_group_layouts: &[&skinning_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(
+                &shaders::c_skinning(device).compute_pipeline_descriptor(
+                    shaders::ComputePipelineDescriptorPartial {
+                        label: "[ModelSystem::new] compute pipeline for skinning",
+                        layout: Some(&layout),
+                        compilation_options: Default::default(),
+                        cache: None,
+                    },
+                ),
+            )
+        };
+
         Self {
-            entries_simple: vec![],
-            pipeline_simple,
+            entries_simple: BTreeMap::new(),
+            pipelines_simple,
             entry_light_source_indicator: None,
             pipeline_light_source_indicator,
+            skinning_bind_group_layout,
+            pipeline_skinning,
+            frame_stats: FrameStats::default(),
+        }
+    }
+
+    pub fn update(&mut self, frame_time: &FrameTime) {
+        for entry in self.entries_simple.values_mut() {
+            entry.update(frame_time);
+        }
+    }
+
+    /// Updates whatever instances, poses and morphs changed since the last
+    /// call, and skins the posed models. Must be called before culling.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("[ModelSystem::prepare] skinning encoder"),
+        });
+        let mut has_skinned = false;
+
+        for entry in self.entries_simple.values_mut() {
+            entry.prepare();
+            entry.prepare_morphs(device, queue);
+            has_skinned |= entry.prepare_skinning(
+                device,
+                queue,
+                &mut encoder,
+                &self.skinning_bind_group_layout,
+                &self.pipeline_skinning,
+            );
+        }
+
+        if has_skinned {
+            queue.submit(Some(encoder.finish()));
         }
     }
 
-    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now_ms: u64) {
-        for entry in &mut self.entries_simple {
-            entry.update(device, queue, now_ms);
+    /// Uploads the instances inside the frustum of `camera_entry`, and starts
+    /// the [`FrameStats`] of a new frame. Must be called before drawing.
+    pub fn cull(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera_entry: &CameraEntry) {
+        let frustum = camera_entry.frustum();
+        self.frame_stats = FrameStats::default();
+        for entry in self.entries_simple.values_mut() {
+            let (instance_count, culled_instance_count) = entry.cull(device, queue, &frustum);
+            self.frame_stats.instance_count += instance_count;
+            self.frame_stats.culled_instance_count += culled_instance_count;
         }
     }
 
@@ -98,10 +266,31 @@ impl ModelSystem {
         light_sys: &LightSystem,
         skybox_sys: &SkyboxSystem,
     ) {
-        for entry in &mut self.entries_simple {
-            entry.draw(
+        for entry in self.entries_simple.values_mut() {
+            self.frame_stats.draw_call_count += entry.draw(
                 render_pass,
-                &self.pipeline_simple,
+                &self.pipelines_simple,
+                MeshPass::Opaque,
+                camera_entry,
+                light_sys,
+                skybox_sys,
+            );
+        }
+
+        // blended meshes need what is behind them drawn first, so models go
+        // from back to front.
+        let camera_position = *camera_entry.camera().position();
+        let mut entries = self
+            .entries_simple
+            .values_mut()
+            .map(|entry| (entry.distance_squared_to(camera_position), entry))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        for (_, entry) in entries {
+            self.frame_stats.draw_call_count += entry.draw(
+                render_pass,
+                &self.pipelines_simple,
+                MeshPass::Transparent,
                 camera_entry,
                 light_sys,
                 skybox_sys,
@@ -115,11 +304,29 @@ impl ModelSystem {
                 camera_entry,
                 light_sys,
             );
+            self.frame_stats.draw_call_count += 1;
         }
     }
 
-    pub fn push_model_entry_simple(&mut self, model_entry: ModelEntrySimple) {
-        self.entries_simple.push(model_entry);
+    /// Of the frame last culled and drawn.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
+
+    pub fn insert_model_entry_simple(&mut self, id: ModelId, model_entry: ModelEntrySimple) {
+        self.entries_simple.insert(id, model_entry);
+    }
+
+    pub fn remove_model_entry_simple(&mut self, id: ModelId) -> Option<ModelEntrySimple> {
+        self.entries_simple.remove(&id)
+    }
+
+    pub fn model_entry_simple(&self, id: ModelId) -> Option<&ModelEntrySimple> {
+        self.entries_simple.get(&id)
+    }
+
+    pub fn model_entry_simple_mut(&mut self, id: ModelId) -> Option<&mut ModelEntrySimple> {
+        self.entries_simple.get_mut(&id)
     }
 
     pub fn set_model_entry_light_source_indicator(
@@ -130,94 +337,789 @@ impl ModelSystem {
     }
 }
 
+/// One per combination of [`MaterialState`], apart from edges and alpha
+/// cutoffs, plus one for the edges of toon shaded materials.
+struct SimplePipelines {
+    /// See [`SimplePipelines::get`].
+    pipelines: Vec<wgpu::RenderPipeline>,
+    toon_edge: wgpu::RenderPipeline,
+}
+
+impl SimplePipelines {
+    const COUNT: usize = 16;
+
+    fn get(&self, state: &MaterialState) -> &wgpu::RenderPipeline {
+        let is_toon = matches!(state.shading, MaterialShading::Toon { .. });
+        let is_blended = state.blend_mode == BlendMode::AlphaBlend;
+        let index = (is_toon as usize) << 3
+            | (state.is_double_sided as usize) << 2
+            | (is_blended as usize) << 1
+            | state.writes_depth as usize;
+        &self.pipelines[index]
+    }
+}
+
+/// Whether `old` and `new` name the same things in the same order, so that
+/// what is kept for each by index still applies to it.
+fn same_names<T>(old: &[T], new: &[T], name: fn(&T) -> &str) -> bool {
+    old.len() == new.len() && old.iter().zip(new).all(|(a, b)| name(a) == name(b))
+}
+
+/// Opaque meshes of all models are drawn before any transparent ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeshPass {
+    /// Opaque and alpha tested meshes.
+    Opaque,
+    /// Alpha blended meshes, in material order, and then edges.
+    Transparent,
+}
+
+impl MeshPass {
+    fn of(blend_mode: BlendMode) -> Self {
+        match blend_mode {
+            BlendMode::Opaque | BlendMode::AlphaTest { .. } => Self::Opaque,
+            BlendMode::AlphaBlend => Self::Transparent,
+        }
+    }
+}
+
+/// A model drawn once per instance. Instances come from an optional
+/// [`SimpleInstancesProvider`], followed by the individually managed ones.
 pub struct ModelEntrySimple {
     model: Arc<Model>,
-    instances_provider: Box<dyn SimpleInstancesProvider>,
+    instances_provider: Option<Box<dyn SimpleInstancesProvider>>,
+
+    managed_instances: BTreeMap<InstanceId, ManagedInstance>,
+    next_instance_id: u64,
+
+    /// Every instance that is not hidden.
+    instance_data_vec: Vec<SimpleInstanceData>,
+    is_instance_data_dirty: bool,
+    /// The instances the last [`ModelEntrySimple::cull`] kept, as in
+    /// [`Self::instance_buffer`].
+    drawn_instance_data_vec: Vec<SimpleInstanceData>,
+    scratch_instance_data_vec: Vec<SimpleInstanceData>,
     instance_buffer: wgpu::Buffer,
+
+    /// One transform per bone of the model's skeleton, if it has one.
+    pose: Vec<BoneTransform>,
+    /// Per bone; only matters for IK bones.
+    ik_enabled: Vec<bool>,
+    is_pose_dirty: bool,
+    /// One weight per morph of the model.
+    morph_weights: Vec<f32>,
+    /// [`Self::morph_weights`] with group morphs resolved.
+    effective_morph_weights: Vec<f32>,
+    motion_player: Option<MotionPlayer>,
+    /// Per material of the model, for models with material morphs. Created
+    /// on the first [`ModelEntrySimple::prepare_morphs`].
+    morphed_materials: Option<Vec<MorphedMaterial>>,
+    /// Created on the first [`ModelEntrySimple::prepare_skinning`].
+    skinning: Option<EntrySkinning>,
+    /// Of the last update.
+    time_s: f64,
+}
+
+struct EntrySkinning {
+    skeleton_state: SkeletonState,
+    /// The pose with bone morphs applied.
+    morphed_pose: Vec<BoneTransform>,
+    /// For models with rigid bodies. Created on the first evaluation, and
+    /// again whenever time goes back.
+    physics: Option<Physics>,
+    /// Scratch space for stepping physics.
+    sampled_pose: SampledPose,
+    bone_buffer: wgpu::Buffer,
+    morph_weight_buffer: wgpu::Buffer,
+    /// Bound in place of the morph buffers of meshes without morphs.
+    empty_morph_buffer: wgpu::Buffer,
+    /// Per mesh of the model; `None` for meshes without skin.
+    meshes: Vec<Option<SkinnedMesh>>,
+}
+
+#[derive(Default)]
+struct SampledPose {
+    pose: Vec<BoneTransform>,
+    ik_enabled: Vec<bool>,
+    morph_weights: Vec<f32>,
+    effective_morph_weights: Vec<f32>,
+}
+
+struct SkinnedMesh {
+    vertex_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+struct MorphedMaterial {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+struct ManagedInstance {
+    transform: InstanceTransform,
+    is_visible: bool,
 }
 
 impl ModelEntrySimple {
     pub fn new(
         device: &wgpu::Device,
         model: Arc<Model>,
-        mut instances_provider: Box<dyn SimpleInstancesProvider>,
+        mut instances_provider: Option<Box<dyn SimpleInstancesProvider>>,
     ) -> Self {
-        instances_provider.update(0);
+        if let Some(instances_provider) = &mut instances_provider {
+            instances_provider.update(&FrameTime::ZERO);
+        }
 
-        let instances_data_slice = instances_provider.instance_data_slice();
-        let instance_data_bytes = bytemuck::cast_slice(instances_data_slice);
-        let instance_buffer = Self::make_instance_buffer(device, instance_data_bytes);
+        let bone_count = model
+            .skeleton()
+            .map_or(0, |skeleton| skeleton.bones().len());
 
         Self {
+            pose: vec![BoneTransform::IDENTITY; bone_count],
+            ik_enabled: vec![true; bone_count],
+            is_pose_dirty: true,
+            morph_weights: vec![0.0; model.morphs().len()],
+            effective_morph_weights: vec![],
+            motion_player: None,
+            morphed_materials: None,
+            skinning: None,
+            time_s: 0.0,
             model,
             instances_provider,
-            instance_buffer,
+            managed_instances: BTreeMap::new(),
+            next_instance_id: 0,
+            instance_data_vec: vec![],
+            is_instance_data_dirty: true,
+            drawn_instance_data_vec: vec![],
+            scratch_instance_data_vec: vec![],
+            instance_buffer: Self::make_instance_buffer(device, 0),
+        }
+    }
+
+    pub fn insert_instance(&mut self, transform: InstanceTransform) -> InstanceId {
+        let id = InstanceId::new(self.next_instance_id);
+        self.next_instance_id += 1;
+
+        self.managed_instances.insert(
+            id,
+            ManagedInstance {
+                transform,
+                is_visible: true,
+            },
+        );
+        self.is_instance_data_dirty = true;
+        id
+    }
+
+    /// Returns `false` if there is no such instance.
+    pub fn remove_instance(&mut self, id: InstanceId) -> bool {
+        let is_removed = self.managed_instances.remove(&id).is_some();
+        self.is_instance_data_dirty |= is_removed;
+        is_removed
+    }
+
+    /// Returns `false` if there is no such instance.
+    pub fn set_instance_transform(&mut self, id: InstanceId, transform: InstanceTransform) -> bool {
+        let Some(instance) = self.managed_instances.get_mut(&id) else {
+            return false;
+        };
+        instance.transform = transform;
+        self.is_instance_data_dirty = true;
+        true
+    }
+
+    /// Returns `false` if there is no such instance.
+    pub fn set_instance_visibility(&mut self, id: InstanceId, is_visible: bool) -> bool {
+        let Some(instance) = self.managed_instances.get_mut(&id) else {
+            return false;
+        };
+        if instance.is_visible != is_visible {
+            instance.is_visible = is_visible;
+            self.is_instance_data_dirty = true;
         }
+        true
     }
 
-    fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, now_ms: u64) {
-        self.instances_provider.update(now_ms);
+    pub fn model(&self) -> &Arc<Model> {
+        &self.model
+    }
+
+    /// Swaps in another version of the model, e.g. one reloaded from disk.
+    /// Instances and the motion are kept. So are bones, IK switches and
+    /// morphs set by hand, unless the new version names its bones or morphs
+    /// differently, which sends them back to their rest values.
+    pub fn set_model(&mut self, model: Arc<Model>) {
+        let old_bones = self
+            .model
+            .skeleton()
+            .map_or(&[][..], |skeleton| skeleton.bones());
+        let new_bones = model
+            .skeleton()
+            .map_or(&[][..], |skeleton| skeleton.bones());
+        if !same_names(old_bones, new_bones, |bone| &bone.name) {
+            self.pose = vec![BoneTransform::IDENTITY; new_bones.len()];
+            self.ik_enabled = vec![true; new_bones.len()];
+        }
+        let keeps_morphs = same_names(self.model.morphs(), model.morphs(), |morph| &morph.name);
+        if !keeps_morphs {
+            self.morph_weights = vec![0.0; model.morphs().len()];
+        }
+        self.is_pose_dirty = true;
+        self.effective_morph_weights = vec![];
+        self.morphed_materials = None;
+        self.skinning = None;
+        self.is_instance_data_dirty = true;
+        self.model = model;
 
-        let instances_data_slice = self.instances_provider.instance_data_slice();
-        let instance_data_bytes = bytemuck::cast_slice(instances_data_slice);
+        let motion = self
+            .motion_player
+            .as_ref()
+            .map(|motion_player| motion_player.motion().clone());
+        self.set_motion(motion);
+    }
+
+    /// See [`Model::bounds`].
+    pub fn bounds(&self) -> &Aabb {
+        self.model.bounds()
+    }
 
-        if self.instance_buffer.size() != instance_data_bytes.len() as wgpu::BufferAddress {
+    /// Where the model's bounds are for this instance, see
+    /// [`SimpleInstanceData::bounds`].
+    pub fn instance_bounds(&self, id: InstanceId) -> Option<Aabb> {
+        self.managed_instances
+            .get(&id)
+            .map(|i| SimpleInstanceData::from(&i.transform).bounds(self.model.bounds()))
+    }
+
+    pub fn instance_transform(&self, id: InstanceId) -> Option<InstanceTransform> {
+        self.managed_instances.get(&id).map(|i| i.transform)
+    }
+
+    pub fn instance_ids(&self) -> impl Iterator<Item = InstanceId> + '_ {
+        self.managed_instances.keys().copied()
+    }
+
+    /// Includes provided instances, but not hidden ones.
+    pub fn drawn_instance_count(&self) -> usize {
+        self.instances_provider
+            .as_ref()
+            .map_or(0, |instances_provider| instances_provider.instance_count())
+            + self
+                .managed_instances
+                .values()
+                .filter(|i| i.is_visible)
+                .count()
+    }
+
+    pub fn skeleton(&self) -> Option<&Arc<Skeleton>> {
+        self.model.skeleton()
+    }
+
+    pub fn bone_transform(&self, bone_index: usize) -> Option<BoneTransform> {
+        self.pose.get(bone_index).copied()
+    }
+
+    /// Returns `false` if there is no such bone.
+    pub fn set_bone_transform(&mut self, bone_index: usize, transform: BoneTransform) -> bool {
+        let Some(bone_transform) = self.pose.get_mut(bone_index) else {
+            return false;
+        };
+        *bone_transform = transform;
+        self.is_pose_dirty = true;
+        true
+    }
+
+    /// Puts every bone back into its rest pose.
+    pub fn reset_pose(&mut self) {
+        self.pose.fill(BoneTransform::IDENTITY);
+        self.is_pose_dirty = true;
+    }
+
+    pub fn is_ik_enabled(&self, bone_index: usize) -> Option<bool> {
+        self.ik_enabled.get(bone_index).copied()
+    }
+
+    /// Returns `false` if there is no such bone.
+    pub fn set_ik_enabled(&mut self, bone_index: usize, is_enabled: bool) -> bool {
+        let Some(ik_enabled) = self.ik_enabled.get_mut(bone_index) else {
+            return false;
+        };
+        *ik_enabled = is_enabled;
+        self.is_pose_dirty = true;
+        true
+    }
+
+    pub fn morphs(&self) -> &[MorphData] {
+        self.model.morphs()
+    }
+
+    pub fn morph_weight(&self, morph_index: usize) -> Option<f32> {
+        self.morph_weights.get(morph_index).copied()
+    }
+
+    /// Returns `false` if there is no such morph. Weights are usually `0..=1`,
+    /// but may go beyond.
+    pub fn set_morph_weight(&mut self, morph_index: usize, weight: f32) -> bool {
+        let Some(morph_weight) = self.morph_weights.get_mut(morph_index) else {
+            return false;
+        };
+        *morph_weight = weight;
+        self.is_pose_dirty = true;
+        true
+    }
+
+    /// Binds `motion` to the model's bones and morphs by name. From the next
+    /// update on, it overwrites the transforms and weights it has tracks for.
+    pub fn set_motion(&mut self, motion: Option<Arc<Motion>>) {
+        self.motion_player = motion.map(|motion| {
+            let bones = self.model.skeleton().map_or(&[][..], |s| s.bones());
+            MotionPlayer::new(
+                motion,
+                bones,
+                self.model.morphs(),
+                self.model.motion_scale(),
+            )
+        });
+    }
+
+    fn provided_instance_data_slice(&self) -> &[SimpleInstanceData] {
+        match &self.instances_provider {
+            Some(instances_provider) => instances_provider.instance_data_slice(),
+            None => &[],
+        }
+    }
+
+    fn update(&mut self, frame_time: &FrameTime) {
+        if let Some(instances_provider) = &mut self.instances_provider {
+            instances_provider.update(frame_time);
+            self.is_instance_data_dirty = true;
+        }
+
+        if let Some(motion_player) = &self.motion_player {
+            motion_player.sample(
+                frame_time.time_s,
+                &mut self.pose,
+                &mut self.ik_enabled,
+                &mut self.morph_weights,
+            );
+            self.is_pose_dirty = true;
+        }
+
+        // physics moves on with time, even without a motion.
+        if frame_time.time_s != self.time_s && !self.model.rigid_bodies().is_empty() {
+            self.is_pose_dirty = true;
+        }
+        self.time_s = frame_time.time_s;
+    }
+
+    fn prepare(&mut self) {
+        if !self.is_instance_data_dirty {
+            return;
+        }
+        self.is_instance_data_dirty = false;
+
+        let mut instance_data_vec = std::mem::take(&mut self.instance_data_vec);
+        instance_data_vec.clear();
+        instance_data_vec.extend_from_slice(self.provided_instance_data_slice());
+        instance_data_vec.extend(
+            self.managed_instances
+                .values()
+                .filter(|i| i.is_visible)
+                .map(|i| SimpleInstanceData::from(&i.transform)),
+        );
+        self.instance_data_vec = instance_data_vec;
+    }
+
+    /// Uploads the instances inside `frustum`, unless they are what was
+    /// uploaded last time. Skinned models are culled by the bounds of their
+    /// current pose. Returns how many instances are drawn and how many were
+    /// culled.
+    fn cull(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frustum: &Frustum,
+    ) -> (u32, u32) {
+        let mut drawn_instance_data_vec = std::mem::take(&mut self.scratch_instance_data_vec);
+        drawn_instance_data_vec.clear();
+        let bounds = match (&self.skinning, self.model.skinned_bounds()) {
+            (Some(skinning), Some(skinned_bounds)) => skinned_bounds.posed(
+                &skinning.skeleton_state.world_matrices,
+                &self.effective_morph_weights,
+            ),
+            _ => *self.model.bounds(),
+        };
+        drawn_instance_data_vec.extend(
+            self.instance_data_vec
+                .iter()
+                .filter(|instance_data| frustum.intersects_aabb(&instance_data.bounds(&bounds))),
+        );
+        let counts = (
+            drawn_instance_data_vec.len() as u32,
+            (self.instance_data_vec.len() - drawn_instance_data_vec.len()) as u32,
+        );
+
+        // the previous instances become the scratch space of the next call.
+        self.scratch_instance_data_vec =
+            std::mem::replace(&mut self.drawn_instance_data_vec, drawn_instance_data_vec);
+        let instance_data_bytes: &[u8] = bytemuck::cast_slice(&self.drawn_instance_data_vec);
+        if instance_data_bytes == bytemuck::cast_slice::<_, u8>(&self.scratch_instance_data_vec) {
+            return counts;
+        }
+
+        // only grows, since instances come and go all the time in editors.
+        if self.instance_buffer.size() < instance_data_bytes.len() as wgpu::BufferAddress {
             self.instance_buffer.destroy();
-            self.instance_buffer = Self::make_instance_buffer(device, instance_data_bytes);
-        } else {
+            self.instance_buffer =
+                Self::make_instance_buffer(device, instance_data_bytes.len().next_power_of_two());
+        }
+        if !instance_data_bytes.is_empty() {
             queue.write_buffer(&self.instance_buffer, 0, instance_data_bytes);
         }
+        counts
+    }
+
+    /// Resolves group morphs and uploads the colors of morphed materials.
+    /// Vertex, UV and bone morphs are left to [`Self::prepare_skinning`].
+    fn prepare_morphs(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.is_pose_dirty {
+            return;
+        }
+
+        let morphs = self.model.morphs();
+        morph::effective_weights(
+            morphs,
+            &self.morph_weights,
+            &mut self.effective_morph_weights,
+        );
+
+        if !morph::has_material_morphs(morphs) {
+            return;
+        }
+        let materials = self.model.materials();
+        let morphed_materials = self.morphed_materials.get_or_insert_with(|| {
+            materials
+                .iter()
+                .map(|material| {
+                    let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("[ModelEntrySimple::prepare_morphs] material uniform buffer"),
+                        size: std::mem::size_of::<MaterialUniform>() as wgpu::BufferAddress,
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    });
+                    let bind_group = material.bind_group_with_uniform(
+                        "[ModelEntrySimple::prepare_morphs] bind group for a morphed material",
+                        device,
+                        &uniform_buffer,
+                    );
+                    MorphedMaterial {
+                        uniform_buffer,
+                        bind_group,
+                    }
+                })
+                .collect()
+        });
+
+        let mut uniforms = materials
+            .iter()
+            .map(|material| *material.uniform())
+            .collect::<Vec<_>>();
+        morph::apply_material_morphs(morphs, &self.effective_morph_weights, &mut uniforms);
+
+        for (morphed_material, uniform) in morphed_materials.iter().zip(&uniforms) {
+            queue.write_buffer(
+                &morphed_material.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[*uniform]),
+            );
+        }
+    }
+
+    /// Returns whether skinning was recorded into `encoder`.
+    fn prepare_skinning(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        pipeline: &wgpu::ComputePipeline,
+    ) -> bool {
+        if !self.is_pose_dirty {
+            return false;
+        }
+        self.is_pose_dirty = false;
+
+        let Some(skeleton) = self.model.skeleton().cloned() else {
+            return false;
+        };
+        let meshes = self.model.meshes();
+        let mut skinning = match self.skinning.take() {
+            Some(skinning) => skinning,
+            None => EntrySkinning::new(
+                device,
+                &skeleton,
+                &meshes,
+                self.model.morphs().len(),
+                bind_group_layout,
+            ),
+        };
+
+        self.evaluate_skeleton(&skeleton, &mut skinning);
+        let bones = skinning
+            .skeleton_state
+            .world_matrices
+            .iter()
+            .enumerate()
+            .map(|(bone_index, world_matrix)| SkinningBone {
+                matrix: skeleton.skinning_matrix(bone_index, world_matrix),
+                rotation: world_matrix.to_scale_rotation_translation().1,
+            })
+            .collect::<Vec<_>>();
+        if !bones.is_empty() {
+            queue.write_buffer(&skinning.bone_buffer, 0, bytemuck::cast_slice(&bones));
+        }
+        if !self.effective_morph_weights.is_empty() {
+            queue.write_buffer(
+                &skinning.morph_weight_buffer,
+                0,
+                bytemuck::cast_slice(&self.effective_morph_weights),
+            );
+        }
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("[ModelEntrySimple::prepare_skinning] compute pass for skinning"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(pipeline);
+            for (mesh, skinned_mesh) in meshes.iter().zip(&skinning.meshes) {
+                let Some(skinned_mesh) = skinned_mesh else {
+                    continue;
+                };
+                pass.set_bind_group(0, &skinned_mesh.bind_group, &[]);
+                pass.dispatch_workgroups(mesh.vertex_count().div_ceil(64), 1, 1);
+            }
+        }
+
+        self.skinning = Some(skinning);
+        true
+    }
+
+    /// Evaluates the skeleton for the current pose, with physics stepped up
+    /// to the current time. Physics only depends on the time and the motion:
+    /// steps in between frames replay the motion at their own time, and
+    /// going back in time starts over from `0`.
+    fn evaluate_skeleton(&self, skeleton: &Skeleton, skinning: &mut EntrySkinning) {
+        skinning.morphed_pose.clone_from(&self.pose);
+        morph::apply_bone_morphs(
+            self.model.morphs(),
+            &self.effective_morph_weights,
+            &mut skinning.morphed_pose,
+        );
+
+        let rigid_bodies = self.model.rigid_bodies();
+        if rigid_bodies.is_empty() {
+            skeleton.evaluate(
+                &skinning.morphed_pose,
+                &self.ik_enabled,
+                &mut skinning.skeleton_state,
+            );
+            return;
+        }
+
+        let physics = physics::simulate_until(
+            &mut skinning.physics,
+            self.time_s,
+            rigid_bodies,
+            self.model.joints(),
+            skeleton,
+            &mut skinning.skeleton_state,
+            |time_s, state| {
+                self.sample_pose(time_s, &mut skinning.sampled_pose);
+                skeleton.evaluate_before_physics(
+                    &skinning.sampled_pose.pose,
+                    &skinning.sampled_pose.ik_enabled,
+                    state,
+                );
+            },
+        );
+
+        skeleton.evaluate_before_physics(
+            &skinning.morphed_pose,
+            &self.ik_enabled,
+            &mut skinning.skeleton_state,
+        );
+        physics.apply(
+            skeleton,
+            &skinning.morphed_pose,
+            &mut skinning.skeleton_state,
+        );
+        skeleton.evaluate_after_physics(
+            &skinning.morphed_pose,
+            &self.ik_enabled,
+            &mut skinning.skeleton_state,
+        );
+    }
+
+    /// The pose at `time_s`: the motion's where it has tracks, and the
+    /// current one elsewhere, with bone morphs applied.
+    fn sample_pose(&self, time_s: f64, sampled_pose: &mut SampledPose) {
+        sampled_pose.pose.clone_from(&self.pose);
+        sampled_pose.ik_enabled.clone_from(&self.ik_enabled);
+        sampled_pose.morph_weights.clone_from(&self.morph_weights);
+        if let Some(motion_player) = &self.motion_player {
+            motion_player.sample(
+                time_s,
+                &mut sampled_pose.pose,
+                &mut sampled_pose.ik_enabled,
+                &mut sampled_pose.morph_weights,
+            );
+        }
+
+        let morphs = self.model.morphs();
+        morph::effective_weights(
+            morphs,
+            &sampled_pose.morph_weights,
+            &mut sampled_pose.effective_morph_weights,
+        );
+        morph::apply_bone_morphs(
+            morphs,
+            &sampled_pose.effective_morph_weights,
+            &mut sampled_pose.pose,
+        );
+    }
+
+    /// From `position` to the nearest drawn instance.
+    fn distance_squared_to(&self, position: glam::Vec3) -> f32 {
+        self.drawn_instance_data_vec
+            .iter()
+            .map(|instance_data| {
+                instance_data
+                    .model
+                    .w_axis
+                    .truncate()
+                    .distance_squared(position)
+            })
+            .fold(f32::INFINITY, f32::min)
     }
 
     fn draw(
         &mut self,
         render_pass: &mut wgpu::RenderPass<'_>,
-        pipeline: &wgpu::RenderPipeline,
+        pipelines: &SimplePipelines,
+        pass: MeshPass,
         camera_entry: &CameraEntry,
         light_sys: &LightSystem,
         skybox_sys: &SkyboxSystem,
-    ) {
-        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-        render_pass.set_pipeline(pipeline);
+    ) -> u32 {
+        if self.drawn_instance_data_vec.is_empty() {
+            return 0;
+        }
 
-        for mesh in self.model.meshes().iter() {
-            let material = &self.model.materials()[mesh.material_index()];
+        let instance_data_size =
+            std::mem::size_of_val(self.drawn_instance_data_vec.as_slice()) as wgpu::BufferAddress;
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..instance_data_size));
 
-            Self::draw_mesh_instanced(
+        let meshes = self.model.meshes();
+        let materials = self.model.materials();
+        let mut draw_call_count = 0;
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            let state = materials[mesh.material_index()].state();
+            if MeshPass::of(state.blend_mode) != pass {
+                continue;
+            }
+            render_pass.set_pipeline(pipelines.get(state));
+            self.draw_mesh(
                 render_pass,
+                mesh_index,
                 mesh,
-                material,
-                0..self.instances_provider.instance_count() as u32,
                 camera_entry,
                 light_sys,
                 skybox_sys,
             );
+            draw_call_count += 1;
         }
+        if pass != MeshPass::Transparent {
+            return draw_call_count;
+        }
+
+        // as in MMD, edges are drawn after all of the model's materials.
+        render_pass.set_pipeline(&pipelines.toon_edge);
+        for (mesh_index, mesh) in meshes.iter().enumerate() {
+            if let MaterialShading::Toon { edge: Some(_) } =
+                materials[mesh.material_index()].state().shading
+            {
+                self.draw_mesh(
+                    render_pass,
+                    mesh_index,
+                    mesh,
+                    camera_entry,
+                    light_sys,
+                    skybox_sys,
+                );
+                draw_call_count += 1;
+            }
+        }
+        draw_call_count
+    }
+
+    /// With whatever pipeline is set.
+    fn draw_mesh(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        mesh_index: usize,
+        mesh: &Mesh,
+        camera_entry: &CameraEntry,
+        light_sys: &LightSystem,
+        skybox_sys: &SkyboxSystem,
+    ) {
+        let material_bind_group = match &self.morphed_materials {
+            Some(morphed_materials) => &morphed_materials[mesh.material_index()].bind_group,
+            None => self.model.materials()[mesh.material_index()].bind_group(),
+        };
+        let vertex_buffer = self
+            .skinning
+            .as_ref()
+            .and_then(|skinning| skinning.meshes[mesh_index].as_ref())
+            .map_or(mesh.vertex_buffer(), |skinned_mesh| {
+                &skinned_mesh.vertex_buffer
+            });
+
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        Self::draw_mesh_instanced(
+            render_pass,
+            mesh,
+            material_bind_group,
+            0..self.drawn_instance_data_vec.len() as u32,
+            camera_entry,
+            light_sys,
+            skybox_sys,
+        );
     }
 
     fn draw_mesh_instanced(
         render_pass: &mut wgpu::RenderPass<'_>,
         mesh: &Mesh,
-        material: &Material,
+        material_bind_group: &wgpu::BindGroup,
         instance_range: Range<u32>,
         camera_entry: &CameraEntry,
         light_sys: &LightSystem,
         skybox_sys: &SkyboxSystem,
     ) {
-        render_pass.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
         render_pass.set_index_buffer(mesh.index_buffer().slice(..), wgpu::IndexFormat::Uint32);
-        render_pass.set_bind_group(0, material.bind_group(), &[]);
+        render_pass.set_bind_group(0, material_bind_group, &[]);
         render_pass.set_bind_group(1, camera_entry.bind_group(), &[]);
-        render_pass.set_bind_group(2, light_sys.entry_demo().bind_group(), &[]);
+        render_pass.set_bind_group(2, light_sys.bind_group(), &[]);
         render_pass.set_bind_group(3, skybox_sys.environment_bind_group(), &[]);
         render_pass.draw_indexed(0..mesh.index_count(), 0, instance_range);
     }
 
-    fn make_instance_buffer(device: &wgpu::Device, instance_data_bytes: &[u8]) -> wgpu::Buffer {
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: instance_data_bytes,
+    fn make_instance_buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("[ModelEntrySimple::make_instance_buffer] instance buffer"),
+            size: size as wgpu::BufferAddress,
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         })
     }
 }
@@ -225,9 +1127,131 @@ impl ModelEntrySimple {
 impl Drop for ModelEntrySimple {
     fn drop(&mut self) {
         self.instance_buffer.destroy();
+        for morphed_material in self.morphed_materials.iter().flatten() {
+            morphed_material.uniform_buffer.destroy();
+        }
     }
 }
 
+impl EntrySkinning {
+    fn new(
+        device: &wgpu::Device,
+        skeleton: &Skeleton,
+        meshes: &[Mesh],
+        morph_count: usize,
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // storage bindings can't be empty.
+        let bone_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("[EntrySkinning::new] bone buffer"),
+            size: (skeleton.bones().len().max(1) * std::mem::size_of::<SkinningBone>())
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let morph_weight_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("[EntrySkinning::new] morph weight buffer"),
+            size: (morph_count.max(1) * std::mem::size_of::<f32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // zeroed, so it reads as empty ranges and as a single unused offset.
+        let empty_morph_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("[EntrySkinning::new] empty morph buffer"),
+            size: std::mem::size_of::<MorphOffset>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let meshes = meshes
+            .iter()
+            .map(|mesh| {
+                let skin_buffer = mesh.skin_buffer()?;
+                let morph_buffers = mesh.morph_buffers();
+                let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("[EntrySkinning::new] skinned vertex buffer"),
+                    size: mesh.vertex_buffer().size(),
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("[EntrySkinning::new] bind group for skinning"),
+                    layout: bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: bone_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: skin_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: mesh.vertex_buffer().as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: vertex_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: morph_buffers
+                                .map_or(&empty_morph_buffer, |buffers| &buffers.range_buffer)
+                                .as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 5,
+                            resource: morph_buffers
+                                .map_or(&empty_morph_buffer, |buffers| &buffers.offset_buffer)
+                                .as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 6,
+                            resource: morph_weight_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+                Some(SkinnedMesh {
+                    vertex_buffer,
+                    bind_group,
+                })
+            })
+            .collect();
+
+        Self {
+            skeleton_state: SkeletonState::default(),
+            morphed_pose: vec![],
+            physics: None,
+            sampled_pose: SampledPose::default(),
+            bone_buffer,
+            morph_weight_buffer,
+            empty_morph_buffer,
+            meshes,
+        }
+    }
+}
+
+impl Drop for EntrySkinning {
+    fn drop(&mut self) {
+        self.bone_buffer.destroy();
+        self.morph_weight_buffer.destroy();
+        self.empty_morph_buffer.destroy();
+        for skinned_mesh in self.meshes.iter().flatten() {
+            skinned_mesh.vertex_buffer.destroy();
+        }
+    }
+}
+
+/// See `Bone` in `skinning.wesl`.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkinningBone {
+    /// From the rest pose to the current pose, in model space.
+    matrix: glam::Mat4,
+    rotation: glam::Quat,
+}
+
 pub struct ModelEntryLightSourceIndicator {
     /// ## TODO
     ///
@@ -262,8 +1286,37 @@ impl ModelEntryLightSourceIndicator {
         render_pass.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
         render_pass.set_index_buffer(mesh.index_buffer().slice(..), wgpu::IndexFormat::Uint32);
         render_pass.set_bind_group(0, camera_entry.bind_group(), &[]);
-        render_pass.set_bind_group(1, light_sys.entry_demo().bind_group(), &[]);
-        render_pass.draw_indexed(0..mesh.index_count(), 0, 0..1);
+        render_pass.set_bind_group(1, light_sys.bind_group(), &[]);
+        // one instance per light, see `model_light_source_indicator.wesl`.
+        render_pass.draw_indexed(0..mesh.index_count(), 0, 0..light_sys.light_count());
+    }
+}
+
+/// Where an individually managed instance is placed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstanceTransform {
+    pub position: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+}
+
+impl InstanceTransform {
+    pub const IDENTITY: Self = Self {
+        position: glam::Vec3::ZERO,
+        rotation: glam::Quat::IDENTITY,
+        scale: glam::Vec3::ONE,
+    };
+}
+
+impl Default for InstanceTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<&InstanceTransform> for SimpleInstanceData {
+    fn from(value: &InstanceTransform) -> Self {
+        Self::new(value.position, value.rotation, value.scale)
     }
 }
 
@@ -276,15 +1329,34 @@ pub struct SimpleInstanceData {
 }
 
 impl SimpleInstanceData {
+    /// Places `model_bounds`, in model space, where this instance is.
+    pub fn bounds(&self, model_bounds: &Aabb) -> Aabb {
+        model_bounds.transformed(&self.matrix())
+    }
+
+    /// Scaled, unlike [`Self::model`], which leaves scaling to the shaders.
+    fn matrix(&self) -> glam::Mat4 {
+        self.model * glam::Mat4::from_scale(self.scale)
+    }
+
+    pub fn new(position: glam::Vec3, rotation: glam::Quat, scale: glam::Vec3) -> Self {
+        let rotation = rotation.normalize();
+        Self {
+            model: glam::Mat4::from_translation(position) * glam::Mat4::from_quat(rotation),
+            normal: glam::Mat3::from_quat(rotation),
+            scale,
+        }
+    }
+
     const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
-        5 => Float32x4,
         6 => Float32x4,
         7 => Float32x4,
         8 => Float32x4,
-        9 => Float32x3,
+        9 => Float32x4,
         10 => Float32x3,
         11 => Float32x3,
         12 => Float32x3,
+        13 => Float32x3,
     ];
 
     const fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -297,7 +1369,9 @@ impl SimpleInstanceData {
 }
 
 pub trait SimpleInstancesProvider {
-    fn update(&mut self, now_ms: u64);
+    /// Must only depend on `frame_time`, so that rendering the same frame
+    /// twice yields the same instances.
+    fn update(&mut self, frame_time: &FrameTime);
     fn instance_data_slice(&self) -> &[SimpleInstanceData];
     fn instance_count(&self) -> usize;
 }
@@ -306,7 +1380,10 @@ pub mod instances_providers {
     pub mod demo_simple_instances_provider {
         use std::{ops::Range, vec};
 
-        use crate::drawing::systems::model_system::{SimpleInstanceData, SimpleInstancesProvider};
+        use crate::{
+            drawing::systems::model_system::{SimpleInstanceData, SimpleInstancesProvider},
+            timeline::FrameTime,
+        };
 
         struct Instance {
             position: glam::Vec3,
@@ -316,12 +1393,7 @@ pub mod instances_providers {
 
         impl From<&Instance> for super::super::SimpleInstanceData {
             fn from(value: &Instance) -> Self {
-                Self {
-                    model: glam::Mat4::from_translation(value.position)
-                        * glam::Mat4::from_quat(value.rotation.normalize()),
-                    normal: glam::Mat3::from_quat(value.rotation.normalize()),
-                    scale: value.scale,
-                }
+                Self::new(value.position, value.rotation, value.scale)
             }
         }
 
@@ -360,7 +1432,7 @@ pub mod instances_providers {
                     instance_displacement,
                 };
 
-                v.update(0);
+                v.update(&FrameTime::ZERO);
                 v
             }
 
@@ -370,13 +1442,15 @@ pub mod instances_providers {
         }
 
         impl SimpleInstancesProvider for DemoSimpleInstancesProvider {
-            fn update(&mut self, now_ms: u64) {
+            fn update(&mut self, frame_time: &FrameTime) {
                 const TRANSLATE_Y_AMPLITUDE: f32 = 0.5;
                 const SCALE_AMPLITUDE_RANGE: Range<f32> = 0.6..1.2;
 
                 const GLOBAL_SCALE_CONTROL: f32 = 0.8;
                 const SPACE_BETWEEN: f32 = 3.0;
 
+                let now_ms = frame_time.time_ms();
+
                 let progress_1 = (now_ms % 2000) as f32 / 2000.0;
                 let progress_2 = (now_ms % 3000) as f32 / 3000.0;
                 let progress_3 = (now_ms % 5000) as f32 / 5000.0;
@@ -432,3 +1506,36 @@ pub mod instances_providers {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::MorphKind;
+
+    fn morphs(names: &[&str]) -> Vec<MorphData> {
+        names
+            .iter()
+            .map(|name| MorphData {
+                name: name.to_string(),
+                kind: MorphKind::Unsupported,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reloaded_state_is_kept_only_for_the_same_names() {
+        let name: fn(&MorphData) -> &str = |morph| &morph.name;
+        let old = morphs(&["あ", "い", "まばたき"]);
+
+        assert!(same_names(&old, &morphs(&["あ", "い", "まばたき"]), name));
+        assert!(same_names(&morphs(&[]), &morphs(&[]), name));
+        assert!(!same_names(&old, &morphs(&["あ", "い"]), name));
+        assert!(!same_names(
+            &old,
+            &morphs(&["あ", "い", "まばたき", "笑い"]),
+            name
+        ));
+        assert!(!same_names(&old, &morphs(&["い", "あ", "まばたき"]), name));
+        assert!(!same_names(&old, &morphs(&["あ", "い", "ウィンク"]), name));
+    }
+}
@@ -0,0 +1,256 @@
+/// Pixel formats that can be read back from an offscreen render target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffscreenPixelFormat {
+    /// sRGB-encoded 8-bit RGBA, ready to be written to image files.
+    Rgba8,
+    /// Linear 16-bit float RGBA (tone-mapped, but not sRGB-encoded).
+    Rgba16Float,
+}
+
+impl OffscreenPixelFormat {
+    /// The format that viewports rendering into this target should be
+    /// configured with. (Pipelines add the sRGB suffix themselves.)
+    pub fn viewport_color_format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::Rgba8 => wgpu::TextureFormat::Rgba8Unorm,
+            Self::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+
+    fn texture_format(&self) -> wgpu::TextureFormat {
+        self.viewport_color_format().add_srgb_suffix()
+    }
+
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Rgba16Float => 8,
+        }
+    }
+}
+
+/// An engine-owned render target plus the buffer used to copy its contents
+/// back to the CPU.
+pub struct OffscreenEntry {
+    pixel_format: OffscreenPixelFormat,
+    size: glam::UVec2,
+
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+}
+
+impl OffscreenEntry {
+    pub fn new(
+        device: &wgpu::Device,
+        size: glam::UVec2,
+        pixel_format: OffscreenPixelFormat,
+    ) -> Self {
+        let size = glam::uvec2(size.x.max(1), size.y.max(1));
+        let (texture, view, readback_buffer) = Self::make_resources(device, size, pixel_format);
+
+        Self {
+            pixel_format,
+            size,
+            texture,
+            view,
+            readback_buffer,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.texture.destroy();
+        self.readback_buffer.destroy();
+
+        self.size = glam::uvec2(width, height);
+        (self.texture, self.view, self.readback_buffer) =
+            Self::make_resources(device, self.size, self.pixel_format);
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn size(&self) -> glam::UVec2 {
+        self.size
+    }
+
+    pub fn pixel_format(&self) -> OffscreenPixelFormat {
+        self.pixel_format
+    }
+
+    /// Copies the target into the readback buffer, waits for the GPU, and
+    /// returns the pixels with the row padding stripped.
+    ///
+    /// Must be called after the commands rendering into [`Self::view`] have
+    /// been submitted.
+    pub fn read_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Vec<u8>> {
+        let unpadded_bytes_per_row = self.unpadded_bytes_per_row();
+        let padded_bytes_per_row = padded_bytes_per_row(unpadded_bytes_per_row);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("[OffscreenEntry::read_pixels] readback encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(self.size.y),
+                },
+            },
+            self.texture.size(),
+        );
+        let submission_index = queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.readback_buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        device.poll(wgpu::PollType::Wait {
+            submission_index: Some(submission_index),
+            timeout: None,
+        })?;
+        rx.recv()??;
+
+        let pixels = unpad_rows(
+            &slice.get_mapped_range(),
+            unpadded_bytes_per_row,
+            padded_bytes_per_row,
+        );
+        self.readback_buffer.unmap();
+
+        Ok(pixels)
+    }
+
+    fn unpadded_bytes_per_row(&self) -> u32 {
+        self.size.x * self.pixel_format.bytes_per_pixel()
+    }
+
+    fn make_resources(
+        device: &wgpu::Device,
+        size: glam::UVec2,
+        pixel_format: OffscreenPixelFormat,
+    ) -> (wgpu::Texture, wgpu::TextureView, wgpu::Buffer) {
+        let format = pixel_format.texture_format();
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("[OffscreenEntry::make_resources] texture"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("[OffscreenEntry::make_resources] texture view"),
+            ..Default::default()
+        });
+
+        let padded_bytes_per_row = padded_bytes_per_row(size.x * pixel_format.bytes_per_pixel());
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("[OffscreenEntry::make_resources] readback buffer"),
+            size: (padded_bytes_per_row * size.y) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        (texture, view, readback_buffer)
+    }
+}
+
+impl Drop for OffscreenEntry {
+    fn drop(&mut self) {
+        self.texture.destroy();
+        self.readback_buffer.destroy();
+    }
+}
+
+/// Rows in texture-to-buffer copies must be aligned to
+/// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`] (256 bytes).
+fn padded_bytes_per_row(unpadded_bytes_per_row: u32) -> u32 {
+    unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Drops the padding at the end of each row of a texture-to-buffer copy.
+fn unpad_rows(padded: &[u8], unpadded_bytes_per_row: u32, padded_bytes_per_row: u32) -> Vec<u8> {
+    let row_count = padded.len() / padded_bytes_per_row as usize;
+    let mut pixels = Vec::with_capacity(unpadded_bytes_per_row as usize * row_count);
+    for row in padded.chunks_exact(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pads rows the way a texture-to-buffer copy does, filling the padding
+    /// with a byte no pixel has.
+    fn pad_rows(pixels: &[u8], unpadded_bytes_per_row: u32) -> (Vec<u8>, u32) {
+        let padded_bytes_per_row = padded_bytes_per_row(unpadded_bytes_per_row);
+        let mut padded = vec![];
+        for row in pixels.chunks_exact(unpadded_bytes_per_row as usize) {
+            padded.extend_from_slice(row);
+            padded.resize(
+                padded.len() + (padded_bytes_per_row - unpadded_bytes_per_row) as usize,
+                0xee,
+            );
+        }
+        (padded, padded_bytes_per_row)
+    }
+
+    #[test]
+    fn padded_bytes_per_row_rounds_up_to_256() {
+        assert_eq!(padded_bytes_per_row(4), 256);
+        assert_eq!(padded_bytes_per_row(256), 256);
+        assert_eq!(padded_bytes_per_row(257), 512);
+        // 640 Rgba8 pixels are already aligned.
+        assert_eq!(padded_bytes_per_row(640 * 4), 640 * 4);
+    }
+
+    #[test]
+    fn unpad_rows_restores_pixels() {
+        for pixel_format in [
+            OffscreenPixelFormat::Rgba8,
+            OffscreenPixelFormat::Rgba16Float,
+        ] {
+            for (width, height) in [(1, 1), (3, 2), (63, 5), (65, 3), (100, 7), (129, 2)] {
+                let unpadded_bytes_per_row = width * pixel_format.bytes_per_pixel();
+                let pixels = (0..unpadded_bytes_per_row * height)
+                    .map(|i| (i % 251) as u8)
+                    .collect::<Vec<_>>();
+                let (padded, padded_bytes_per_row) = pad_rows(&pixels, unpadded_bytes_per_row);
+                assert_eq!(padded.len(), (padded_bytes_per_row * height) as usize);
+
+                let unpadded = unpad_rows(&padded, unpadded_bytes_per_row, padded_bytes_per_row);
+                assert_eq!(unpadded, pixels, "{:?} {}x{}", pixel_format, width, height);
+            }
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::drawing::{
         canvas_system::CANVAS_COLOR_FORMAT,
     },
     textures,
-    utils::make_render_pipeline,
+    utils::{RenderState, make_render_pipeline},
 };
 
 pub struct SkyboxSystem {
@@ -43,20 +43,8 @@ impl SkyboxSystem {
                 ],
             });
 
-        let environment_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("[SkyboxSystem::new] bind group for environment"),
-            layout: &environment_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&sky_texture.view()),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sky_texture.sampler()),
-                },
-            ],
-        });
+        let environment_bind_group =
+            Self::make_environment_bind_group(device, &environment_bind_group_layout, &sky_texture);
 
         let sky_pipeline = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -74,7 +62,7 @@ impl SkyboxSystem {
                 CANVAS_COLOR_FORMAT,
                 Some(textures::DEPTH_FORMAT),
                 &[],
-                wgpu::PrimitiveTopology::TriangleList,
+                RenderState::default(),
                 &shaders::r_sky(&device),
             )
         };
@@ -86,6 +74,41 @@ impl SkyboxSystem {
         }
     }
 
+    /// Models pick the new environment up as well, as they bind
+    /// [`Self::environment_bind_group`] on every draw.
+    pub fn set_sky_texture(
+        &mut self,
+        device: &wgpu::Device,
+        sky_texture: textures::CubeTexture<textures::TextureFormatRgba32Float>,
+    ) {
+        self.environment_bind_group = Self::make_environment_bind_group(
+            device,
+            &self.environment_bind_group_layout,
+            &sky_texture,
+        );
+    }
+
+    fn make_environment_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sky_texture: &textures::CubeTexture<textures::TextureFormatRgba32Float>,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("[SkyboxSystem::make_environment_bind_group] bind group for environment"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(sky_texture.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sky_texture.sampler()),
+                },
+            ],
+        })
+    }
+
     pub fn environment_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.environment_bind_group_layout
     }
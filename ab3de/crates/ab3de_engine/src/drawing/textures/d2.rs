@@ -72,7 +72,10 @@ impl<T: super::TextureFormat> D2Texture<T> {
             dimension: wgpu::TextureDimension::D2,
             format,
             usage: opts.usage,
-            view_formats: &[format.add_srgb_suffix()],
+            // every view is created in `format`. Listing its sRGB twin would
+            // need `DownlevelFlags::VIEW_FORMATS` for linear textures, which
+            // the GL backend lacks.
+            view_formats: &[],
         });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
@@ -98,18 +101,6 @@ impl<T: super::TextureFormat> D2Texture<T> {
         }
     }
 
-    pub fn from_image_in_memory(
-        name: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        bytes: &[u8],
-        is_color_map: bool,
-    ) -> anyhow::Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        let texture = Self::from_image(name, device, queue, &img, is_color_map);
-        Ok(texture)
-    }
-
     pub fn from_image(
         name: &str,
         device: &wgpu::Device,
@@ -3,26 +3,16 @@ use super::d2::D2TextureRgba8;
 pub struct D2DiffuseTexture(D2TextureRgba8);
 
 impl D2DiffuseTexture {
-    pub fn from_image_in_memory(
+    pub fn from_image(
         name: &str,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        bytes: &[u8],
-    ) -> anyhow::Result<Self> {
-        let inner = D2TextureRgba8::from_image_in_memory(name, device, queue, bytes, true)?;
-        Ok(Self(inner))
+        img: &image::DynamicImage,
+    ) -> Self {
+        let inner = D2TextureRgba8::from_image(name, device, queue, img, true);
+        Self(inner)
     }
 
-    // pub fn from_image(
-    //     name: Option<&str>,
-    //     device: &wgpu::Device,
-    //     queue: &wgpu::Queue,
-    //     img: &image::DynamicImage,
-    // ) -> Self {
-    //     let inner = D2TextureRgba8::from_image(name, device, queue, img, true);
-    //     Self(inner)
-    // }
-
     // pub fn texture(&self) -> &wgpu::Texture {
     //     &self.0.texture()
     // }
@@ -34,8 +24,4 @@ impl D2DiffuseTexture {
     pub fn sampler(&self) -> &wgpu::Sampler {
         &self.0.sampler()
     }
-
-    pub fn size(&self) -> wgpu::Extent3d {
-        self.0.size()
-    }
 }
@@ -3,16 +3,6 @@ use super::d2::D2TextureRgba8;
 pub struct D2NormalTexture(D2TextureRgba8);
 
 impl D2NormalTexture {
-    pub fn from_image_in_memory(
-        name: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        bytes: &[u8],
-    ) -> anyhow::Result<Self> {
-        let inner = D2TextureRgba8::from_image_in_memory(name, device, queue, bytes, false)?;
-        Ok(Self(inner))
-    }
-
     pub fn from_image(
         name: &str,
         device: &wgpu::Device,
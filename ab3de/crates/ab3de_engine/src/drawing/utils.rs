@@ -7,7 +7,7 @@ pub fn make_render_pipeline(
     color_format: wgpu::TextureFormat,
     depth_format: Option<wgpu::TextureFormat>,
     vertex_layouts: &[wgpu::VertexBufferLayout],
-    topology: wgpu::PrimitiveTopology,
+    state: RenderState,
     shader: &shaders::RenderShader,
 ) -> wgpu::RenderPipeline {
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -20,23 +20,23 @@ pub fn make_render_pipeline(
         fragment: shader.fragment_state(shaders::FragmentStatePartial {
             targets: &[Some(wgpu::ColorTargetState {
                 format: color_format.add_srgb_suffix(),
-                blend: Some(wgpu::BlendState::REPLACE),
+                blend: Some(state.blend),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
             compilation_options: wgpu::PipelineCompilationOptions::default(),
         }),
         primitive: wgpu::PrimitiveState {
-            topology,
+            topology: state.topology,
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
+            cull_mode: state.cull_mode,
             unclipped_depth: false,
             polygon_mode: wgpu::PolygonMode::Fill,
             conservative: false,
         },
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format,
-            depth_write_enabled: true,
+            depth_write_enabled: state.depth_write_enabled,
             depth_compare: wgpu::CompareFunction::LessEqual,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState {
@@ -54,3 +54,25 @@ pub fn make_render_pipeline(
         cache: None,
     })
 }
+
+/// What differs between the pipelines made by [`make_render_pipeline`].
+#[derive(Debug, Clone, Copy)]
+pub struct RenderState {
+    pub topology: wgpu::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::Face>,
+    pub blend: wgpu::BlendState,
+    /// Only matters with a depth format.
+    pub depth_write_enabled: bool,
+}
+
+/// Opaque triangle lists with back faces culled, writing depth.
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: wgpu::BlendState::REPLACE,
+            depth_write_enabled: true,
+        }
+    }
+}
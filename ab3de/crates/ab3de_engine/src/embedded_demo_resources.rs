@@ -1,5 +1,7 @@
 use rust_embed::Embed;
 
+use crate::io::{fs_accessors::embed_fs_accessor::EmbedFsAccessor, virtual_fs::VirtualFs};
+
 #[derive(Embed)]
 #[folder = "res/cube"]
 pub struct ResCube;
@@ -11,3 +13,10 @@ pub struct ResAoi;
 #[derive(Embed)]
 #[folder = "res/sky"]
 pub struct ResSky;
+
+/// Mounts each set under `builtin://<set>/`.
+pub fn mount(vfs: &mut VirtualFs) {
+    vfs.mount("builtin://cube/", EmbedFsAccessor::<ResCube>::new("cube"));
+    vfs.mount("builtin://aoi/", EmbedFsAccessor::<ResAoi>::new("aoi"));
+    vfs.mount("builtin://sky/", EmbedFsAccessor::<ResSky>::new("sky"));
+}
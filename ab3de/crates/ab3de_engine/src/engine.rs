@@ -1,25 +1,51 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Weak},
+};
 
 use crate::{
+    animation::{motion::Motion, skeleton::BoneTransform},
+    bounds::Aabb,
     drawing::{
+        models::{Mesh, Model},
         systems::{
             camera_system::{CameraData, CameraEntry, CameraSystem},
             canvas_system::{CANVAS_COLOR_FORMAT, CanvasEntry, CanvasEntryConfiguration},
             depth_system::DepthEntry,
-            light_system::LightSystem,
+            light_system::{LightEntry, LightSystem},
             model_system::{
-                ModelEntryLightSourceIndicator, ModelEntrySimple, ModelSystem,
+                FrameStats, InstanceTransform, ModelEntryLightSourceIndicator, ModelEntrySimple,
+                ModelSystem, SimpleInstancesProvider,
                 instances_providers::demo_simple_instances_provider::DemoSimpleInstancesProvider,
             },
+            offscreen_system::{OffscreenEntry, OffscreenPixelFormat},
             skybox_system::SkyboxSystem,
         },
         textures,
     },
     embedded_demo_resources,
-    io::fs_accessors::{FsAccessor, embed_fs_accessor::EmbedFsAccessor},
+    formats::vmd::parse_vmd,
+    handles::{InstanceHandle, ModelHandle, ModelId},
+    io::{
+        fs_accessors::{
+            FsAccessor, dir_fs_accessor::DirFsAccessor, path_key,
+            recording_fs_accessor::RecordingFsAccessor, zip_fs_accessor::ZipFsAccessor,
+        },
+        virtual_fs::VirtualFs,
+    },
     model_loaders::{
-        ModelLoader, obj_loader::ObjLoader, pmx_loader::PmxLoader, virtual_loader::VirtualLoader,
+        ModelLoader,
+        gltf_loader::GltfLoader,
+        model_data::{BoneData, ModelData, MorphData},
+        obj_loader::ObjLoader,
+        pmx_loader::PmxLoader,
+        virtual_loader::VirtualLoader,
+    },
+    scene::{
+        EnvironmentDescription, InstancesDescription, ModelDescription, ModelLoaderKind,
+        MountDescription, SceneDescription,
     },
+    timeline::Timeline,
 };
 
 pub struct Engine {
@@ -30,82 +56,125 @@ pub struct Engine {
     model_sys: ModelSystem,
     light_sys: LightSystem,
     skybox_sys: SkyboxSystem,
+
+    material_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// Where the URIs of scene descriptions are looked up.
+    vfs: VirtualFs,
+    /// For reloading the environment map, see [`Engine::reload_changed`].
+    environment: EnvironmentDescription,
+    cube_texture_factory: textures::CubeTextureFactory,
+
+    models: HashMap<ModelId, ModelRecord>,
+    next_model_id: u64,
+    /// Lets models loaded from the same file share GPU resources for as long
+    /// as any of them is alive.
+    loaded_models: HashMap<LoadedModelKey, LoadedModel>,
+    /// Keeps the models listed in the scene description alive.
+    scene_models: Vec<ModelHandle>,
+
+    timeline: Timeline,
+    initial_camera_data: CameraData,
+}
+
+/// The loader, the URI, and the bits of the fit size.
+type LoadedModelKey = (ModelLoaderKind, String, Option<u32>);
+
+struct LoadedModel {
+    model: Weak<Model>,
+    /// The [`path_key`]s of the URIs of every file the model was read from.
+    dependencies: HashSet<String>,
+}
+
+struct ModelRecord {
+    liveness: Weak<ModelId>,
+    name: Option<String>,
+    loader: ModelLoaderKind,
+    resource: String,
+}
+
+/// What [`Engine::models`] reports about a loaded model.
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub id: ModelId,
+    pub name: Option<String>,
+    pub loader: ModelLoaderKind,
+    pub resource: String,
+    /// Visible instances, including animated ones that have no
+    /// [`InstanceHandle`].
+    pub drawn_instance_count: usize,
+    /// In model space, see [`Engine::model_bounds`].
+    pub bounds: Aabb,
 }
 
 impl Engine {
+    /// Builds the engine with [`SceneDescription::demo`].
     pub fn try_new(device: wgpu::Device, queue: wgpu::Queue) -> anyhow::Result<Self> {
-        let texture_bind_group_layout = textures::make_regular_d2_texture_bind_group_layout(
-            "[Engine::try_new] texture bind group layout",
+        Self::try_new_from_scene(device, queue, &SceneDescription::demo())
+    }
+
+    pub fn try_new_from_scene(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        scene: &SceneDescription,
+    ) -> anyhow::Result<Self> {
+        let material_bind_group_layout = textures::make_material_bind_group_layout(
+            "[Engine::try_new_from_scene] material bind group layout",
             &device,
         );
 
         let camera_sys = CameraSystem::new(&device);
 
-        let light_sys = LightSystem::new(&device);
-
-        let cube_texture_factory = textures::CubeTextureFactory::new(&device);
+        let light_sys = LightSystem::new(
+            &device,
+            scene
+                .lights
+                .iter()
+                .map(|light| LightEntry {
+                    initial_position: light.position,
+                    color: light.color,
+                    orbit_period_s: light.orbit_period_s,
+                })
+                .collect(),
+        );
 
-        let sky_texture = {
-            const FILE_NAME: &str = "pure-sky.hdr";
+        let vfs = make_virtual_fs(&scene.mounts)?;
 
-            let sky_res_loader = EmbedFsAccessor::<embedded_demo_resources::ResSky>::new("sky");
-            let sky_bytes = sky_res_loader.load_binary(FILE_NAME)?;
+        let cube_texture_factory = textures::CubeTextureFactory::new(&device);
 
-            cube_texture_factory.try_make_cube_texture_from_equirectangular_hdr_image_in_memory(
-                FILE_NAME, &device, &queue, &sky_bytes, 1080,
-            )?
-        };
+        let sky_texture = load_sky_texture(
+            &vfs,
+            &scene.environment,
+            &cube_texture_factory,
+            &device,
+            &queue,
+        )?;
 
         let skybox_sys = SkyboxSystem::new(&device, sky_texture, &camera_sys);
 
         let mut model_sys = ModelSystem::new(
             &device,
             CANVAS_COLOR_FORMAT,
-            &texture_bind_group_layout,
+            &material_bind_group_layout,
             &camera_sys,
             &light_sys,
             &skybox_sys,
         );
 
-        let cube_model = {
-            let obj_res_loader = EmbedFsAccessor::<embedded_demo_resources::ResCube>::new("cube");
-            let obj_model_loader = ObjLoader::new(obj_res_loader);
-            obj_model_loader.load_model("cube.obj", &device, &queue, &texture_bind_group_layout)?
-        };
-        let cube_model = Arc::new(cube_model);
-
-        let obj_model = if true {
-            cube_model.clone()
-        } else {
-            let obj_res_loader = EmbedFsAccessor::<embedded_demo_resources::ResAoi>::new("aoi");
-            let obj_model_loader = PmxLoader::new(obj_res_loader);
-            let obj_model = obj_model_loader.load_model(
-                "A.I.VOICE_琴葉葵_ver1.02.pmx",
-                &device,
-                &queue,
-                &texture_bind_group_layout,
-            )?;
-            Arc::new(obj_model)
-        };
-
-        const NUM_INSTANCES_PER_ROW: usize = 10;
-        let instance_provider = DemoSimpleInstancesProvider::new(NUM_INSTANCES_PER_ROW);
-        model_sys.push_model_entry_simple(ModelEntrySimple::new(
+        let simple_cube_mesh_data_for_light_source_indicator =
+            VirtualLoader::make_cube_mesh_with_minimal_effort_for_light_source_indicators(1.0);
+        let simple_cube_mesh_for_light_source_indicator = Mesh::upload(
+            &simple_cube_mesh_data_for_light_source_indicator.name,
             &device,
-            obj_model,
-            Box::new(instance_provider),
-        ));
-
-        let simple_cube_mesh_for_light_source_indicator =
-            VirtualLoader::make_cube_mesh_with_minimal_effort_for_light_source_indicators(
-                &device, 1.0,
-            );
+            &simple_cube_mesh_data_for_light_source_indicator,
+            None,
+        );
 
         model_sys.set_model_entry_light_source_indicator(ModelEntryLightSourceIndicator::new(
             Arc::new(simple_cube_mesh_for_light_source_indicator),
         ));
 
-        Ok(Self {
+        let mut engine = Self {
             device,
             queue,
 
@@ -113,19 +182,492 @@ impl Engine {
             model_sys,
             light_sys,
             skybox_sys,
-        })
+
+            material_bind_group_layout,
+
+            vfs,
+            environment: scene.environment.clone(),
+            cube_texture_factory,
+
+            models: HashMap::new(),
+            next_model_id: 0,
+            loaded_models: HashMap::new(),
+            scene_models: vec![],
+
+            timeline: Timeline::default(),
+            initial_camera_data: scene.camera_data(),
+        };
+
+        for model_desc in &scene.models {
+            let model = engine.add_model(model_desc)?;
+            engine.scene_models.push(model);
+        }
+
+        Ok(engine)
+    }
+
+    /// Loads a model and creates the instances listed in `model_desc`.
+    ///
+    /// Loading a file that is already loaded reuses its GPU resources.
+    pub fn add_model(&mut self, model_desc: &ModelDescription) -> anyhow::Result<ModelHandle> {
+        let key = (
+            model_desc.loader,
+            model_desc.resource.clone(),
+            model_desc.fit_size.map(f32::to_bits),
+        );
+        let model = match self
+            .loaded_models
+            .get(&key)
+            .and_then(|loaded| loaded.model.upgrade())
+        {
+            Some(model) => model,
+            None => {
+                let (model, dependencies) = self.load_model(&key)?;
+                self.loaded_models.insert(
+                    key,
+                    LoadedModel {
+                        model: Arc::downgrade(&model),
+                        dependencies,
+                    },
+                );
+                model
+            }
+        };
+
+        let mut model_entry = ModelEntrySimple::new(
+            &self.device,
+            model,
+            make_instances_provider(&model_desc.instances),
+        );
+        if let InstancesDescription::Transforms { transforms } = &model_desc.instances {
+            for transform in transforms {
+                model_entry.insert_instance(transform.to_instance_transform());
+            }
+        }
+        if let Some(motion) = &model_desc.motion {
+            model_entry.set_motion(Some(Arc::new(load_motion(&self.vfs, motion)?)));
+        }
+
+        let id = ModelId::new(self.next_model_id);
+        self.next_model_id += 1;
+
+        let (handle, liveness) = ModelHandle::new(id);
+        self.model_sys.insert_model_entry_simple(id, model_entry);
+        self.models.insert(
+            id,
+            ModelRecord {
+                liveness,
+                name: model_desc.name.clone(),
+                loader: model_desc.loader,
+                resource: model_desc.resource.clone(),
+            },
+        );
+
+        Ok(handle)
+    }
+
+    /// Returns the model with the [`path_key`]s of the files it was read from.
+    fn load_model(&self, key: &LoadedModelKey) -> anyhow::Result<(Arc<Model>, HashSet<String>)> {
+        let (loader, resource, fit_size_bits) = key;
+        let (mut model_data, uris) = load_model_data(&self.vfs, *loader, resource)?;
+        if let Some(size) = fit_size_bits.map(f32::from_bits) {
+            model_data.fit(size);
+        }
+        let model = Arc::new(Model::upload(
+            &model_data,
+            &self.device,
+            &self.queue,
+            &self.material_bind_group_layout,
+        )?);
+        let dependencies = uris.iter().filter_map(|uri| path_key(uri)).collect();
+        Ok((model, dependencies))
+    }
+
+    /// Reloads the models and the environment map that were read from files
+    /// which changed in watched mounts, see [`MountDescription::Directory`].
+    /// Models are swapped in place, so their handles and instances stay
+    /// valid. Whatever fails to reload is kept as it was, and the errors are
+    /// returned. Call before [`Engine::update`], so that motions are applied
+    /// to reloaded models before they are drawn.
+    pub fn reload_changed(&mut self) -> Vec<anyhow::Error> {
+        let changed = self
+            .vfs
+            .take_changed_filenames()
+            .iter()
+            .filter_map(|uri| path_key(uri))
+            .collect::<HashSet<_>>();
+        if changed.is_empty() {
+            return vec![];
+        }
+
+        let mut errors = vec![];
+        if path_key(&self.environment.map).is_some_and(|key| changed.contains(&key)) {
+            log::info!(
+                "[Engine::reload_changed] reloading {}",
+                self.environment.map
+            );
+            match load_sky_texture(
+                &self.vfs,
+                &self.environment,
+                &self.cube_texture_factory,
+                &self.device,
+                &self.queue,
+            ) {
+                Ok(sky_texture) => self.skybox_sys.set_sky_texture(&self.device, sky_texture),
+                Err(e) => {
+                    errors.push(e.context(format!("Unable to reload {}", self.environment.map)))
+                }
+            }
+        }
+
+        let stale_keys = self
+            .loaded_models
+            .iter()
+            .filter(|(_, loaded)| !loaded.dependencies.is_disjoint(&changed))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in stale_keys {
+            if let Err(e) = self.reload_model(&key) {
+                errors.push(e.context(format!("Unable to reload {}", key.1)));
+            }
+        }
+        errors
+    }
+
+    fn reload_model(&mut self, key: &LoadedModelKey) -> anyhow::Result<()> {
+        let Some(old_model) = self.loaded_models[key].model.upgrade() else {
+            return Ok(());
+        };
+
+        log::info!("[Engine::reload_model] reloading {}", key.1);
+        let (model, dependencies) = self.load_model(key)?;
+        for id in self.models.keys() {
+            if let Some(entry) = self.model_sys.model_entry_simple_mut(*id)
+                && Arc::ptr_eq(entry.model(), &old_model)
+            {
+                entry.set_model(model.clone());
+            }
+        }
+        self.loaded_models.insert(
+            key.clone(),
+            LoadedModel {
+                model: Arc::downgrade(&model),
+                dependencies,
+            },
+        );
+        Ok(())
+    }
+
+    /// Releases the model and all its instances now, even if other clones of
+    /// the handle are still around.
+    pub fn remove_model(&mut self, model: ModelHandle) {
+        self.scene_models.retain(|m| m != &model);
+        self.release_model(model.id());
+    }
+
+    /// Handles for the models listed in the scene description. The engine
+    /// keeps these alive until they are removed with
+    /// [`Engine::remove_model`].
+    pub fn scene_models(&self) -> &[ModelHandle] {
+        &self.scene_models
+    }
+
+    /// Returns a new handle to a model that is still loaded.
+    pub fn model_handle(&self, id: ModelId) -> Option<ModelHandle> {
+        self.models
+            .get(&id)
+            .and_then(|record| ModelHandle::from_liveness(&record.liveness))
+    }
+
+    /// All loaded models, in the order they were added.
+    pub fn models(&self) -> Vec<ModelInfo> {
+        let mut infos = self
+            .models
+            .iter()
+            .filter_map(|(id, record)| {
+                let entry = self.model_sys.model_entry_simple(*id)?;
+                Some(ModelInfo {
+                    id: *id,
+                    name: record.name.clone(),
+                    loader: record.loader,
+                    resource: record.resource.clone(),
+                    drawn_instance_count: entry.drawn_instance_count(),
+                    bounds: *entry.bounds(),
+                })
+            })
+            .collect::<Vec<_>>();
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+
+    /// Handles for the model's individually managed instances.
+    pub fn instances(&self, model: &ModelHandle) -> anyhow::Result<Vec<InstanceHandle>> {
+        let entry = self.model_entry_simple(model.id())?;
+        Ok(entry
+            .instance_ids()
+            .map(|instance_id| InstanceHandle::new(model.id(), instance_id))
+            .collect())
+    }
+
+    pub fn insert_instance(
+        &mut self,
+        model: &ModelHandle,
+        transform: InstanceTransform,
+    ) -> anyhow::Result<InstanceHandle> {
+        let entry = self.model_entry_simple_mut(model.id())?;
+        let instance_id = entry.insert_instance(transform);
+        Ok(InstanceHandle::new(model.id(), instance_id))
+    }
+
+    pub fn remove_instance(&mut self, instance: InstanceHandle) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(instance.model_id())?;
+        if !entry.remove_instance(instance.instance_id()) {
+            anyhow::bail!("Instance not found: {:?}", instance);
+        }
+        Ok(())
+    }
+
+    pub fn instance_transform(
+        &self,
+        instance: InstanceHandle,
+    ) -> anyhow::Result<InstanceTransform> {
+        self.model_entry_simple(instance.model_id())?
+            .instance_transform(instance.instance_id())
+            .ok_or_else(|| anyhow::anyhow!("Instance not found: {:?}", instance))
+    }
+
+    pub fn set_instance_transform(
+        &mut self,
+        instance: InstanceHandle,
+        transform: InstanceTransform,
+    ) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(instance.model_id())?;
+        if !entry.set_instance_transform(instance.instance_id(), transform) {
+            anyhow::bail!("Instance not found: {:?}", instance);
+        }
+        Ok(())
+    }
+
+    /// Of all the model's meshes, in model space, in the rest pose and
+    /// without morphs. Animated models may leave it.
+    pub fn model_bounds(&self, model: &ModelHandle) -> anyhow::Result<Aabb> {
+        Ok(*self.model_entry_simple(model.id())?.bounds())
+    }
+
+    /// [`Engine::model_bounds`], in world space.
+    pub fn instance_bounds(&self, instance: InstanceHandle) -> anyhow::Result<Aabb> {
+        self.model_entry_simple(instance.model_id())?
+            .instance_bounds(instance.instance_id())
+            .ok_or_else(|| anyhow::anyhow!("Instance not found: {:?}", instance))
+    }
+
+    /// Hidden instances keep their transform and are not drawn.
+    pub fn set_instance_visibility(
+        &mut self,
+        instance: InstanceHandle,
+        is_visible: bool,
+    ) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(instance.model_id())?;
+        if !entry.set_instance_visibility(instance.instance_id(), is_visible) {
+            anyhow::bail!("Instance not found: {:?}", instance);
+        }
+        Ok(())
+    }
+
+    /// The model's skeleton, empty for models without one. Bone indices into
+    /// this slice are what the pose functions take.
+    pub fn bones(&self, model: &ModelHandle) -> anyhow::Result<&[BoneData]> {
+        let entry = self.model_entry_simple(model.id())?;
+        Ok(entry
+            .skeleton()
+            .map_or(&[][..], |skeleton| skeleton.bones()))
+    }
+
+    /// Relative to the bone's rest pose.
+    pub fn bone_transform(
+        &self,
+        model: &ModelHandle,
+        bone_index: usize,
+    ) -> anyhow::Result<BoneTransform> {
+        self.model_entry_simple(model.id())?
+            .bone_transform(bone_index)
+            .ok_or_else(|| anyhow::anyhow!("Bone not found: {:?} #{}", model, bone_index))
+    }
+
+    /// The pose is shared by all instances of the model.
+    pub fn set_bone_transform(
+        &mut self,
+        model: &ModelHandle,
+        bone_index: usize,
+        transform: BoneTransform,
+    ) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(model.id())?;
+        if !entry.set_bone_transform(bone_index, transform) {
+            anyhow::bail!("Bone not found: {:?} #{}", model, bone_index);
+        }
+        Ok(())
+    }
+
+    pub fn reset_pose(&mut self, model: &ModelHandle) -> anyhow::Result<()> {
+        self.model_entry_simple_mut(model.id())?.reset_pose();
+        Ok(())
+    }
+
+    /// Whether the IK bone solves its chain. Always `true` for other bones.
+    pub fn is_ik_enabled(&self, model: &ModelHandle, bone_index: usize) -> anyhow::Result<bool> {
+        self.model_entry_simple(model.id())?
+            .is_ik_enabled(bone_index)
+            .ok_or_else(|| anyhow::anyhow!("Bone not found: {:?} #{}", model, bone_index))
+    }
+
+    /// Motions may switch IK bones on and off too.
+    pub fn set_ik_enabled(
+        &mut self,
+        model: &ModelHandle,
+        bone_index: usize,
+        is_enabled: bool,
+    ) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(model.id())?;
+        if !entry.set_ik_enabled(bone_index, is_enabled) {
+            anyhow::bail!("Bone not found: {:?} #{}", model, bone_index);
+        }
+        Ok(())
+    }
+
+    /// Morph indices into this slice are what the morph weight functions
+    /// take.
+    pub fn morphs(&self, model: &ModelHandle) -> anyhow::Result<&[MorphData]> {
+        Ok(self.model_entry_simple(model.id())?.morphs())
+    }
+
+    pub fn morph_weight(&self, model: &ModelHandle, morph_index: usize) -> anyhow::Result<f32> {
+        self.model_entry_simple(model.id())?
+            .morph_weight(morph_index)
+            .ok_or_else(|| anyhow::anyhow!("Morph not found: {:?} #{}", model, morph_index))
+    }
+
+    pub fn set_morph_weight(
+        &mut self,
+        model: &ModelHandle,
+        morph_index: usize,
+        weight: f32,
+    ) -> anyhow::Result<()> {
+        let entry = self.model_entry_simple_mut(model.id())?;
+        if !entry.set_morph_weight(morph_index, weight) {
+            anyhow::bail!("Morph not found: {:?} #{}", model, morph_index);
+        }
+        Ok(())
+    }
+
+    /// Plays `motion` on the model, following the timeline. While it plays,
+    /// it overrides the bones and morphs it has tracks for.
+    pub fn set_motion(
+        &mut self,
+        model: &ModelHandle,
+        motion: Option<Arc<Motion>>,
+    ) -> anyhow::Result<()> {
+        self.model_entry_simple_mut(model.id())?.set_motion(motion);
+        Ok(())
+    }
+
+    fn model_entry_simple(&self, id: ModelId) -> anyhow::Result<&ModelEntrySimple> {
+        self.model_sys
+            .model_entry_simple(id)
+            .ok_or_else(|| anyhow::anyhow!("Model not found: {:?}", id))
+    }
+
+    fn model_entry_simple_mut(&mut self, id: ModelId) -> anyhow::Result<&mut ModelEntrySimple> {
+        self.model_sys
+            .model_entry_simple_mut(id)
+            .ok_or_else(|| anyhow::anyhow!("Model not found: {:?}", id))
+    }
+
+    fn release_model(&mut self, id: ModelId) {
+        self.models.remove(&id);
+        self.model_sys.remove_model_entry_simple(id);
+        self.loaded_models
+            .retain(|_, loaded| loaded.model.strong_count() > 0);
+    }
+
+    fn release_unused_models(&mut self) {
+        let unused_ids = self
+            .models
+            .iter()
+            .filter(|(_, record)| record.liveness.strong_count() == 0)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in unused_ids {
+            log::debug!("[Engine::release_unused_models] releasing {:?}", id);
+            self.release_model(id);
+        }
+    }
+
+    /// The camera the scene starts with. New viewports use it.
+    pub fn initial_camera_data(&self) -> &CameraData {
+        &self.initial_camera_data
     }
 
     pub fn make_viewport(&self, config: ViewportConfiguration) -> Viewport {
-        Viewport::new(&self.device, &self.camera_sys, config)
+        Viewport::new(
+            &self.device,
+            &self.camera_sys,
+            self.initial_camera_data.clone(),
+            config,
+        )
     }
 
-    pub fn update(&mut self, now_ms: u64, dt_s: f32) {
-        self.light_sys.update(&self.queue, dt_s);
-        self.model_sys.update(&self.device, &self.queue, now_ms);
+    pub fn make_offscreen_viewport(
+        &self,
+        config: OffscreenViewportConfiguration,
+    ) -> OffscreenViewport {
+        OffscreenViewport::new(
+            &self.device,
+            &self.camera_sys,
+            self.initial_camera_data.clone(),
+            config,
+        )
+    }
+
+    pub fn vfs(&self) -> &VirtualFs {
+        &self.vfs
+    }
+
+    /// For mounting more files at runtime, e.g. an archive the user opened.
+    /// Models already loaded from a URI are reused while they are alive,
+    /// even if a new mount shadows it.
+    pub fn vfs_mut(&mut self) -> &mut VirtualFs {
+        &mut self.vfs
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn timeline_mut(&mut self) -> &mut Timeline {
+        &mut self.timeline
+    }
+
+    /// Brings every animated system to the timeline's current time. Hosts
+    /// move the timeline first, either with [`Timeline::advance`] for
+    /// real-time playback or [`Timeline::seek_frame`] for frame-exact
+    /// rendering.
+    ///
+    /// Also releases models whose handles have all been dropped.
+    pub fn update(&mut self) {
+        self.release_unused_models();
+
+        let frame_time = self.timeline.sample();
+
+        self.light_sys.update(&self.queue, &frame_time);
+        self.model_sys.update(&frame_time);
     }
 
     pub fn render(&mut self, viewport: &Viewport, output_view: &wgpu::TextureView) {
+        self.model_sys.prepare(&self.device, &self.queue);
+        self.model_sys
+            .cull(&self.device, &self.queue, &viewport.camera_entry);
+
         let encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -144,6 +686,131 @@ impl Engine {
             },
         );
     }
+
+    /// What the last [`Engine::render`] drew.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.model_sys.frame_stats()
+    }
+
+    /// Renders into the viewport's engine-owned texture and reads the result
+    /// back. Does not need a [`wgpu::Surface`].
+    pub fn render_offscreen(
+        &mut self,
+        viewport: &OffscreenViewport,
+    ) -> anyhow::Result<OffscreenPixels> {
+        self.render(&viewport.viewport, viewport.offscreen_entry.view());
+
+        let data = viewport
+            .offscreen_entry
+            .read_pixels(&self.device, &self.queue)?;
+
+        Ok(OffscreenPixels {
+            size: viewport.offscreen_entry.size(),
+            pixel_format: viewport.offscreen_entry.pixel_format(),
+            data,
+        })
+    }
+}
+
+/// The built-in resource sets, shadowed by `mounts`.
+fn make_virtual_fs(mounts: &[MountDescription]) -> anyhow::Result<VirtualFs> {
+    let mut vfs = VirtualFs::default();
+    embedded_demo_resources::mount(&mut vfs);
+    for mount in mounts {
+        match mount {
+            MountDescription::Directory {
+                prefix,
+                root,
+                watch,
+            } => {
+                let mut accessor = DirFsAccessor::new(root);
+                if *watch && let Err(e) = accessor.watch() {
+                    log::warn!("[make_virtual_fs] {}", e);
+                }
+                vfs.mount(prefix.as_str(), accessor);
+            }
+            MountDescription::Zip {
+                prefix,
+                opened: Some(opened),
+                ..
+            } => {
+                vfs.mount(prefix.as_str(), opened.0.clone());
+            }
+            MountDescription::Zip {
+                prefix,
+                archive,
+                opened: None,
+            } => {
+                vfs.mount(prefix.as_str(), ZipFsAccessor::open(archive)?);
+            }
+        }
+    }
+    Ok(vfs)
+}
+
+fn load_sky_texture(
+    vfs: &VirtualFs,
+    environment: &EnvironmentDescription,
+    cube_texture_factory: &textures::CubeTextureFactory,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<textures::CubeTexture<textures::TextureFormatRgba32Float>> {
+    let sky_bytes = vfs.load_binary(&environment.map)?;
+    cube_texture_factory.try_make_cube_texture_from_equirectangular_hdr_image_in_memory(
+        &environment.map,
+        device,
+        queue,
+        &sky_bytes,
+        environment.cube_size,
+    )
+}
+
+/// Reads a model without a GPU, e.g. on a worker thread. The loader reads
+/// from the mount `uri` is in, so that textures are found next to it. Also
+/// returns the URIs of every file it read.
+pub fn load_model_data(
+    vfs: &VirtualFs,
+    loader: ModelLoaderKind,
+    uri: &str,
+) -> anyhow::Result<(ModelData, Vec<String>)> {
+    let (res_loader, filename) = vfs.resolve(uri)?;
+    let prefix = &uri[..uri.len() - filename.len()];
+    let res_loader = RecordingFsAccessor::new(res_loader);
+
+    let model_data = match loader {
+        ModelLoaderKind::Obj => ObjLoader::new(&res_loader).load_model_data(filename),
+        ModelLoaderKind::Pmx => PmxLoader::new(&res_loader).load_model_data(filename),
+        ModelLoaderKind::Gltf => GltfLoader::new(&res_loader).load_model_data(filename),
+    }?;
+    let uris = res_loader
+        .into_filenames()
+        .into_iter()
+        .map(|filename| format!("{}{}", prefix, filename))
+        .collect();
+    Ok((model_data, uris))
+}
+
+/// Reads a VMD motion.
+pub fn load_motion(vfs: &VirtualFs, uri: &str) -> anyhow::Result<Motion> {
+    let (res_loader, filename) = vfs.resolve(uri)?;
+    let vmd_data = res_loader.load_binary(filename)?;
+    let vmd = parse_vmd(&vmd_data)
+        .map_err(|e| anyhow::anyhow!("{} (#{}/{})", e, res_loader.name(), filename))?;
+
+    Ok(Motion::from_vmd(&vmd))
+}
+
+/// Fixed transforms become managed instances instead, see
+/// [`Engine::add_model`].
+fn make_instances_provider(
+    desc: &InstancesDescription,
+) -> Option<Box<dyn SimpleInstancesProvider>> {
+    match desc {
+        InstancesDescription::DemoGrid { per_row } => {
+            Some(Box::new(DemoSimpleInstancesProvider::new(*per_row)))
+        }
+        InstancesDescription::Transforms { .. } => None,
+    }
 }
 
 pub struct Viewport {
@@ -161,6 +828,7 @@ impl Viewport {
     fn new(
         device: &wgpu::Device,
         camera_sys: &CameraSystem,
+        camera_data: CameraData,
         config: ViewportConfiguration,
     ) -> Self {
         let canvas_entry = CanvasEntry::new(
@@ -171,7 +839,7 @@ impl Viewport {
             },
         );
         let depth_entry = DepthEntry::new(&device, canvas_entry.config());
-        let camera_entry = camera_sys.make_entry(device, config.size);
+        let camera_entry = camera_sys.make_entry(device, config.size, camera_data);
 
         Self {
             canvas_entry,
@@ -242,3 +910,54 @@ impl Viewport {
         );
     }
 }
+
+pub struct OffscreenViewport {
+    viewport: Viewport,
+    offscreen_entry: OffscreenEntry,
+}
+
+pub struct OffscreenViewportConfiguration {
+    pub size: glam::UVec2,
+    pub pixel_format: OffscreenPixelFormat,
+}
+
+impl OffscreenViewport {
+    fn new(
+        device: &wgpu::Device,
+        camera_sys: &CameraSystem,
+        camera_data: CameraData,
+        config: OffscreenViewportConfiguration,
+    ) -> Self {
+        let viewport = Viewport::new(
+            device,
+            camera_sys,
+            camera_data,
+            ViewportConfiguration {
+                size: config.size,
+                color_format: config.pixel_format.viewport_color_format(),
+            },
+        );
+        let offscreen_entry = OffscreenEntry::new(device, config.size, config.pixel_format);
+
+        Self {
+            viewport,
+            offscreen_entry,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.viewport.resize(device, queue, width, height);
+        self.offscreen_entry.resize(device, width, height);
+    }
+
+    pub fn update_camera(&mut self, queue: &wgpu::Queue, f: impl FnOnce(&mut CameraData)) {
+        self.viewport.update_camera(queue, f);
+    }
+}
+
+/// Tightly packed pixels (no row padding), top row first.
+pub struct OffscreenPixels {
+    pub size: glam::UVec2,
+    pub pixel_format: OffscreenPixelFormat,
+    pub data: Vec<u8>,
+}
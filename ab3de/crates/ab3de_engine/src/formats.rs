@@ -0,0 +1,2 @@
+pub mod pmx;
+pub mod vmd;
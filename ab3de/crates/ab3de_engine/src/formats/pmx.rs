@@ -0,0 +1,474 @@
+//! PMX 2.0 / 2.1 models, as used by MikuMikuDance.
+//!
+//! [`parse_pmx`] only deals with bytes, so it needs neither a GPU nor a file
+//! system. Soft bodies (PMX 2.1) are not read.
+
+mod parser;
+
+pub use parser::parse_pmx;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxModel {
+    pub header: PmxHeader,
+    pub vertices: Vec<PmxVertex>,
+    /// Triangles, as indices into [`Self::vertices`].
+    pub faces: Vec<[u32; 3]>,
+    /// Paths relative to the model file. Usually with `\` as the separator.
+    pub textures: Vec<String>,
+    /// Each material covers the next [`PmxMaterial::index_count`] indices of
+    /// [`Self::faces`], in order.
+    pub materials: Vec<PmxMaterial>,
+    pub bones: Vec<PmxBone>,
+    pub morphs: Vec<PmxMorph>,
+    pub display_frames: Vec<PmxDisplayFrame>,
+    pub rigid_bodies: Vec<PmxRigidBody>,
+    pub joints: Vec<PmxJoint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxHeader {
+    /// `2.0` or `2.1`.
+    pub version: f32,
+    pub text_encoding: PmxTextEncoding,
+    /// How many of [`PmxVertex::additional_uvs`] are used, `0..=4`.
+    pub additional_uv_count: u8,
+    pub vertex_index_size: u8,
+    pub texture_index_size: u8,
+    pub material_index_size: u8,
+    pub bone_index_size: u8,
+    pub morph_index_size: u8,
+    pub rigid_body_index_size: u8,
+    pub name: PmxText,
+    pub comment: PmxText,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxTextEncoding {
+    Utf16Le,
+    Utf8,
+}
+
+/// Text in the model's language (usually Japanese) and in English.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PmxText {
+    pub local: String,
+    pub universal: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxVertex {
+    pub position: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub uv: glam::Vec2,
+    /// Only the first [`PmxHeader::additional_uv_count`] are meaningful.
+    pub additional_uvs: [glam::Vec4; 4],
+    pub deform: PmxVertexDeform,
+    pub edge_scale: f32,
+}
+
+/// Bone indices are `None` where the file says `-1`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PmxVertexDeform {
+    Bdef1 {
+        bone: Option<u32>,
+    },
+    /// `weight` is the weight of `bones[0]`; `bones[1]` gets the rest.
+    Bdef2 {
+        bones: [Option<u32>; 2],
+        weight: f32,
+    },
+    Bdef4 {
+        bones: [Option<u32>; 4],
+        weights: [f32; 4],
+    },
+    /// Spherical deform. Like [`Self::Bdef2`], plus the rotation center `c`
+    /// and the two reference points `r0` and `r1`.
+    Sdef {
+        bones: [Option<u32>; 2],
+        weight: f32,
+        c: glam::Vec3,
+        r0: glam::Vec3,
+        r1: glam::Vec3,
+    },
+    /// Dual quaternion deform (PMX 2.1).
+    Qdef {
+        bones: [Option<u32>; 4],
+        weights: [f32; 4],
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxMaterial {
+    pub name: PmxText,
+    pub diffuse: glam::Vec4,
+    pub specular: glam::Vec3,
+    pub specular_strength: f32,
+    pub ambient: glam::Vec3,
+    pub flags: PmxMaterialFlags,
+    pub edge_color: glam::Vec4,
+    pub edge_size: f32,
+    pub texture: Option<u32>,
+    pub sphere_texture: Option<u32>,
+    pub sphere_mode: PmxSphereMode,
+    pub toon: PmxToon,
+    pub memo: String,
+    /// A multiple of 3.
+    pub index_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PmxMaterialFlags(pub u8);
+
+impl PmxMaterialFlags {
+    pub const NO_CULL: Self = Self(0x01);
+    pub const GROUND_SHADOW: Self = Self(0x02);
+    pub const DRAW_SHADOW: Self = Self(0x04);
+    pub const RECEIVE_SHADOW: Self = Self(0x08);
+    pub const HAS_EDGE: Self = Self(0x10);
+    /// PMX 2.1.
+    pub const VERTEX_COLOR: Self = Self(0x20);
+    /// PMX 2.1.
+    pub const POINT_DRAWING: Self = Self(0x40);
+    /// PMX 2.1.
+    pub const LINE_DRAWING: Self = Self(0x80);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxSphereMode {
+    Disabled,
+    Multiply,
+    Add,
+    /// Sampled with the first additional UV instead of being a sphere map.
+    SubTexture,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxToon {
+    /// An index into [`PmxModel::textures`].
+    Texture(Option<u32>),
+    /// One of the toon textures shipped with MMD, `0` being `toon01.bmp`.
+    Shared(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxBone {
+    pub name: PmxText,
+    pub position: glam::Vec3,
+    pub parent: Option<u32>,
+    /// Deform layer. Bones are transformed in ascending layer order.
+    pub layer: i32,
+    pub flags: PmxBoneFlags,
+    pub tail: PmxBoneTail,
+    /// Also called "append" or "grant" transform.
+    pub inherit: Option<PmxBoneInherit>,
+    pub fixed_axis: Option<glam::Vec3>,
+    pub local_axes: Option<PmxBoneLocalAxes>,
+    pub external_parent_key: Option<i32>,
+    pub ik: Option<PmxIk>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PmxBoneFlags(pub u16);
+
+impl PmxBoneFlags {
+    pub const TAIL_IS_BONE: Self = Self(0x0001);
+    pub const ROTATABLE: Self = Self(0x0002);
+    pub const TRANSLATABLE: Self = Self(0x0004);
+    pub const VISIBLE: Self = Self(0x0008);
+    pub const ENABLED: Self = Self(0x0010);
+    pub const IK: Self = Self(0x0020);
+    pub const INHERIT_LOCAL: Self = Self(0x0080);
+    pub const INHERIT_ROTATION: Self = Self(0x0100);
+    pub const INHERIT_TRANSLATION: Self = Self(0x0200);
+    pub const FIXED_AXIS: Self = Self(0x0400);
+    pub const LOCAL_AXES: Self = Self(0x0800);
+    pub const PHYSICS_AFTER_DEFORM: Self = Self(0x1000);
+    pub const EXTERNAL_PARENT_DEFORM: Self = Self(0x2000);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PmxBoneTail {
+    Bone(Option<u32>),
+    /// Relative to the bone's position.
+    Offset(glam::Vec3),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxBoneInherit {
+    pub parent: Option<u32>,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxBoneLocalAxes {
+    pub x: glam::Vec3,
+    pub z: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxIk {
+    pub target: Option<u32>,
+    pub loop_count: i32,
+    /// Maximum rotation per iteration, in radians.
+    pub limit_angle: f32,
+    /// From the bone next to the target towards the root.
+    pub links: Vec<PmxIkLink>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxIkLink {
+    pub bone: Option<u32>,
+    /// Euler angle limits in radians.
+    pub limits: Option<PmxIkLimits>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxIkLimits {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxMorph {
+    pub name: PmxText,
+    pub panel: PmxMorphPanel,
+    pub offsets: PmxMorphOffsets,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxMorphPanel {
+    System,
+    Eyebrow,
+    Eye,
+    Mouth,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PmxMorphOffsets {
+    Group(Vec<PmxGroupMorphOffset>),
+    Vertex(Vec<PmxVertexMorphOffset>),
+    Bone(Vec<PmxBoneMorphOffset>),
+    /// `channel` 0 is [`PmxVertex::uv`], 1 to 4 are
+    /// [`PmxVertex::additional_uvs`].
+    Uv {
+        channel: u8,
+        offsets: Vec<PmxUvMorphOffset>,
+    },
+    Material(Vec<PmxMaterialMorphOffset>),
+    /// PMX 2.1.
+    Flip(Vec<PmxGroupMorphOffset>),
+    /// PMX 2.1.
+    Impulse(Vec<PmxImpulseMorphOffset>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxGroupMorphOffset {
+    pub morph: Option<u32>,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxVertexMorphOffset {
+    pub vertex: u32,
+    pub translation: glam::Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxBoneMorphOffset {
+    pub bone: Option<u32>,
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxUvMorphOffset {
+    pub vertex: u32,
+    pub offset: glam::Vec4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxMaterialMorphOffset {
+    /// `None` applies to every material.
+    pub material: Option<u32>,
+    pub operation: PmxMaterialMorphOperation,
+    pub diffuse: glam::Vec4,
+    pub specular: glam::Vec3,
+    pub specular_strength: f32,
+    pub ambient: glam::Vec3,
+    pub edge_color: glam::Vec4,
+    pub edge_size: f32,
+    pub texture_tint: glam::Vec4,
+    pub sphere_tint: glam::Vec4,
+    pub toon_tint: glam::Vec4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxMaterialMorphOperation {
+    Multiply,
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxImpulseMorphOffset {
+    pub rigid_body: Option<u32>,
+    pub is_local: bool,
+    pub velocity: glam::Vec3,
+    pub torque: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxDisplayFrame {
+    pub name: PmxText,
+    /// The root and expression frames, which MMD treats specially.
+    pub is_special: bool,
+    pub items: Vec<PmxDisplayFrameItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxDisplayFrameItem {
+    Bone(Option<u32>),
+    Morph(Option<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxRigidBody {
+    pub name: PmxText,
+    pub bone: Option<u32>,
+    pub group: u8,
+    /// Bit `n` set means the body collides with group `n`.
+    pub collision_mask: u16,
+    pub shape: PmxRigidBodyShape,
+    /// Radius for spheres; half extents for boxes; radius and height for
+    /// capsules.
+    pub size: glam::Vec3,
+    pub position: glam::Vec3,
+    /// Euler angles in radians.
+    pub rotation: glam::Vec3,
+    pub mass: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub mode: PmxRigidBodyMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxRigidBodyShape {
+    Sphere,
+    Box,
+    Capsule,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxRigidBodyMode {
+    /// Kinematic, moved by its bone.
+    FollowBone,
+    /// Simulated, moves its bone.
+    Physics,
+    /// Simulated, but only rotates its bone; the bone keeps its position.
+    PhysicsWithBonePosition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxJoint {
+    pub name: PmxText,
+    pub kind: PmxJointKind,
+    pub rigid_bodies: [Option<u32>; 2],
+    pub position: glam::Vec3,
+    /// Euler angles in radians.
+    pub rotation: glam::Vec3,
+    pub linear_min: glam::Vec3,
+    pub linear_max: glam::Vec3,
+    pub angular_min: glam::Vec3,
+    pub angular_max: glam::Vec3,
+    pub linear_spring: glam::Vec3,
+    pub angular_spring: glam::Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxJointKind {
+    Spring6Dof,
+    /// PMX 2.1.
+    SixDof,
+    /// PMX 2.1.
+    PointToPoint,
+    /// PMX 2.1.
+    ConeTwist,
+    /// PMX 2.1.
+    Slider,
+    /// PMX 2.1.
+    Hinge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxError {
+    pub section: PmxSection,
+    /// From the start of the file.
+    pub offset: usize,
+    pub kind: PmxErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmxSection {
+    Header,
+    Vertices,
+    Faces,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    DisplayFrames,
+    RigidBodies,
+    Joints,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PmxErrorKind {
+    UnexpectedEnd,
+    InvalidMagic,
+    UnsupportedVersion(f32),
+    InvalidIndexSize(u8),
+    InvalidText,
+    InvalidCount(i64),
+    /// An enum field with an unknown value.
+    InvalidValue {
+        field: &'static str,
+        value: i64,
+    },
+    Other(nom::error::ErrorKind),
+}
+
+impl std::fmt::Display for PmxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid PMX in {:?} at byte {} ({:#x}): ",
+            self.section, self.offset, self.offset
+        )?;
+        match &self.kind {
+            PmxErrorKind::UnexpectedEnd => write!(f, "unexpected end of data"),
+            PmxErrorKind::InvalidMagic => write!(f, "not a PMX file"),
+            PmxErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            PmxErrorKind::InvalidIndexSize(size) => write!(f, "invalid index size {}", size),
+            PmxErrorKind::InvalidText => write!(f, "invalid text"),
+            PmxErrorKind::InvalidCount(count) => write!(f, "invalid count {}", count),
+            PmxErrorKind::InvalidValue { field, value } => {
+                write!(f, "invalid {} {}", field, value)
+            }
+            PmxErrorKind::Other(kind) => write!(f, "{}", kind.description()),
+        }
+    }
+}
+
+impl std::error::Error for PmxError {}
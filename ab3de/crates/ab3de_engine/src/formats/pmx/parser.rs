@@ -0,0 +1,1194 @@
+use nom::{
+    IResult, Parser,
+    bytes::complete::take,
+    error::ErrorKind,
+    number::complete::{le_f32, le_i8, le_i16, le_i32, le_u8, le_u16},
+};
+
+use super::*;
+
+type Input<'a> = &'a [u8];
+type PResult<'a, T> = IResult<Input<'a>, T, ParseError<'a>>;
+
+#[derive(Debug)]
+struct ParseError<'a> {
+    input: Input<'a>,
+    kind: PmxErrorKind,
+}
+
+impl<'a> nom::error::ParseError<Input<'a>> for ParseError<'a> {
+    fn from_error_kind(input: Input<'a>, kind: ErrorKind) -> Self {
+        let kind = match kind {
+            ErrorKind::Eof => PmxErrorKind::UnexpectedEnd,
+            kind => PmxErrorKind::Other(kind),
+        };
+        Self { input, kind }
+    }
+
+    fn append(_input: Input<'a>, _kind: ErrorKind, other: Self) -> Self {
+        // the innermost error is the most precise one.
+        other
+    }
+}
+
+fn fail<T>(input: Input, kind: PmxErrorKind) -> PResult<T> {
+    Err(nom::Err::Failure(ParseError { input, kind }))
+}
+
+/// Parses a whole PMX file.
+pub fn parse_pmx(bytes: &[u8]) -> Result<PmxModel, PmxError> {
+    let in_section = |section, error: nom::Err<ParseError>| {
+        let (offset, kind) = match error {
+            nom::Err::Error(e) | nom::Err::Failure(e) => (bytes.len() - e.input.len(), e.kind),
+            nom::Err::Incomplete(_) => (bytes.len(), PmxErrorKind::UnexpectedEnd),
+        };
+        PmxError {
+            section,
+            offset,
+            kind,
+        }
+    };
+
+    let (i, header) = header(bytes).map_err(|e| in_section(PmxSection::Header, e))?;
+    let g = &header;
+
+    let (i, vertices) =
+        list(i, |i| vertex(g, i)).map_err(|e| in_section(PmxSection::Vertices, e))?;
+    let (i, faces) = faces(g, i).map_err(|e| in_section(PmxSection::Faces, e))?;
+    let (i, textures) = list(i, |i| text(g, i)).map_err(|e| in_section(PmxSection::Textures, e))?;
+    let (i, materials) =
+        list(i, |i| material(g, i)).map_err(|e| in_section(PmxSection::Materials, e))?;
+    let (i, bones) = list(i, |i| bone(g, i)).map_err(|e| in_section(PmxSection::Bones, e))?;
+    let (i, morphs) = list(i, |i| morph(g, i)).map_err(|e| in_section(PmxSection::Morphs, e))?;
+    let (i, display_frames) =
+        list(i, |i| display_frame(g, i)).map_err(|e| in_section(PmxSection::DisplayFrames, e))?;
+    let (i, rigid_bodies) =
+        list(i, |i| rigid_body(g, i)).map_err(|e| in_section(PmxSection::RigidBodies, e))?;
+    let (_soft_bodies, joints) =
+        list(i, |i| joint(g, i)).map_err(|e| in_section(PmxSection::Joints, e))?;
+
+    Ok(PmxModel {
+        header,
+        vertices,
+        faces,
+        textures,
+        materials,
+        bones,
+        morphs,
+        display_frames,
+        rigid_bodies,
+        joints,
+    })
+}
+
+fn header(i: Input) -> PResult<PmxHeader> {
+    let magic_input = i;
+    let (i, magic) = take(4usize)(i)?;
+    if magic != b"PMX " {
+        return fail(magic_input, PmxErrorKind::InvalidMagic);
+    }
+
+    let version_input = i;
+    let (i, version) = le_f32(i)?;
+    if version != 2.0 && version != 2.1 {
+        return fail(version_input, PmxErrorKind::UnsupportedVersion(version));
+    }
+
+    // newer versions may append more globals, which we ignore.
+    let globals_input = i;
+    let (i, globals_count) = le_u8(i)?;
+    if globals_count < 8 {
+        return fail(
+            globals_input,
+            PmxErrorKind::InvalidCount(globals_count.into()),
+        );
+    }
+    let (i, globals) = take(globals_count)(i)?;
+    // errors are located by how much input is left, so the input has to
+    // run to the end of the file.
+    let global = |index: usize| (globals[index], &globals_input[1 + index..]);
+
+    let text_encoding = match global(0) {
+        (0, _) => PmxTextEncoding::Utf16Le,
+        (1, _) => PmxTextEncoding::Utf8,
+        (value, input) => return fail(input, invalid_value("text encoding", value)),
+    };
+    let additional_uv_count = match global(1) {
+        (count @ 0..=4, _) => count,
+        (count, input) => return fail(input, PmxErrorKind::InvalidCount(count.into())),
+    };
+    let mut index_sizes = [0; 6];
+    for (n, index_size) in index_sizes.iter_mut().enumerate() {
+        *index_size = match global(2 + n) {
+            (size @ (1 | 2 | 4), _) => size,
+            (size, input) => return fail(input, PmxErrorKind::InvalidIndexSize(size)),
+        };
+    }
+    let [
+        vertex_index_size,
+        texture_index_size,
+        material_index_size,
+        bone_index_size,
+        morph_index_size,
+        rigid_body_index_size,
+    ] = index_sizes;
+
+    let mut header = PmxHeader {
+        version,
+        text_encoding,
+        additional_uv_count,
+        vertex_index_size,
+        texture_index_size,
+        material_index_size,
+        bone_index_size,
+        morph_index_size,
+        rigid_body_index_size,
+        name: PmxText::default(),
+        comment: PmxText::default(),
+    };
+
+    let (i, name) = localized_text(&header, i)?;
+    let (i, comment) = localized_text(&header, i)?;
+    header.name = name;
+    header.comment = comment;
+
+    Ok((i, header))
+}
+
+fn vertex<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxVertex> {
+    let (i, position) = vec3(i)?;
+    let (i, normal) = vec3(i)?;
+    let (i, uv) = vec2(i)?;
+    let mut additional_uvs = [glam::Vec4::ZERO; 4];
+    let mut i = i;
+    for additional_uv in &mut additional_uvs[..g.additional_uv_count as usize] {
+        (i, *additional_uv) = vec4(i)?;
+    }
+
+    let deform_input = i;
+    let (i, deform_kind) = le_u8(i)?;
+    let bone = |i| index(g.bone_index_size, i);
+    let (i, deform) = match deform_kind {
+        0 => {
+            let (i, bone) = bone(i)?;
+            (i, PmxVertexDeform::Bdef1 { bone })
+        }
+        1 => {
+            let (i, bones) = array(i, bone)?;
+            let (i, weight) = le_f32(i)?;
+            (i, PmxVertexDeform::Bdef2 { bones, weight })
+        }
+        2 | 4 => {
+            let (i, bones) = array(i, bone)?;
+            let (i, weights) = array(i, le_f32)?;
+            if deform_kind == 2 {
+                (i, PmxVertexDeform::Bdef4 { bones, weights })
+            } else {
+                (i, PmxVertexDeform::Qdef { bones, weights })
+            }
+        }
+        3 => {
+            let (i, bones) = array(i, bone)?;
+            let (i, weight) = le_f32(i)?;
+            let (i, c) = vec3(i)?;
+            let (i, r0) = vec3(i)?;
+            let (i, r1) = vec3(i)?;
+            (
+                i,
+                PmxVertexDeform::Sdef {
+                    bones,
+                    weight,
+                    c,
+                    r0,
+                    r1,
+                },
+            )
+        }
+        value => return fail(deform_input, invalid_value("vertex deform", value)),
+    };
+    let (i, edge_scale) = le_f32(i)?;
+
+    Ok((
+        i,
+        PmxVertex {
+            position,
+            normal,
+            uv,
+            additional_uvs,
+            deform,
+            edge_scale,
+        },
+    ))
+}
+
+fn faces<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, Vec<[u32; 3]>> {
+    let count_input = i;
+    let (i, index_count) = le_i32(i)?;
+    if index_count < 0 || index_count % 3 != 0 {
+        return fail(count_input, PmxErrorKind::InvalidCount(index_count.into()));
+    }
+    check_count(count_input, i, index_count as usize / 3)?;
+
+    nom::multi::count(
+        |i| array(i, |i| vertex_index(g.vertex_index_size, i)),
+        index_count as usize / 3,
+    )
+    .parse(i)
+}
+
+fn material<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxMaterial> {
+    let (i, name) = localized_text(g, i)?;
+    let (i, diffuse) = vec4(i)?;
+    let (i, specular) = vec3(i)?;
+    let (i, specular_strength) = le_f32(i)?;
+    let (i, ambient) = vec3(i)?;
+    let (i, flags) = le_u8(i)?;
+    let (i, edge_color) = vec4(i)?;
+    let (i, edge_size) = le_f32(i)?;
+    let (i, texture) = index(g.texture_index_size, i)?;
+    let (i, sphere_texture) = index(g.texture_index_size, i)?;
+
+    let sphere_mode_input = i;
+    let (i, sphere_mode) = le_u8(i)?;
+    let sphere_mode = match sphere_mode {
+        0 => PmxSphereMode::Disabled,
+        1 => PmxSphereMode::Multiply,
+        2 => PmxSphereMode::Add,
+        3 => PmxSphereMode::SubTexture,
+        value => return fail(sphere_mode_input, invalid_value("sphere mode", value)),
+    };
+
+    let toon_input = i;
+    let (i, is_shared_toon) = le_u8(i)?;
+    let (i, toon) = match is_shared_toon {
+        0 => {
+            let (i, texture) = index(g.texture_index_size, i)?;
+            (i, PmxToon::Texture(texture))
+        }
+        1 => {
+            let (i, shared) = le_u8(i)?;
+            (i, PmxToon::Shared(shared))
+        }
+        value => return fail(toon_input, invalid_value("toon mode", value)),
+    };
+
+    let (i, memo) = text(g, i)?;
+
+    let count_input = i;
+    let (i, index_count) = le_i32(i)?;
+    if index_count < 0 || index_count % 3 != 0 {
+        return fail(count_input, PmxErrorKind::InvalidCount(index_count.into()));
+    }
+
+    Ok((
+        i,
+        PmxMaterial {
+            name,
+            diffuse,
+            specular,
+            specular_strength,
+            ambient,
+            flags: PmxMaterialFlags(flags),
+            edge_color,
+            edge_size,
+            texture,
+            sphere_texture,
+            sphere_mode,
+            toon,
+            memo,
+            index_count: index_count as u32,
+        },
+    ))
+}
+
+fn bone<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxBone> {
+    let bone_index = |i| index(g.bone_index_size, i);
+
+    let (i, name) = localized_text(g, i)?;
+    let (i, position) = vec3(i)?;
+    let (i, parent) = bone_index(i)?;
+    let (i, layer) = le_i32(i)?;
+    let (i, flags) = le_u16(i)?;
+    let flags = PmxBoneFlags(flags);
+
+    let (i, tail) = if flags.contains(PmxBoneFlags::TAIL_IS_BONE) {
+        let (i, bone) = bone_index(i)?;
+        (i, PmxBoneTail::Bone(bone))
+    } else {
+        let (i, offset) = vec3(i)?;
+        (i, PmxBoneTail::Offset(offset))
+    };
+
+    let (i, inherit) = if flags.contains(PmxBoneFlags::INHERIT_ROTATION)
+        || flags.contains(PmxBoneFlags::INHERIT_TRANSLATION)
+    {
+        let (i, parent) = bone_index(i)?;
+        let (i, weight) = le_f32(i)?;
+        (i, Some(PmxBoneInherit { parent, weight }))
+    } else {
+        (i, None)
+    };
+
+    let (i, fixed_axis) = if flags.contains(PmxBoneFlags::FIXED_AXIS) {
+        let (i, axis) = vec3(i)?;
+        (i, Some(axis))
+    } else {
+        (i, None)
+    };
+
+    let (i, local_axes) = if flags.contains(PmxBoneFlags::LOCAL_AXES) {
+        let (i, x) = vec3(i)?;
+        let (i, z) = vec3(i)?;
+        (i, Some(PmxBoneLocalAxes { x, z }))
+    } else {
+        (i, None)
+    };
+
+    let (i, external_parent_key) = if flags.contains(PmxBoneFlags::EXTERNAL_PARENT_DEFORM) {
+        let (i, key) = le_i32(i)?;
+        (i, Some(key))
+    } else {
+        (i, None)
+    };
+
+    let (i, ik) = if flags.contains(PmxBoneFlags::IK) {
+        let (i, target) = bone_index(i)?;
+        let (i, loop_count) = le_i32(i)?;
+        let (i, limit_angle) = le_f32(i)?;
+        let (i, links) = list(i, |i| ik_link(g, i))?;
+        (
+            i,
+            Some(PmxIk {
+                target,
+                loop_count,
+                limit_angle,
+                links,
+            }),
+        )
+    } else {
+        (i, None)
+    };
+
+    Ok((
+        i,
+        PmxBone {
+            name,
+            position,
+            parent,
+            layer,
+            flags,
+            tail,
+            inherit,
+            fixed_axis,
+            local_axes,
+            external_parent_key,
+            ik,
+        },
+    ))
+}
+
+fn ik_link<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxIkLink> {
+    let (i, bone) = index(g.bone_index_size, i)?;
+    let (i, has_limits) = le_u8(i)?;
+    let (i, limits) = if has_limits != 0 {
+        let (i, min) = vec3(i)?;
+        let (i, max) = vec3(i)?;
+        (i, Some(PmxIkLimits { min, max }))
+    } else {
+        (i, None)
+    };
+
+    Ok((i, PmxIkLink { bone, limits }))
+}
+
+fn morph<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxMorph> {
+    let (i, name) = localized_text(g, i)?;
+
+    let panel_input = i;
+    let (i, panel) = le_u8(i)?;
+    let panel = match panel {
+        0 => PmxMorphPanel::System,
+        1 => PmxMorphPanel::Eyebrow,
+        2 => PmxMorphPanel::Eye,
+        3 => PmxMorphPanel::Mouth,
+        4 => PmxMorphPanel::Other,
+        value => return fail(panel_input, invalid_value("morph panel", value)),
+    };
+
+    let kind_input = i;
+    let (i, kind) = le_u8(i)?;
+    let (i, offsets) = match kind {
+        0 => {
+            let (i, offsets) = list(i, |i| group_morph_offset(g, i))?;
+            (i, PmxMorphOffsets::Group(offsets))
+        }
+        1 => {
+            let (i, offsets) = list(i, |i| {
+                let (i, vertex) = vertex_index(g.vertex_index_size, i)?;
+                let (i, translation) = vec3(i)?;
+                Ok((
+                    i,
+                    PmxVertexMorphOffset {
+                        vertex,
+                        translation,
+                    },
+                ))
+            })?;
+            (i, PmxMorphOffsets::Vertex(offsets))
+        }
+        2 => {
+            let (i, offsets) = list(i, |i| {
+                let (i, bone) = index(g.bone_index_size, i)?;
+                let (i, translation) = vec3(i)?;
+                let (i, rotation) = vec4(i)?;
+                Ok((
+                    i,
+                    PmxBoneMorphOffset {
+                        bone,
+                        translation,
+                        rotation: glam::Quat::from_vec4(rotation),
+                    },
+                ))
+            })?;
+            (i, PmxMorphOffsets::Bone(offsets))
+        }
+        3..=7 => {
+            let (i, offsets) = list(i, |i| {
+                let (i, vertex) = vertex_index(g.vertex_index_size, i)?;
+                let (i, offset) = vec4(i)?;
+                Ok((i, PmxUvMorphOffset { vertex, offset }))
+            })?;
+            (
+                i,
+                PmxMorphOffsets::Uv {
+                    channel: kind - 3,
+                    offsets,
+                },
+            )
+        }
+        8 => {
+            let (i, offsets) = list(i, |i| material_morph_offset(g, i))?;
+            (i, PmxMorphOffsets::Material(offsets))
+        }
+        9 => {
+            let (i, offsets) = list(i, |i| group_morph_offset(g, i))?;
+            (i, PmxMorphOffsets::Flip(offsets))
+        }
+        10 => {
+            let (i, offsets) = list(i, |i| {
+                let (i, rigid_body) = index(g.rigid_body_index_size, i)?;
+                let (i, is_local) = le_u8(i)?;
+                let (i, velocity) = vec3(i)?;
+                let (i, torque) = vec3(i)?;
+                Ok((
+                    i,
+                    PmxImpulseMorphOffset {
+                        rigid_body,
+                        is_local: is_local != 0,
+                        velocity,
+                        torque,
+                    },
+                ))
+            })?;
+            (i, PmxMorphOffsets::Impulse(offsets))
+        }
+        value => return fail(kind_input, invalid_value("morph type", value)),
+    };
+
+    Ok((
+        i,
+        PmxMorph {
+            name,
+            panel,
+            offsets,
+        },
+    ))
+}
+
+fn group_morph_offset<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxGroupMorphOffset> {
+    let (i, morph) = index(g.morph_index_size, i)?;
+    let (i, weight) = le_f32(i)?;
+
+    Ok((i, PmxGroupMorphOffset { morph, weight }))
+}
+
+fn material_morph_offset<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxMaterialMorphOffset> {
+    let (i, material) = index(g.material_index_size, i)?;
+
+    let operation_input = i;
+    let (i, operation) = le_u8(i)?;
+    let operation = match operation {
+        0 => PmxMaterialMorphOperation::Multiply,
+        1 => PmxMaterialMorphOperation::Add,
+        value => {
+            return fail(
+                operation_input,
+                invalid_value("material morph operation", value),
+            );
+        }
+    };
+
+    let (i, diffuse) = vec4(i)?;
+    let (i, specular) = vec3(i)?;
+    let (i, specular_strength) = le_f32(i)?;
+    let (i, ambient) = vec3(i)?;
+    let (i, edge_color) = vec4(i)?;
+    let (i, edge_size) = le_f32(i)?;
+    let (i, texture_tint) = vec4(i)?;
+    let (i, sphere_tint) = vec4(i)?;
+    let (i, toon_tint) = vec4(i)?;
+
+    Ok((
+        i,
+        PmxMaterialMorphOffset {
+            material,
+            operation,
+            diffuse,
+            specular,
+            specular_strength,
+            ambient,
+            edge_color,
+            edge_size,
+            texture_tint,
+            sphere_tint,
+            toon_tint,
+        },
+    ))
+}
+
+fn display_frame<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxDisplayFrame> {
+    let (i, name) = localized_text(g, i)?;
+    let (i, is_special) = le_u8(i)?;
+    let (i, items) = list(i, |i| {
+        let kind_input = i;
+        let (i, kind) = le_u8(i)?;
+        match kind {
+            0 => {
+                let (i, bone) = index(g.bone_index_size, i)?;
+                Ok((i, PmxDisplayFrameItem::Bone(bone)))
+            }
+            1 => {
+                let (i, morph) = index(g.morph_index_size, i)?;
+                Ok((i, PmxDisplayFrameItem::Morph(morph)))
+            }
+            value => fail(kind_input, invalid_value("display frame item type", value)),
+        }
+    })?;
+
+    Ok((
+        i,
+        PmxDisplayFrame {
+            name,
+            is_special: is_special != 0,
+            items,
+        },
+    ))
+}
+
+fn rigid_body<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxRigidBody> {
+    let (i, name) = localized_text(g, i)?;
+    let (i, bone) = index(g.bone_index_size, i)?;
+    let (i, group) = le_u8(i)?;
+    let (i, collision_mask) = le_u16(i)?;
+
+    let shape_input = i;
+    let (i, shape) = le_u8(i)?;
+    let shape = match shape {
+        0 => PmxRigidBodyShape::Sphere,
+        1 => PmxRigidBodyShape::Box,
+        2 => PmxRigidBodyShape::Capsule,
+        value => return fail(shape_input, invalid_value("rigid body shape", value)),
+    };
+
+    let (i, size) = vec3(i)?;
+    let (i, position) = vec3(i)?;
+    let (i, rotation) = vec3(i)?;
+    let (i, mass) = le_f32(i)?;
+    let (i, linear_damping) = le_f32(i)?;
+    let (i, angular_damping) = le_f32(i)?;
+    let (i, restitution) = le_f32(i)?;
+    let (i, friction) = le_f32(i)?;
+
+    let mode_input = i;
+    let (i, mode) = le_u8(i)?;
+    let mode = match mode {
+        0 => PmxRigidBodyMode::FollowBone,
+        1 => PmxRigidBodyMode::Physics,
+        2 => PmxRigidBodyMode::PhysicsWithBonePosition,
+        value => return fail(mode_input, invalid_value("rigid body mode", value)),
+    };
+
+    Ok((
+        i,
+        PmxRigidBody {
+            name,
+            bone,
+            group,
+            collision_mask,
+            shape,
+            size,
+            position,
+            rotation,
+            mass,
+            linear_damping,
+            angular_damping,
+            restitution,
+            friction,
+            mode,
+        },
+    ))
+}
+
+fn joint<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxJoint> {
+    let (i, name) = localized_text(g, i)?;
+
+    let kind_input = i;
+    let (i, kind) = le_u8(i)?;
+    let kind = match kind {
+        0 => PmxJointKind::Spring6Dof,
+        1 => PmxJointKind::SixDof,
+        2 => PmxJointKind::PointToPoint,
+        3 => PmxJointKind::ConeTwist,
+        4 => PmxJointKind::Slider,
+        5 => PmxJointKind::Hinge,
+        value => return fail(kind_input, invalid_value("joint type", value)),
+    };
+
+    let (i, rigid_bodies) = array(i, |i| index(g.rigid_body_index_size, i))?;
+    let (i, position) = vec3(i)?;
+    let (i, rotation) = vec3(i)?;
+    let (i, linear_min) = vec3(i)?;
+    let (i, linear_max) = vec3(i)?;
+    let (i, angular_min) = vec3(i)?;
+    let (i, angular_max) = vec3(i)?;
+    let (i, linear_spring) = vec3(i)?;
+    let (i, angular_spring) = vec3(i)?;
+
+    Ok((
+        i,
+        PmxJoint {
+            name,
+            kind,
+            rigid_bodies,
+            position,
+            rotation,
+            linear_min,
+            linear_max,
+            angular_min,
+            angular_max,
+            linear_spring,
+            angular_spring,
+        },
+    ))
+}
+
+fn localized_text<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, PmxText> {
+    let (i, local) = text(g, i)?;
+    let (i, universal) = text(g, i)?;
+
+    Ok((i, PmxText { local, universal }))
+}
+
+fn text<'a>(g: &PmxHeader, i: Input<'a>) -> PResult<'a, String> {
+    let length_input = i;
+    let (i, length) = le_i32(i)?;
+    if length < 0 {
+        return fail(length_input, PmxErrorKind::InvalidCount(length.into()));
+    }
+    let (i, bytes) = take(length as usize)(i)?;
+
+    let text = match g.text_encoding {
+        PmxTextEncoding::Utf16Le => {
+            if bytes.len() % 2 != 0 {
+                return fail(length_input, PmxErrorKind::InvalidText);
+            }
+            let units = bytes
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+            char::decode_utf16(units)
+                .collect::<Result<String, _>>()
+                .ok()
+        }
+        PmxTextEncoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+    };
+
+    match text {
+        Some(text) => Ok((i, text)),
+        None => fail(length_input, PmxErrorKind::InvalidText),
+    }
+}
+
+/// Vertex indices are unsigned, except for 4 byte ones.
+fn vertex_index(size: u8, i: Input) -> PResult<u32> {
+    match size {
+        1 => le_u8.map(u32::from).parse(i),
+        2 => le_u16.map(u32::from).parse(i),
+        _ => {
+            let (rest, index) = le_i32(i)?;
+            match u32::try_from(index) {
+                Ok(index) => Ok((rest, index)),
+                Err(_) => fail(i, invalid_value("vertex index", index)),
+            }
+        }
+    }
+}
+
+/// Other indices are signed, with `-1` meaning none.
+fn index(size: u8, i: Input) -> PResult<Option<u32>> {
+    let (i, index) = match size {
+        1 => le_i8.map(i32::from).parse(i)?,
+        2 => le_i16.map(i32::from).parse(i)?,
+        _ => le_i32(i)?,
+    };
+
+    Ok((i, u32::try_from(index).ok()))
+}
+
+/// A list prefixed with its length.
+fn list<'a, T>(
+    i: Input<'a>,
+    element: impl FnMut(Input<'a>) -> PResult<'a, T>,
+) -> PResult<'a, Vec<T>> {
+    let count_input = i;
+    let (i, count) = le_i32(i)?;
+    if count < 0 {
+        return fail(count_input, PmxErrorKind::InvalidCount(count.into()));
+    }
+    check_count(count_input, i, count as usize)?;
+
+    nom::multi::count(element, count as usize).parse(i)
+}
+
+/// Every element takes at least one byte, so larger counts can only come from
+/// a broken file. Checked before allocating anything.
+fn check_count<'a>(count_input: Input<'a>, i: Input<'a>, count: usize) -> PResult<'a, ()> {
+    if count > i.len() {
+        return fail(count_input, PmxErrorKind::InvalidCount(count as i64));
+    }
+
+    Ok((i, ()))
+}
+
+fn array<'a, T: Copy + Default, const N: usize>(
+    mut i: Input<'a>,
+    mut element: impl FnMut(Input<'a>) -> PResult<'a, T>,
+) -> PResult<'a, [T; N]> {
+    let mut values = [T::default(); N];
+    for value in &mut values {
+        (i, *value) = element(i)?;
+    }
+
+    Ok((i, values))
+}
+
+fn vec2(i: Input) -> PResult<glam::Vec2> {
+    let (i, values) = array(i, le_f32)?;
+    Ok((i, glam::Vec2::from_array(values)))
+}
+
+fn vec3(i: Input) -> PResult<glam::Vec3> {
+    let (i, values) = array(i, le_f32)?;
+    Ok((i, glam::Vec3::from_array(values)))
+}
+
+fn vec4(i: Input) -> PResult<glam::Vec4> {
+    let (i, values) = array(i, le_f32)?;
+    Ok((i, glam::Vec4::from_array(values)))
+}
+
+fn invalid_value(field: &'static str, value: impl Into<i64>) -> PmxErrorKind {
+    PmxErrorKind::InvalidValue {
+        field,
+        value: value.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX: usize = 0;
+    const BONE: usize = 3;
+
+    /// Writes PMX files field by field, to build fixtures from.
+    struct Writer {
+        bytes: Vec<u8>,
+        encoding: PmxTextEncoding,
+        /// In header order: vertex, texture, material, bone, morph and rigid
+        /// body indices.
+        index_sizes: [u8; 6],
+    }
+
+    impl Writer {
+        /// Up to the end of the globals, before the name.
+        fn new(version: f32, encoding: PmxTextEncoding, index_sizes: [u8; 6]) -> Self {
+            let mut w = Self {
+                bytes: b"PMX ".to_vec(),
+                encoding,
+                index_sizes,
+            };
+            w.f32s(&[version]);
+            w.u8(8);
+            w.u8(match encoding {
+                PmxTextEncoding::Utf16Le => 0,
+                PmxTextEncoding::Utf8 => 1,
+            });
+            w.u8(0);
+            for size in index_sizes {
+                w.u8(size);
+            }
+            w
+        }
+
+        /// A whole header, named `name`.
+        fn header(
+            version: f32,
+            encoding: PmxTextEncoding,
+            index_sizes: [u8; 6],
+            name: &str,
+        ) -> Self {
+            let mut w = Self::new(version, encoding, index_sizes);
+            w.text(name);
+            w.text("");
+            w.text("");
+            w.text("");
+            w
+        }
+
+        fn len(&self) -> usize {
+            self.bytes.len()
+        }
+
+        fn u8(&mut self, value: u8) {
+            self.bytes.push(value);
+        }
+
+        fn i32(&mut self, value: i32) {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        fn f32s(&mut self, values: &[f32]) {
+            for value in values {
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        fn text(&mut self, text: &str) {
+            let bytes = match self.encoding {
+                PmxTextEncoding::Utf16Le => text
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>(),
+                PmxTextEncoding::Utf8 => text.as_bytes().to_vec(),
+            };
+            self.i32(bytes.len() as i32);
+            self.bytes.extend_from_slice(&bytes);
+        }
+
+        /// Two's complement, so that the same value reads back as either
+        /// signed or unsigned.
+        fn index(&mut self, kind: usize, value: i64) {
+            let size = self.index_sizes[kind] as usize;
+            self.bytes.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+
+        fn vertex(&mut self, additional_uv_count: usize, deform: impl FnOnce(&mut Self)) {
+            self.f32s(&[1.0, 2.0, 3.0, 0.0, 1.0, 0.0, 0.25, 0.75]);
+            for _ in 0..additional_uv_count {
+                self.f32s(&[0.5; 4]);
+            }
+            deform(self);
+            self.f32s(&[1.0]);
+        }
+
+        fn faces(&mut self, indices: &[i64]) {
+            self.i32(indices.len() as i32);
+            for &index in indices {
+                self.index(VERTEX, index);
+            }
+        }
+
+        /// Textures, and empty sections after them.
+        fn rest(mut self, textures: &[&str]) -> Vec<u8> {
+            self.i32(textures.len() as i32);
+            for texture in textures {
+                self.text(texture);
+            }
+            // materials, bones, morphs, display frames, bodies and joints.
+            for _ in 0..6 {
+                self.i32(0);
+            }
+            self.bytes
+        }
+    }
+
+    #[test]
+    fn parses_every_index_size() {
+        // vertex indices above the signed range of 1 and 2 bytes.
+        for (size, vertex_index, bone_index) in [(1, 200, 100), (2, 40_000, 30_000), (4, 3, 2)] {
+            let mut w = Writer::header(2.0, PmxTextEncoding::Utf8, [size; 6], "model");
+            w.i32(4);
+            w.vertex(0, |w| {
+                w.u8(0);
+                w.index(BONE, bone_index);
+            });
+            w.vertex(0, |w| {
+                w.u8(1);
+                w.index(BONE, -1);
+                w.index(BONE, bone_index);
+                w.f32s(&[0.25]);
+            });
+            w.vertex(0, |w| {
+                w.u8(2);
+                for bone in [0, 1, -1, bone_index] {
+                    w.index(BONE, bone);
+                }
+                w.f32s(&[0.1, 0.2, 0.3, 0.4]);
+            });
+            w.vertex(0, |w| {
+                w.u8(3);
+                w.index(BONE, bone_index);
+                w.index(BONE, 0);
+                w.f32s(&[0.75, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 3.0, 3.0, 3.0]);
+            });
+            w.faces(&[0, 1, vertex_index]);
+            let model = parse_pmx(&w.rest(&["tex.png"])).unwrap();
+
+            let bone_index = Some(bone_index as u32);
+            assert_eq!(model.header.vertex_index_size, size);
+            assert_eq!(model.header.bone_index_size, size);
+            assert_eq!(
+                model.vertices[0].deform,
+                PmxVertexDeform::Bdef1 { bone: bone_index }
+            );
+            assert_eq!(
+                model.vertices[1].deform,
+                PmxVertexDeform::Bdef2 {
+                    bones: [None, bone_index],
+                    weight: 0.25
+                }
+            );
+            assert_eq!(
+                model.vertices[2].deform,
+                PmxVertexDeform::Bdef4 {
+                    bones: [Some(0), Some(1), None, bone_index],
+                    weights: [0.1, 0.2, 0.3, 0.4]
+                }
+            );
+            assert_eq!(
+                model.vertices[3].deform,
+                PmxVertexDeform::Sdef {
+                    bones: [bone_index, Some(0)],
+                    weight: 0.75,
+                    c: glam::Vec3::ONE,
+                    r0: glam::Vec3::splat(2.0),
+                    r1: glam::Vec3::splat(3.0),
+                }
+            );
+            assert_eq!(model.vertices[3].position, glam::vec3(1.0, 2.0, 3.0));
+            assert_eq!(model.vertices[3].edge_scale, 1.0);
+            assert_eq!(model.faces, [[0, 1, vertex_index as u32]]);
+            assert_eq!(model.textures, ["tex.png"]);
+        }
+    }
+
+    #[test]
+    fn parses_pmx_2_1_qdef_and_additional_uvs() {
+        let mut w = Writer::new(2.1, PmxTextEncoding::Utf8, [4, 1, 1, 2, 1, 1]);
+        // one additional UV.
+        w.bytes[10] = 1;
+        w.text("model");
+        w.text("");
+        w.text("");
+        w.text("");
+        w.i32(1);
+        w.vertex(1, |w| {
+            w.u8(4);
+            for bone in [0, 1, 2, 3] {
+                w.index(BONE, bone);
+            }
+            w.f32s(&[0.4, 0.3, 0.2, 0.1]);
+        });
+        w.faces(&[0, 0, 0]);
+        let model = parse_pmx(&w.rest(&[])).unwrap();
+
+        assert_eq!(model.header.version, 2.1);
+        assert_eq!(model.header.additional_uv_count, 1);
+        assert_eq!(model.vertices[0].additional_uvs[0], glam::Vec4::splat(0.5));
+        assert_eq!(model.vertices[0].additional_uvs[1], glam::Vec4::ZERO);
+        assert_eq!(
+            model.vertices[0].deform,
+            PmxVertexDeform::Qdef {
+                bones: [Some(0), Some(1), Some(2), Some(3)],
+                weights: [0.4, 0.3, 0.2, 0.1]
+            }
+        );
+    }
+
+    #[test]
+    fn decodes_utf8_and_utf16le_text() {
+        for encoding in [PmxTextEncoding::Utf8, PmxTextEncoding::Utf16Le] {
+            let mut w = Writer::header(2.0, encoding, [1; 6], "初音ミク");
+            w.i32(0);
+            w.faces(&[]);
+            let model = parse_pmx(&w.rest(&["tex\\顔.png", ""])).unwrap();
+
+            assert_eq!(model.header.text_encoding, encoding);
+            assert_eq!(model.header.name.local, "初音ミク");
+            assert_eq!(model.header.name.universal, "");
+            assert_eq!(model.textures, ["tex\\顔.png", ""]);
+        }
+    }
+
+    fn parse_error(bytes: &[u8]) -> PmxError {
+        parse_pmx(bytes).unwrap_err()
+    }
+
+    #[test]
+    fn reports_invalid_headers() {
+        assert_eq!(
+            parse_error(b"PMD \0\0\0\0"),
+            PmxError {
+                section: PmxSection::Header,
+                offset: 0,
+                kind: PmxErrorKind::InvalidMagic,
+            }
+        );
+        assert_eq!(
+            parse_error(b"PMX "),
+            PmxError {
+                section: PmxSection::Header,
+                offset: 4,
+                kind: PmxErrorKind::UnexpectedEnd,
+            }
+        );
+
+        let w = Writer::new(3.0, PmxTextEncoding::Utf8, [1; 6]);
+        assert_eq!(
+            parse_error(&w.bytes),
+            PmxError {
+                section: PmxSection::Header,
+                offset: 4,
+                kind: PmxErrorKind::UnsupportedVersion(3.0),
+            }
+        );
+
+        // the bone index size is the 6th global, after the magic, the
+        // version and the globals count.
+        let w = Writer::header(2.0, PmxTextEncoding::Utf8, [1, 1, 1, 3, 1, 1], "model");
+        assert_eq!(
+            parse_error(&w.bytes),
+            PmxError {
+                section: PmxSection::Header,
+                offset: 14,
+                kind: PmxErrorKind::InvalidIndexSize(3),
+            }
+        );
+
+        let mut w = Writer::new(2.0, PmxTextEncoding::Utf16Le, [1; 6]);
+        let name_offset = w.len();
+        w.i32(3);
+        w.bytes.extend_from_slice(b"abc");
+        assert_eq!(
+            parse_error(&w.bytes),
+            PmxError {
+                section: PmxSection::Header,
+                offset: name_offset,
+                kind: PmxErrorKind::InvalidText,
+            }
+        );
+    }
+
+    #[test]
+    fn reports_section_and_offset_of_invalid_vertices() {
+        let mut w = Writer::header(2.0, PmxTextEncoding::Utf8, [2; 6], "model");
+        w.i32(2);
+        w.vertex(0, |w| {
+            w.u8(0);
+            w.index(BONE, 0);
+        });
+        let mut deform_offset = 0;
+        w.vertex(0, |w| {
+            deform_offset = w.len();
+            w.u8(7);
+        });
+        let error = parse_error(&w.rest(&[]));
+
+        assert_eq!(
+            error,
+            PmxError {
+                section: PmxSection::Vertices,
+                offset: deform_offset,
+                kind: PmxErrorKind::InvalidValue {
+                    field: "vertex deform",
+                    value: 7
+                },
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid PMX in Vertices at byte {} ({:#x}): invalid vertex deform 7",
+                deform_offset, deform_offset
+            )
+        );
+    }
+
+    #[test]
+    fn reports_section_and_offset_of_truncated_faces() {
+        let mut w = Writer::header(2.0, PmxTextEncoding::Utf8, [2; 6], "model");
+        w.i32(0);
+        w.i32(3);
+        w.index(VERTEX, 0);
+        let truncated_offset = w.len();
+        w.u8(1);
+
+        assert_eq!(
+            parse_error(&w.bytes),
+            PmxError {
+                section: PmxSection::Faces,
+                offset: truncated_offset,
+                kind: PmxErrorKind::UnexpectedEnd,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_negative_4_byte_vertex_indices() {
+        let mut w = Writer::header(2.0, PmxTextEncoding::Utf8, [4; 6], "model");
+        w.i32(0);
+        w.i32(3);
+        w.index(VERTEX, 0);
+        let negative_offset = w.len();
+        w.index(VERTEX, -5);
+        w.index(VERTEX, 0);
+
+        assert_eq!(
+            parse_error(&w.rest(&[])),
+            PmxError {
+                section: PmxSection::Faces,
+                offset: negative_offset,
+                kind: PmxErrorKind::InvalidValue {
+                    field: "vertex index",
+                    value: -5
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_counts_larger_than_the_file() {
+        let mut w = Writer::header(2.0, PmxTextEncoding::Utf8, [1; 6], "model");
+        let count_offset = w.len();
+        w.i32(1_000_000);
+
+        assert_eq!(
+            parse_error(&w.rest(&[])),
+            PmxError {
+                section: PmxSection::Vertices,
+                offset: count_offset,
+                kind: PmxErrorKind::InvalidCount(1_000_000),
+            }
+        );
+    }
+}
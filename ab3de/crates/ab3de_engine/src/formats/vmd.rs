@@ -0,0 +1,309 @@
+//! VMD motions, as used by MikuMikuDance.
+//!
+//! Keyframes are stored in file order, which is not necessarily sorted by
+//! frame. Names are kept as the raw Shift-JIS bytes, see [`VmdName`].
+
+mod parser;
+
+pub use parser::parse_vmd;
+
+/// MMD plays motions at this rate.
+pub const VMD_FPS: f64 = 30.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdMotion {
+    pub header: VmdHeader,
+    pub bone_keyframes: Vec<VmdBoneKeyframe>,
+    pub morph_keyframes: Vec<VmdMorphKeyframe>,
+    pub camera_keyframes: Vec<VmdCameraKeyframe>,
+    pub light_keyframes: Vec<VmdLightKeyframe>,
+    pub self_shadow_keyframes: Vec<VmdSelfShadowKeyframe>,
+    pub ik_keyframes: Vec<VmdIkKeyframe>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdHeader {
+    pub version: VmdVersion,
+    /// The model the motion was made for. Camera motions use a placeholder.
+    pub model_name: VmdName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmdVersion {
+    /// `Vocaloid Motion Data file`, with 10 byte model names.
+    V1,
+    /// `Vocaloid Motion Data 0002`, with 20 byte model names.
+    V2,
+}
+
+/// A Shift-JIS name from a fixed-size field, without the terminator.
+///
+/// MMD cuts names that don't fit, sometimes in the middle of a character, so
+/// compare them with [`Self::matches`] rather than by decoding.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VmdName {
+    pub bytes: Vec<u8>,
+    /// The name filled its field, so it may have been cut.
+    pub fills_field: bool,
+}
+
+impl VmdName {
+    /// Lossy, as the name may end in half a character.
+    pub fn decode(&self) -> String {
+        encoding_rs::SHIFT_JIS
+            .decode_without_bom_handling(&self.bytes)
+            .0
+            .into_owned()
+    }
+
+    /// Whether this is `name` (e.g. a PMX bone name), as MMD would have
+    /// written it.
+    pub fn matches(&self, name: &str) -> bool {
+        let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode(name);
+        if self.fills_field {
+            encoded.starts_with(&self.bytes)
+        } else {
+            *encoded == *self.bytes
+        }
+    }
+}
+
+impl std::fmt::Display for VmdName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.decode())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdBoneKeyframe {
+    pub bone_name: VmdName,
+    pub frame: u32,
+    /// Relative to the bone's rest position.
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub interpolation: VmdBoneInterpolation,
+}
+
+/// How to get from the previous keyframe to this one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VmdBoneInterpolation {
+    pub translation_x: VmdBezier,
+    pub translation_y: VmdBezier,
+    pub translation_z: VmdBezier,
+    pub rotation: VmdBezier,
+}
+
+/// The inner control points of a cubic Bézier curve from `(0, 0)` to
+/// `(127, 127)`. Each coordinate is `0..=127`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmdBezier {
+    pub x1: u8,
+    pub y1: u8,
+    pub x2: u8,
+    pub y2: u8,
+}
+
+impl VmdBezier {
+    pub const LINEAR: Self = Self {
+        x1: 20,
+        y1: 20,
+        x2: 107,
+        y2: 107,
+    };
+
+    /// Maps the progress `x` between two keyframes, `0..=1`, to the
+    /// interpolation weight.
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        let [x1, y1, x2, y2] = [self.x1, self.y1, self.x2, self.y2].map(|c| c as f32 / 127.0);
+        if x1 == y1 && x2 == y2 {
+            return x;
+        }
+
+        let bezier = |t: f32, p1: f32, p2: f32| {
+            let s = 1.0 - t;
+            3.0 * s * s * t * p1 + 3.0 * s * t * t * p2 + t * t * t
+        };
+
+        // x(t) is monotonic since both control points are in the unit square.
+        let (mut t_min, mut t_max) = (0.0f32, 1.0f32);
+        let mut t = x;
+        for _ in 0..32 {
+            let error = bezier(t, x1, x2) - x;
+            if error.abs() < 1e-5 {
+                break;
+            }
+            if error > 0.0 {
+                t_max = t;
+            } else {
+                t_min = t;
+            }
+            t = (t_min + t_max) * 0.5;
+        }
+
+        bezier(t, y1, y2)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdMorphKeyframe {
+    pub morph_name: VmdName,
+    pub frame: u32,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdCameraKeyframe {
+    pub frame: u32,
+    /// From the camera to [`Self::target`], negative in front of it.
+    pub distance: f32,
+    pub target: glam::Vec3,
+    /// Euler angles in radians.
+    pub rotation: glam::Vec3,
+    /// For x, y, z, rotation, distance and fov, in that order.
+    pub interpolation: [VmdBezier; 6],
+    pub fov_degrees: u32,
+    pub is_perspective: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdLightKeyframe {
+    pub frame: u32,
+    pub color: glam::Vec3,
+    pub direction: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdSelfShadowKeyframe {
+    pub frame: u32,
+    /// `0` is off, `1` and `2` are MMD's two shadow modes.
+    pub mode: u8,
+    pub distance: f32,
+}
+
+/// Sets the model's visibility and switches IK bones on or off, from this
+/// frame on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdIkKeyframe {
+    pub frame: u32,
+    pub is_visible: bool,
+    pub ik_states: Vec<VmdIkState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdIkState {
+    pub bone_name: VmdName,
+    pub is_enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmdError {
+    pub section: VmdSection,
+    /// From the start of the file.
+    pub offset: usize,
+    pub kind: VmdErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmdSection {
+    Header,
+    BoneKeyframes,
+    MorphKeyframes,
+    CameraKeyframes,
+    LightKeyframes,
+    SelfShadowKeyframes,
+    IkKeyframes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmdErrorKind {
+    UnexpectedEnd,
+    InvalidMagic,
+    InvalidCount(u32),
+    Other(nom::error::ErrorKind),
+}
+
+impl std::fmt::Display for VmdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid VMD in {:?} at byte {} ({:#x}): ",
+            self.section, self.offset, self.offset
+        )?;
+        match &self.kind {
+            VmdErrorKind::UnexpectedEnd => write!(f, "unexpected end of data"),
+            VmdErrorKind::InvalidMagic => write!(f, "not a VMD file"),
+            VmdErrorKind::InvalidCount(count) => write!(f, "invalid count {}", count),
+            VmdErrorKind::Other(kind) => write!(f, "{}", kind.description()),
+        }
+    }
+}
+
+impl std::error::Error for VmdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bezier_curves_start_at_zero_and_end_at_one() {
+        let ease_in = VmdBezier {
+            x1: 64,
+            y1: 0,
+            x2: 127,
+            y2: 127,
+        };
+        for curve in [VmdBezier::LINEAR, ease_in] {
+            assert_eq!(curve.evaluate(0.0), 0.0);
+            assert!((curve.evaluate(1.0) - 1.0).abs() < 1e-4);
+            assert_eq!(curve.evaluate(-1.0), curve.evaluate(0.0));
+            assert_eq!(curve.evaluate(2.0), curve.evaluate(1.0));
+        }
+    }
+
+    #[test]
+    fn bezier_curves_pass_through_their_midpoint() {
+        assert_eq!(VmdBezier::LINEAR.evaluate(0.5), 0.5);
+
+        // symmetric around the center, so halfway in time is halfway there.
+        let ease_in_out = VmdBezier {
+            x1: 100,
+            y1: 10,
+            x2: 27,
+            y2: 117,
+        };
+        assert!((ease_in_out.evaluate(0.5) - 0.5).abs() < 1e-4);
+
+        // at t = 0.5 this curve is at x = 3/8 * (64 + 127) / 127 + 1/8 and
+        // y = 3/8 + 1/8.
+        let ease_in = VmdBezier {
+            x1: 64,
+            y1: 0,
+            x2: 127,
+            y2: 127,
+        };
+        let x = 3.0 / 8.0 * (64.0 + 127.0) / 127.0 + 1.0 / 8.0;
+        assert!((ease_in.evaluate(x) - 0.5).abs() < 1e-4);
+        assert!(ease_in.evaluate(0.5) < 0.5);
+    }
+
+    #[test]
+    fn names_that_fill_their_field_match_by_prefix() {
+        let full_name = encoding_rs::SHIFT_JIS.encode("右足首ＩＫ親の先").0;
+        let cut = VmdName {
+            bytes: full_name[..15].to_vec(),
+            fills_field: true,
+        };
+        assert!(cut.matches("右足首ＩＫ親の先"));
+        assert!(cut.matches("右足首ＩＫ親の先端"));
+        assert!(!cut.matches("右足首ＩＫ親"));
+        assert!(!cut.matches("左足首ＩＫ親の先"));
+
+        let short = VmdName {
+            bytes: encoding_rs::SHIFT_JIS.encode("右足").0.into_owned(),
+            fills_field: false,
+        };
+        assert!(short.matches("右足"));
+        assert!(!short.matches("右足首"));
+    }
+}
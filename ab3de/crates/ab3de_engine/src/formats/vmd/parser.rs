@@ -0,0 +1,443 @@
+use nom::{
+    IResult, Parser,
+    bytes::complete::take,
+    error::ErrorKind,
+    number::complete::{le_f32, le_u8, le_u32},
+};
+
+use super::*;
+
+type Input<'a> = &'a [u8];
+type PResult<'a, T> = IResult<Input<'a>, T, ParseError<'a>>;
+
+#[derive(Debug)]
+struct ParseError<'a> {
+    input: Input<'a>,
+    kind: VmdErrorKind,
+}
+
+impl<'a> nom::error::ParseError<Input<'a>> for ParseError<'a> {
+    fn from_error_kind(input: Input<'a>, kind: ErrorKind) -> Self {
+        let kind = match kind {
+            ErrorKind::Eof => VmdErrorKind::UnexpectedEnd,
+            kind => VmdErrorKind::Other(kind),
+        };
+        Self { input, kind }
+    }
+
+    fn append(_input: Input<'a>, _kind: ErrorKind, other: Self) -> Self {
+        // the innermost error is the most precise one.
+        other
+    }
+}
+
+fn fail<T>(input: Input, kind: VmdErrorKind) -> PResult<T> {
+    Err(nom::Err::Failure(ParseError { input, kind }))
+}
+
+/// Parses a whole VMD file.
+///
+/// Older files end after any of the keyframe lists; the missing lists are
+/// left empty.
+pub fn parse_vmd(bytes: &[u8]) -> Result<VmdMotion, VmdError> {
+    let in_section = |section, error: nom::Err<ParseError>| {
+        let (offset, kind) = match error {
+            nom::Err::Error(e) | nom::Err::Failure(e) => (bytes.len() - e.input.len(), e.kind),
+            nom::Err::Incomplete(_) => (bytes.len(), VmdErrorKind::UnexpectedEnd),
+        };
+        VmdError {
+            section,
+            offset,
+            kind,
+        }
+    };
+
+    let (i, header) = header(bytes).map_err(|e| in_section(VmdSection::Header, e))?;
+    let (i, bone_keyframes) = optional_list(i, 111, bone_keyframe)
+        .map_err(|e| in_section(VmdSection::BoneKeyframes, e))?;
+    let (i, morph_keyframes) = optional_list(i, 23, morph_keyframe)
+        .map_err(|e| in_section(VmdSection::MorphKeyframes, e))?;
+    let (i, camera_keyframes) = optional_list(i, 61, camera_keyframe)
+        .map_err(|e| in_section(VmdSection::CameraKeyframes, e))?;
+    let (i, light_keyframes) = optional_list(i, 28, light_keyframe)
+        .map_err(|e| in_section(VmdSection::LightKeyframes, e))?;
+    let (i, self_shadow_keyframes) = optional_list(i, 9, self_shadow_keyframe)
+        .map_err(|e| in_section(VmdSection::SelfShadowKeyframes, e))?;
+    let (_, ik_keyframes) =
+        optional_list(i, 9, ik_keyframe).map_err(|e| in_section(VmdSection::IkKeyframes, e))?;
+
+    Ok(VmdMotion {
+        header,
+        bone_keyframes,
+        morph_keyframes,
+        camera_keyframes,
+        light_keyframes,
+        self_shadow_keyframes,
+        ik_keyframes,
+    })
+}
+
+fn header(i: Input) -> PResult<VmdHeader> {
+    let (i, magic) = take(30usize)(i)?;
+    let version = if magic.starts_with(b"Vocaloid Motion Data 0002") {
+        VmdVersion::V2
+    } else if magic.starts_with(b"Vocaloid Motion Data file") {
+        VmdVersion::V1
+    } else {
+        return fail(magic, VmdErrorKind::InvalidMagic);
+    };
+
+    let (i, model_name) = name(
+        i,
+        match version {
+            VmdVersion::V1 => 10,
+            VmdVersion::V2 => 20,
+        },
+    )?;
+
+    Ok((
+        i,
+        VmdHeader {
+            version,
+            model_name,
+        },
+    ))
+}
+
+fn bone_keyframe(i: Input) -> PResult<VmdBoneKeyframe> {
+    let (i, bone_name) = name(i, 15)?;
+    let (i, frame) = le_u32(i)?;
+    let (i, translation) = vec3(i)?;
+    let (i, rotation) = array(i, le_f32)?;
+    let (i, table) = take(64usize)(i)?;
+
+    // the first row holds x1, y1, x2 and y2 of each curve, interleaved. The
+    // other three rows are shifted copies kept for MMD's sake.
+    let curve = |channel: usize| VmdBezier {
+        x1: table[channel],
+        y1: table[4 + channel],
+        x2: table[8 + channel],
+        y2: table[12 + channel],
+    };
+
+    Ok((
+        i,
+        VmdBoneKeyframe {
+            bone_name,
+            frame,
+            translation,
+            rotation: glam::Quat::from_array(rotation),
+            interpolation: VmdBoneInterpolation {
+                translation_x: curve(0),
+                translation_y: curve(1),
+                translation_z: curve(2),
+                rotation: curve(3),
+            },
+        },
+    ))
+}
+
+fn morph_keyframe(i: Input) -> PResult<VmdMorphKeyframe> {
+    let (i, morph_name) = name(i, 15)?;
+    let (i, frame) = le_u32(i)?;
+    let (i, weight) = le_f32(i)?;
+
+    Ok((
+        i,
+        VmdMorphKeyframe {
+            morph_name,
+            frame,
+            weight,
+        },
+    ))
+}
+
+fn camera_keyframe(i: Input) -> PResult<VmdCameraKeyframe> {
+    let (i, frame) = le_u32(i)?;
+    let (i, distance) = le_f32(i)?;
+    let (i, target) = vec3(i)?;
+    let (i, rotation) = vec3(i)?;
+    let (i, table) = take(24usize)(i)?;
+    let (i, fov_degrees) = le_u32(i)?;
+    let (i, is_perspective) = le_u8(i)?;
+
+    // unlike bones, each curve's points are stored together.
+    let curve = |n: usize| VmdBezier {
+        x1: table[4 * n],
+        x2: table[4 * n + 1],
+        y1: table[4 * n + 2],
+        y2: table[4 * n + 3],
+    };
+
+    Ok((
+        i,
+        VmdCameraKeyframe {
+            frame,
+            distance,
+            target,
+            rotation,
+            interpolation: std::array::from_fn(curve),
+            fov_degrees,
+            is_perspective: is_perspective == 0,
+        },
+    ))
+}
+
+fn light_keyframe(i: Input) -> PResult<VmdLightKeyframe> {
+    let (i, frame) = le_u32(i)?;
+    let (i, color) = vec3(i)?;
+    let (i, direction) = vec3(i)?;
+
+    Ok((
+        i,
+        VmdLightKeyframe {
+            frame,
+            color,
+            direction,
+        },
+    ))
+}
+
+fn self_shadow_keyframe(i: Input) -> PResult<VmdSelfShadowKeyframe> {
+    let (i, frame) = le_u32(i)?;
+    let (i, mode) = le_u8(i)?;
+    let (i, distance) = le_f32(i)?;
+
+    Ok((
+        i,
+        VmdSelfShadowKeyframe {
+            frame,
+            mode,
+            distance,
+        },
+    ))
+}
+
+fn ik_keyframe(i: Input) -> PResult<VmdIkKeyframe> {
+    let (i, frame) = le_u32(i)?;
+    let (i, is_visible) = le_u8(i)?;
+    let (i, ik_states) = list(i, 21, |i| {
+        let (i, bone_name) = name(i, 20)?;
+        let (i, is_enabled) = le_u8(i)?;
+        Ok((
+            i,
+            VmdIkState {
+                bone_name,
+                is_enabled: is_enabled != 0,
+            },
+        ))
+    })?;
+
+    Ok((
+        i,
+        VmdIkKeyframe {
+            frame,
+            is_visible: is_visible != 0,
+            ik_states,
+        },
+    ))
+}
+
+/// Up to the first NUL. MMD leaves garbage after it.
+fn name(i: Input, size: usize) -> PResult<VmdName> {
+    let (i, field) = take(size)(i)?;
+    let bytes = match field.iter().position(|&b| b == 0) {
+        Some(end) => &field[..end],
+        None => field,
+    };
+
+    Ok((
+        i,
+        VmdName {
+            bytes: bytes.to_vec(),
+            fills_field: bytes.len() == size,
+        },
+    ))
+}
+
+/// A list that may be missing at the end of the file.
+fn optional_list<'a, T>(
+    i: Input<'a>,
+    element_size: usize,
+    element: impl FnMut(Input<'a>) -> PResult<'a, T>,
+) -> PResult<'a, Vec<T>> {
+    if i.is_empty() {
+        return Ok((i, vec![]));
+    }
+
+    list(i, element_size, element)
+}
+
+/// A list prefixed with its length. The count is checked against the
+/// smallest possible element size before allocating anything.
+fn list<'a, T>(
+    i: Input<'a>,
+    min_element_size: usize,
+    element: impl FnMut(Input<'a>) -> PResult<'a, T>,
+) -> PResult<'a, Vec<T>> {
+    let count_input = i;
+    let (i, count) = le_u32(i)?;
+    if (count as usize).saturating_mul(min_element_size) > i.len() {
+        return fail(count_input, VmdErrorKind::InvalidCount(count));
+    }
+
+    nom::multi::count(element, count as usize).parse(i)
+}
+
+fn array<'a, T: Copy + Default, const N: usize>(
+    mut i: Input<'a>,
+    mut element: impl FnMut(Input<'a>) -> PResult<'a, T>,
+) -> PResult<'a, [T; N]> {
+    let mut values = [T::default(); N];
+    for value in &mut values {
+        (i, *value) = element(i)?;
+    }
+
+    Ok((i, values))
+}
+
+fn vec3(i: Input) -> PResult<glam::Vec3> {
+    let (i, values) = array(i, le_f32)?;
+    Ok((i, glam::Vec3::from_array(values)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shift_jis(name: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(name).0.into_owned()
+    }
+
+    /// A name field of `size` bytes, NUL-terminated if there's room and then
+    /// padded with garbage, as MMD does.
+    fn name_field(bytes: &mut Vec<u8>, name: &[u8], size: usize) {
+        let mut field = name[..name.len().min(size)].to_vec();
+        if field.len() < size {
+            field.push(0);
+        }
+        field.resize(size, 0xFD);
+        bytes.extend(field);
+    }
+
+    fn f32s(bytes: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+
+    fn header(model_name: &str) -> Vec<u8> {
+        let mut bytes = b"Vocaloid Motion Data 0002\0\0\0\0\0".to_vec();
+        name_field(&mut bytes, &shift_jis(model_name), 20);
+        bytes
+    }
+
+    #[test]
+    fn parses_bone_and_morph_keyframes() {
+        let mut bytes = header("初音ミク");
+
+        bytes.extend(1u32.to_le_bytes());
+        name_field(&mut bytes, &shift_jis("センター"), 15);
+        bytes.extend(42u32.to_le_bytes());
+        f32s(&mut bytes, &[1.0, 2.0, 3.0]);
+        f32s(&mut bytes, &[0.0, 0.6, 0.0, 0.8]);
+        // x1, y1, x2 and y2 of x, y, z and rotation, then three rows that
+        // aren't read.
+        let mut table: Vec<u8> = (0..16).map(|n| 10 + n).collect();
+        table.resize(64, 0xFF);
+        bytes.extend(table);
+
+        bytes.extend(1u32.to_le_bytes());
+        name_field(&mut bytes, &shift_jis("まばたき"), 15);
+        bytes.extend(7u32.to_le_bytes());
+        f32s(&mut bytes, &[0.5]);
+
+        let motion = parse_vmd(&bytes).unwrap();
+
+        assert_eq!(motion.header.version, VmdVersion::V2);
+        assert!(motion.header.model_name.matches("初音ミク"));
+        assert!(!motion.header.model_name.fills_field);
+
+        let [bone] = &motion.bone_keyframes[..] else {
+            panic!("{:?}", motion.bone_keyframes);
+        };
+        assert!(bone.bone_name.matches("センター"));
+        assert_eq!(bone.bone_name.decode(), "センター");
+        assert_eq!(bone.frame, 42);
+        assert_eq!(bone.translation, glam::vec3(1.0, 2.0, 3.0));
+        assert_eq!(bone.rotation, glam::Quat::from_xyzw(0.0, 0.6, 0.0, 0.8));
+        let curve = |channel: u8| VmdBezier {
+            x1: 10 + channel,
+            y1: 14 + channel,
+            x2: 18 + channel,
+            y2: 22 + channel,
+        };
+        assert_eq!(
+            bone.interpolation,
+            VmdBoneInterpolation {
+                translation_x: curve(0),
+                translation_y: curve(1),
+                translation_z: curve(2),
+                rotation: curve(3),
+            }
+        );
+
+        assert_eq!(
+            motion.morph_keyframes,
+            [VmdMorphKeyframe {
+                morph_name: VmdName {
+                    bytes: shift_jis("まばたき"),
+                    fills_field: false,
+                },
+                frame: 7,
+                weight: 0.5,
+            }]
+        );
+
+        assert!(motion.camera_keyframes.is_empty());
+        assert!(motion.light_keyframes.is_empty());
+        assert!(motion.self_shadow_keyframes.is_empty());
+        assert!(motion.ik_keyframes.is_empty());
+    }
+
+    #[test]
+    fn keeps_names_that_fill_their_field() {
+        let full_name = shift_jis("右足首ＩＫ親の先");
+        assert_eq!(full_name.len(), 16);
+
+        let mut bytes = header("");
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        name_field(&mut bytes, &full_name, 15);
+        bytes.extend(0u32.to_le_bytes());
+        f32s(&mut bytes, &[1.0]);
+
+        let motion = parse_vmd(&bytes).unwrap();
+
+        let name = &motion.morph_keyframes[0].morph_name;
+        assert_eq!(name.bytes, full_name[..15]);
+        assert!(name.fills_field);
+        assert!(name.matches("右足首ＩＫ親の先"));
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        let error = parse_vmd(b"Vocaloid Motion Data 0003\0\0\0\0\0").unwrap_err();
+        assert_eq!(error.section, VmdSection::Header);
+        assert_eq!(error.kind, VmdErrorKind::InvalidMagic);
+
+        let mut bytes = header("");
+        let count_offset = bytes.len();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend([0; 111]);
+        let error = parse_vmd(&bytes).unwrap_err();
+        assert_eq!(
+            error,
+            VmdError {
+                section: VmdSection::BoneKeyframes,
+                offset: count_offset,
+                kind: VmdErrorKind::InvalidCount(2),
+            }
+        );
+    }
+}
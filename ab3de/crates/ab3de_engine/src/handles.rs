@@ -0,0 +1,89 @@
+use std::sync::{Arc, Weak};
+
+/// Identifies a model added to an [`Engine`](crate::Engine). Never reused,
+/// even after the model has been released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModelId(u64);
+
+impl ModelId {
+    pub(crate) fn new(raw: u64) -> Self {
+        Self(raw)
+    }
+}
+
+/// Keeps a model loaded.
+///
+/// Once every clone has been dropped, the model and its GPU resources are
+/// released on the next [`Engine::update`](crate::Engine::update).
+/// [`Engine::remove_model`](crate::Engine::remove_model) releases it right
+/// away instead.
+#[derive(Debug, Clone)]
+pub struct ModelHandle(Arc<ModelId>);
+
+impl ModelHandle {
+    pub(crate) fn new(id: ModelId) -> (Self, Weak<ModelId>) {
+        let inner = Arc::new(id);
+        let liveness = Arc::downgrade(&inner);
+        (Self(inner), liveness)
+    }
+
+    pub(crate) fn from_liveness(liveness: &Weak<ModelId>) -> Option<Self> {
+        liveness.upgrade().map(Self)
+    }
+
+    pub fn id(&self) -> ModelId {
+        *self.0
+    }
+}
+
+impl PartialEq for ModelHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for ModelHandle {}
+
+impl std::hash::Hash for ModelHandle {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+/// Identifies an instance within its model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(u64);
+
+impl InstanceId {
+    pub(crate) fn new(raw: u64) -> Self {
+        Self(raw)
+    }
+}
+
+/// Refers to one instance of a model.
+///
+/// Unlike [`ModelHandle`], this does not keep anything alive: instances live
+/// as long as their model, or until removed. Using the handle afterwards is
+/// an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceHandle {
+    model_id: ModelId,
+    instance_id: InstanceId,
+}
+
+impl InstanceHandle {
+    pub(crate) fn new(model_id: ModelId, instance_id: InstanceId) -> Self {
+        Self {
+            model_id,
+            instance_id,
+        }
+    }
+
+    pub fn model_id(&self) -> ModelId {
+        self.model_id
+    }
+
+    pub fn instance_id(&self) -> InstanceId {
+        self.instance_id
+    }
+}
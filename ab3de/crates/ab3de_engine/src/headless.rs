@@ -0,0 +1,43 @@
+#[derive(Default)]
+pub struct HeadlessDeviceOptions {
+    /// Prefer a software adapter (e.g. lavapipe or WARP). Useful on machines
+    /// without a GPU, such as CI boxes.
+    pub force_fallback_adapter: bool,
+}
+
+/// Requests a device that is not tied to any [`wgpu::Surface`], for use with
+/// [`crate::Engine::render_offscreen`].
+pub async fn request_headless_device(
+    opts: HeadlessDeviceOptions,
+) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: opts.force_fallback_adapter,
+        })
+        .await?;
+
+    log::info!(
+        "[request_headless_device] using adapter: {:?}",
+        adapter.get_info()
+    );
+
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: Some("[request_headless_device]"),
+            required_features: wgpu::Features::empty(),
+            experimental_features: wgpu::ExperimentalFeatures::disabled(),
+            required_limits: wgpu::Limits::defaults(),
+            memory_hints: Default::default(),
+            trace: wgpu::Trace::Off,
+        })
+        .await?;
+
+    Ok((device, queue))
+}
@@ -1,2 +1,3 @@
 pub mod fs_accessors;
+pub mod virtual_fs;
 pub mod window_handling;
@@ -1,7 +1,292 @@
+pub mod dir_fs_accessor;
 pub mod embed_fs_accessor;
+pub mod recording_fs_accessor;
+pub mod zip_fs_accessor;
+
+use std::{collections::HashMap, sync::Arc};
+
+use unicode_normalization::UnicodeNormalization;
 
 pub trait FsAccessor {
     fn name(&self) -> &str;
+    fn contains(&self, filename: &str) -> bool;
     fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>>;
     fn load_string(&self, filename: &str) -> anyhow::Result<String>;
+
+    /// Files that were created, modified or removed since the last call, for
+    /// accessors that watch for changes.
+    fn take_changed_filenames(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+impl<T: FsAccessor + ?Sized> FsAccessor for Box<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        (**self).contains(filename)
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        (**self).load_binary(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        (**self).load_string(filename)
+    }
+
+    fn take_changed_filenames(&self) -> Vec<String> {
+        (**self).take_changed_filenames()
+    }
+}
+
+impl<T: FsAccessor + ?Sized> FsAccessor for &T {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        (**self).contains(filename)
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        (**self).load_binary(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        (**self).load_string(filename)
+    }
+
+    fn take_changed_filenames(&self) -> Vec<String> {
+        (**self).take_changed_filenames()
+    }
+}
+
+impl<T: FsAccessor + ?Sized> FsAccessor for Arc<T> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        (**self).contains(filename)
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        (**self).load_binary(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        (**self).load_string(filename)
+    }
+
+    fn take_changed_filenames(&self) -> Vec<String> {
+        (**self).take_changed_filenames()
+    }
+}
+
+/// The filename of what the file `filename` refers to as `reference`, as
+/// models refer to their textures relative to themselves.
+pub fn relative_to(filename: &str, reference: &str) -> String {
+    match filename.rfind(['/', '\\']) {
+        Some(end) => format!("{}/{}", &filename[..end], reference),
+        None => reference.to_string(),
+    }
+}
+
+/// Splits `filename` at both `/` and `\`, as PMX files use the latter,
+/// resolving `.` and `..` segments. Fails for filenames that would leave the
+/// directory they are relative to.
+pub fn path_segments(filename: &str) -> anyhow::Result<Vec<&str>> {
+    let mut segments = vec![];
+    for segment in filename.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    anyhow::bail!("Path escapes the root");
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Ok(segments)
+}
+
+/// Finds files by the names they are referred to with, which often differ
+/// from the stored ones: PMX files written on Windows get the case wrong and
+/// use `\` separators, and macOS and RustEmbed on the web store decomposed
+/// (NFD) names where references are mostly composed (NFC). Stored names and
+/// references are both folded to lowercase NFC, so either form finds both.
+pub struct PathIndex<T> {
+    /// From folded names to the stored names and values folded into them.
+    entries: HashMap<String, Vec<(String, T)>>,
+}
+
+impl<T> PathIndex<T> {
+    /// If several stored names fold into that of `filename`, the one equal
+    /// to it is preferred, and otherwise the first in sorted order with a
+    /// warning.
+    pub fn get(&self, filename: &str) -> Option<&T> {
+        let candidates = self.entries.get(&path_key(filename)?)?;
+        if let [(_, value)] = candidates.as_slice() {
+            return Some(value);
+        }
+
+        let segments = path_segments(filename).ok()?;
+        if let Some((_, value)) = candidates
+            .iter()
+            .find(|(name, _)| path_segments(name).is_ok_and(|s| s == segments))
+        {
+            return Some(value);
+        }
+
+        let names = candidates
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        log::warn!(
+            "[PathIndex::get] {} is ambiguous, using {} of {:?}",
+            filename,
+            names[0],
+            names
+        );
+        candidates.first().map(|(_, value)| value)
+    }
+}
+
+/// What [`PathIndex`] compares filenames by: their [`path_segments`], in
+/// lowercase NFC. `None` for names that escape the root, which can never be
+/// found.
+pub fn path_key(filename: &str) -> Option<String> {
+    let segments = path_segments(filename).ok()?;
+    Some(segments.join("/").to_lowercase().nfc().collect())
+}
+
+impl<T> FromIterator<(String, T)> for PathIndex<T> {
+    fn from_iter<I: IntoIterator<Item = (String, T)>>(iter: I) -> Self {
+        let mut entries: HashMap<String, Vec<(String, T)>> = HashMap::new();
+        for (name, value) in iter {
+            if let Some(key) = path_key(&name) {
+                entries.entry(key).or_default().push((name, value));
+            }
+        }
+        // directory listings come in no particular order.
+        for candidates in entries.values_mut() {
+            candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        Self { entries }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::collections::HashMap;
+
+    use crate::io::fs_accessors::FsAccessor;
+
+    /// Files held in memory, for testing loaders without fixtures on disk.
+    pub struct MemoryFsAccessor {
+        files: HashMap<String, Vec<u8>>,
+    }
+
+    impl MemoryFsAccessor {
+        pub fn new<'a>(files: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Self {
+            Self {
+                files: files
+                    .into_iter()
+                    .map(|(filename, data)| (filename.to_string(), data.to_vec()))
+                    .collect(),
+            }
+        }
+    }
+
+    impl FsAccessor for MemoryFsAccessor {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        fn contains(&self, filename: &str) -> bool {
+            self.files.contains_key(filename)
+        }
+
+        fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+            self.files
+                .get(filename)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Resource not found: #memory/{}", filename))
+        }
+
+        fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+            Ok(String::from_utf8(self.load_binary(filename)?)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index<'a>(names: impl IntoIterator<Item = &'a str>) -> PathIndex<&'a str> {
+        names
+            .into_iter()
+            .map(|name| (name.to_string(), name))
+            .collect()
+    }
+
+    #[test]
+    fn finds_dakuten_and_handakuten_in_either_normal_form() {
+        // が and ぱ, composed and decomposed.
+        let nfc = "\u{304C}/\u{3071}.png";
+        let nfd = "\u{304B}\u{3099}/\u{306F}\u{309A}.png";
+
+        assert_eq!(index([nfc]).get(nfd), Some(&nfc));
+        assert_eq!(index([nfd]).get(nfc), Some(&nfd));
+        assert_eq!(index([nfd]).get(nfd), Some(&nfd));
+        // か and は are different names.
+        assert_eq!(index([nfc]).get("\u{304B}/\u{306F}.png"), None);
+    }
+
+    #[test]
+    fn folds_case_and_separators() {
+        let index = index(["Textures/Face.PNG"]);
+        assert_eq!(index.get("textures/face.png"), Some(&"Textures/Face.PNG"));
+        assert_eq!(index.get("TEXTURES\\face.png"), Some(&"Textures/Face.PNG"));
+        assert_eq!(
+            index.get("./textures//sub/../face.png"),
+            Some(&"Textures/Face.PNG")
+        );
+        assert_eq!(index.get("textures/body.png"), None);
+        assert_eq!(index.get("../textures/face.png"), None);
+    }
+
+    #[test]
+    fn prefers_the_exact_name_when_ambiguous() {
+        let nfc = "\u{304C}.png";
+        let nfd = "\u{304B}\u{3099}.png";
+        let mut names = ["face.png", "Face.png", "FACE.png", nfc, nfd];
+        for _ in 0..2 {
+            names.reverse();
+            let index = index(names);
+            assert_eq!(index.get("Face.png"), Some(&"Face.png"));
+            assert_eq!(index.get("FACE.png"), Some(&"FACE.png"));
+            assert_eq!(index.get(nfd), Some(&nfd));
+            assert_eq!(index.get(nfc), Some(&nfc));
+            // neither is equal, so the first in sorted order is used
+            // regardless of the order they were listed in.
+            assert_eq!(index.get("fACE.png"), Some(&"FACE.png"));
+        }
+    }
+
+    #[test]
+    fn path_segments_stay_inside_the_root() {
+        assert_eq!(path_segments("a\\b/../c").unwrap(), ["a", "c"]);
+        assert!(path_segments("a/../../b").is_err());
+        assert_eq!(
+            relative_to("model\\miku.pmx", "tex/a.png"),
+            "model/tex/a.png"
+        );
+        assert_eq!(relative_to("miku.pmx", "a.png"), "a.png");
+    }
 }
@@ -0,0 +1,274 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::io::fs_accessors::{FsAccessor, PathIndex, path_segments};
+
+/// Reads files from a directory on disk. Filenames are relative to the root,
+/// and may use either `/` or `\` as separators, as PMX files do.
+pub struct DirFsAccessor {
+    name: String,
+    root: PathBuf,
+    /// Set by [`Self::watch`].
+    #[cfg(not(target_arch = "wasm32"))]
+    watcher: Option<DirWatcher>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct DirWatcher {
+    /// Stops watching when dropped.
+    _watcher: notify::RecommendedWatcher,
+    /// The root as the paths of events start with.
+    canonical_root: PathBuf,
+    events: std::sync::Mutex<std::sync::mpsc::Receiver<notify::Result<notify::Event>>>,
+}
+
+impl DirFsAccessor {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            name: root.display().to_string(),
+            root,
+            #[cfg(not(target_arch = "wasm32"))]
+            watcher: None,
+        }
+    }
+
+    /// Starts reporting changes to anything under the root through
+    /// [`FsAccessor::take_changed_filenames`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch(&mut self) -> anyhow::Result<()> {
+        use notify::Watcher;
+
+        let canonical_root = self
+            .root
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("Unable to watch {}: {}", self.name, e))?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&canonical_root, notify::RecursiveMode::Recursive)?;
+        self.watcher = Some(DirWatcher {
+            _watcher: watcher,
+            canonical_root,
+            events: std::sync::Mutex::new(receiver),
+        });
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn watch(&mut self) -> anyhow::Result<()> {
+        anyhow::bail!("Unable to watch {}: not supported on the web", self.name)
+    }
+
+    /// Joins `filename` onto the root, resolving `..` segments lexically, see
+    /// [`path_segments`]. Symbolic links are followed, but fail if they lead
+    /// out of the root.
+    pub fn resolve(&self, filename: &str) -> anyhow::Result<PathBuf> {
+        let mut path = self.root.clone();
+        path.extend(self.segments(filename)?);
+        self.confine(filename, path)
+    }
+
+    /// Like [`Self::resolve`], but segments that don't exist as they are are
+    /// looked up in a [`PathIndex`] of their directory. Returns the resolved
+    /// path if that finds nothing either.
+    fn find(&self, filename: &str) -> anyhow::Result<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in self.segments(filename)? {
+            let exact_path = path.join(segment);
+            if exact_path.exists() {
+                path = exact_path;
+                continue;
+            }
+
+            let index: PathIndex<PathBuf> = std::fs::read_dir(&path)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|entry| Some((entry.file_name().into_string().ok()?, entry.path())))
+                .collect();
+            match index.get(segment) {
+                Some(found_path) => path = found_path.clone(),
+                None => return self.resolve(filename),
+            }
+        }
+        self.confine(filename, path)
+    }
+
+    /// Fails if `path`, once its symbolic links are followed, is outside the
+    /// root. Trailing segments that don't exist yet can't be links, so only
+    /// the rest is checked.
+    fn confine(&self, filename: &str, path: PathBuf) -> anyhow::Result<PathBuf> {
+        let canonical_root = self
+            .root
+            .canonicalize()
+            .map_err(|e| anyhow::anyhow!("Unable to read #{}/{}: {}", self.name, filename, e))?;
+
+        let mut existing = path.as_path();
+        let mut missing = vec![];
+        let mut canonical_path = loop {
+            if let Ok(canonical_path) = existing.canonicalize() {
+                break canonical_path;
+            }
+            let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                break existing.to_path_buf();
+            };
+            missing.push(name);
+            existing = parent;
+        };
+        canonical_path.extend(missing.into_iter().rev());
+
+        if !canonical_path.starts_with(&canonical_root) {
+            anyhow::bail!(
+                "#{}/{} resolves to {}, outside of {}",
+                self.name,
+                filename,
+                canonical_path.display(),
+                canonical_root.display()
+            );
+        }
+        Ok(path)
+    }
+
+    fn segments<'a>(&self, filename: &'a str) -> anyhow::Result<Vec<&'a str>> {
+        let segments = path_segments(filename)
+            .map_err(|e| anyhow::anyhow!("{} (#{}/{})", e, self.name, filename))?;
+        // rejects drive prefixes such as `C:`, which would replace the root
+        // when joined onto it.
+        for segment in &segments {
+            let mut components = Path::new(segment).components();
+            if !matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            ) {
+                anyhow::bail!("Invalid path: #{}/{}", self.name, filename);
+            }
+        }
+        Ok(segments)
+    }
+
+    fn read(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        let path = self.find(filename)?;
+        std::fs::read(&path).map_err(|e| {
+            anyhow::anyhow!(
+                "Unable to read #{}/{} at {}: {}",
+                self.name,
+                filename,
+                path.display(),
+                e
+            )
+        })
+    }
+}
+
+impl FsAccessor for DirFsAccessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        self.find(filename).is_ok_and(|path| path.is_file())
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        self.read(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        let data = self.read(filename)?;
+        Ok(String::from_utf8(data)?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn take_changed_filenames(&self) -> Vec<String> {
+        let Some(watcher) = &self.watcher else {
+            return vec![];
+        };
+
+        let mut filenames: Vec<String> = vec![];
+        for event in watcher.events.lock().unwrap().try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!(
+                        "[DirFsAccessor::take_changed_filenames] {} ({})",
+                        e,
+                        self.name
+                    );
+                    continue;
+                }
+            };
+            if matches!(event.kind, notify::EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths {
+                let Ok(relative_path) = path.strip_prefix(&watcher.canonical_root) else {
+                    continue;
+                };
+                let filename = relative_path
+                    .components()
+                    .filter_map(|component| component.as_os_str().to_str())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !filenames.contains(&filename) {
+                    filenames.push(filename);
+                }
+            }
+        }
+        filenames
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system's temporary one, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("ab3de-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn symlinks_must_stay_inside_the_root() {
+        let dir = TempDir::new("symlinks");
+        let root = dir.0.join("root");
+        std::fs::create_dir_all(root.join("textures")).unwrap();
+        std::fs::write(root.join("textures/face.png"), b"inside").unwrap();
+        std::fs::write(dir.0.join("secret.txt"), b"outside").unwrap();
+        std::os::unix::fs::symlink(root.join("textures"), root.join("tex")).unwrap();
+        std::os::unix::fs::symlink(&dir.0, root.join("up")).unwrap();
+        std::os::unix::fs::symlink(dir.0.join("secret.txt"), root.join("secret.txt")).unwrap();
+
+        let accessor = DirFsAccessor::new(&root);
+        assert_eq!(accessor.load_binary("tex/face.png").unwrap(), b"inside");
+        assert_eq!(accessor.load_binary("TEX\\Face.png").unwrap(), b"inside");
+        assert!(accessor.resolve("textures/new.png").is_ok());
+
+        for filename in [
+            "up/secret.txt",
+            "secret.txt",
+            "UP/Secret.txt",
+            "up/missing.txt",
+        ] {
+            assert!(!accessor.contains(filename), "{}", filename);
+            let error = accessor.load_binary(filename).unwrap_err().to_string();
+            assert!(error.contains("outside of"), "{}", error);
+            assert!(
+                error.contains(&root.canonicalize().unwrap().display().to_string()),
+                "{}",
+                error
+            );
+        }
+    }
+}
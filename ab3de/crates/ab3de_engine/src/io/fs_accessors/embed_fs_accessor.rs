@@ -1,7 +1,12 @@
-use crate::io::fs_accessors::FsAccessor;
+use std::borrow::Cow;
+
+use crate::io::fs_accessors::{FsAccessor, PathIndex};
 
 pub struct EmbedFsAccessor<T: rust_embed::RustEmbed> {
     name: &'static str,
+    /// See [`PathIndex`]. On the web build, RustEmbed keeps names in the
+    /// normalization form they had on the machine that built it.
+    index: PathIndex<Cow<'static, str>>,
     _marker: std::marker::PhantomData<T>,
 }
 
@@ -9,15 +14,17 @@ impl<T: rust_embed::RustEmbed> EmbedFsAccessor<T> {
     pub fn new(name: &'static str) -> Self {
         Self {
             name,
+            index: T::iter().map(|path| (path.to_string(), path)).collect(),
             _marker: std::marker::PhantomData,
         }
     }
-}
 
-/// TODO: normalize all dakuten & handakuten. (Otherwise RustEmbed won't find
-/// files in some cases on the web build.)
-fn normalize_filename(filename: &str) -> String {
-    filename.replace("\u{30d4}", "\u{30d2}\u{309a}") // ???
+    fn get(&self, filename: &str) -> anyhow::Result<rust_embed::EmbeddedFile> {
+        self.index
+            .get(filename)
+            .and_then(|path| T::get(path))
+            .ok_or_else(|| anyhow::anyhow!("Resource not found: #{}/{}", self.name, filename))
+    }
 }
 
 impl<T: rust_embed::RustEmbed> FsAccessor for EmbedFsAccessor<T> {
@@ -25,15 +32,17 @@ impl<T: rust_embed::RustEmbed> FsAccessor for EmbedFsAccessor<T> {
         self.name
     }
 
+    fn contains(&self, filename: &str) -> bool {
+        self.index.get(filename).is_some()
+    }
+
     fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
-        let file = T::get(&normalize_filename(filename))
-            .ok_or_else(|| anyhow::anyhow!("Resource not found: #{}/{}", self.name, filename))?;
+        let file = self.get(filename)?;
         Ok(file.data.into_owned())
     }
 
     fn load_string(&self, filename: &str) -> anyhow::Result<String> {
-        let file = T::get(&normalize_filename(filename))
-            .ok_or_else(|| anyhow::anyhow!("Resource not found: #{}/{}", self.name, filename))?;
+        let file = self.get(filename)?;
         let s = std::str::from_utf8(&file.data)?;
         Ok(s.to_string())
     }
@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use crate::io::fs_accessors::FsAccessor;
+
+/// Remembers every filename read through it, including ones that failed to
+/// load, to find out which files something loaded from them depends on.
+pub struct RecordingFsAccessor<T: FsAccessor> {
+    inner: T,
+    filenames: Mutex<Vec<String>>,
+}
+
+impl<T: FsAccessor> RecordingFsAccessor<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            filenames: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn into_filenames(self) -> Vec<String> {
+        self.filenames.into_inner().unwrap()
+    }
+
+    fn record(&self, filename: &str) {
+        self.filenames.lock().unwrap().push(filename.to_string());
+    }
+}
+
+impl<T: FsAccessor> FsAccessor for RecordingFsAccessor<T> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        self.inner.contains(filename)
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        self.record(filename);
+        self.inner.load_binary(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        self.record(filename);
+        self.inner.load_string(filename)
+    }
+
+    fn take_changed_filenames(&self) -> Vec<String> {
+        self.inner.take_changed_filenames()
+    }
+}
@@ -0,0 +1,297 @@
+use std::{io::Read, path::Path, sync::Mutex};
+
+use crate::{
+    io::fs_accessors::{FsAccessor, PathIndex},
+    scene::ModelLoaderKind,
+};
+
+/// Reads files from inside a `.zip` archive, which is held in memory.
+pub struct ZipFsAccessor {
+    name: String,
+    archive: Mutex<zip::ZipArchive<std::io::Cursor<Vec<u8>>>>,
+    /// Decoded names of the files, without directories, in archive order.
+    filenames: Vec<String>,
+    /// From the decoded names to archive indices.
+    indices: PathIndex<usize>,
+}
+
+impl ZipFsAccessor {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", path.display(), e))?;
+        Self::from_bytes(path.display().to_string(), bytes)
+    }
+
+    /// For archives that did not come from the filesystem, as on the web.
+    pub fn from_bytes(name: impl Into<String>, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let name = name.into();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))
+            .map_err(|e| anyhow::anyhow!("{} ({})", e, name))?;
+
+        let mut names = Vec::with_capacity(archive.len());
+        for index in 0..archive.len() {
+            let file = archive.by_index_raw(index)?;
+            if !file.is_dir() {
+                names.push((decode_name(&file), index));
+            }
+        }
+        let filenames = names.iter().map(|(filename, _)| filename.clone()).collect();
+        let indices = names.into_iter().collect();
+
+        Ok(Self {
+            name,
+            archive: Mutex::new(archive),
+            filenames,
+            indices,
+        })
+    }
+
+    /// Every file in the archive, as the names it was stored under.
+    pub fn filenames(&self) -> impl Iterator<Item = &str> {
+        self.filenames.iter().map(String::as_str)
+    }
+
+    /// The files one of the model loaders can read, and which one.
+    pub fn model_filenames(&self) -> impl Iterator<Item = (&str, ModelLoaderKind)> {
+        self.filenames().filter_map(|filename| {
+            ModelLoaderKind::from_path(Path::new(filename)).map(|loader| (filename, loader))
+        })
+    }
+
+    fn read(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        let index = self
+            .indices
+            .get(filename)
+            .ok_or_else(|| anyhow::anyhow!("Resource not found: #{}/{}", self.name, filename))?;
+
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive.by_index(*index)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .map_err(|e| anyhow::anyhow!("{} (#{}/{})", e, self.name, filename))?;
+        Ok(data)
+    }
+}
+
+/// Entries whose names are UTF-8 should say so with their own flag (bit 11
+/// of the general purpose flags) or a Unicode path field. Other names are
+/// taken to be Shift-JIS, which MMD models made on Japanese Windows use,
+/// unless they only make sense as UTF-8, as some tools leave the flag unset.
+fn decode_name(file: &zip::read::ZipFile<'_>) -> String {
+    // `zip` decodes names without either as CP437 instead, which only
+    // agrees with UTF-8 for ASCII, which Shift-JIS leaves alone too.
+    let utf8_name = String::from_utf8_lossy(file.name_raw());
+    if file.name() == utf8_name {
+        return utf8_name.into_owned();
+    }
+    let (name, had_errors) = encoding_rs::SHIFT_JIS.decode_without_bom_handling(file.name_raw());
+    if had_errors && let Ok(name) = std::str::from_utf8(file.name_raw()) {
+        return name.to_string();
+    }
+    name.into_owned()
+}
+
+impl FsAccessor for ZipFsAccessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn contains(&self, filename: &str) -> bool {
+        self.indices.get(filename).is_some()
+    }
+
+    fn load_binary(&self, filename: &str) -> anyhow::Result<Vec<u8>> {
+        self.read(filename)
+    }
+
+    fn load_string(&self, filename: &str) -> anyhow::Result<String> {
+        let data = self.read(filename)?;
+        Ok(String::from_utf8(data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flags of entries whose names are UTF-8.
+    const UTF8: u16 = 1 << 11;
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0_u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    /// An archive of uncompressed entries with names stored exactly as
+    /// given, which `zip::ZipWriter` doesn't allow for.
+    fn archive(entries: &[(&[u8], u16, &[u8])]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut central_directory = vec![];
+        for &(raw_name, flags, data) in entries {
+            let offset = bytes.len() as u32;
+            let header = |signature: u32, central: bool| {
+                let mut header = signature.to_le_bytes().to_vec();
+                if central {
+                    header.extend(20_u16.to_le_bytes());
+                }
+                header.extend(20_u16.to_le_bytes());
+                header.extend(flags.to_le_bytes());
+                header.extend([0; 2]); // stored
+                header.extend([0, 0, 0x21, 0]); // 1980-01-01
+                header.extend(crc32(data).to_le_bytes());
+                header.extend((data.len() as u32).to_le_bytes());
+                header.extend((data.len() as u32).to_le_bytes());
+                header.extend((raw_name.len() as u16).to_le_bytes());
+                header.extend([0; 2]); // extra field
+                if central {
+                    header.extend([0; 6]); // comment, disk, internal attributes
+                    header.extend([0; 4]); // external attributes
+                    header.extend(offset.to_le_bytes());
+                }
+                header.extend(raw_name);
+                header
+            };
+            bytes.extend(header(0x0403_4b50, false));
+            bytes.extend(data);
+            central_directory.extend(header(0x0201_4b50, true));
+        }
+
+        let central_directory_offset = bytes.len() as u32;
+        bytes.extend(&central_directory);
+        bytes.extend(0x0605_4b50_u32.to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((entries.len() as u16).to_le_bytes());
+        bytes.extend((central_directory.len() as u32).to_le_bytes());
+        bytes.extend(central_directory_offset.to_le_bytes());
+        bytes.extend([0; 2]);
+        bytes
+    }
+
+    fn shift_jis(name: &str) -> Vec<u8> {
+        encoding_rs::SHIFT_JIS.encode(name).0.into_owned()
+    }
+
+    #[test]
+    fn decodes_each_name_by_its_own_flag() {
+        let model = shift_jis("初音ミク/初音ミク.pmx");
+        let texture = shift_jis("初音ミク/テクスチャ/顔.png");
+        let motion = "モーション/ダンス.vmd".as_bytes();
+        let accessor = ZipFsAccessor::from_bytes(
+            "mixed.zip",
+            archive(&[
+                (&model, 0, b"model"),
+                (&texture, 0, b"texture"),
+                (motion, UTF8, b"motion"),
+                (b"readme.txt", 0, b"readme"),
+                (b"Shift-JIS/", 0, b""),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            accessor.filenames().collect::<Vec<_>>(),
+            [
+                "初音ミク/初音ミク.pmx",
+                "初音ミク/テクスチャ/顔.png",
+                "モーション/ダンス.vmd",
+                "readme.txt"
+            ]
+        );
+        assert_eq!(
+            accessor.load_binary("初音ミク/初音ミク.pmx").unwrap(),
+            b"model"
+        );
+        assert_eq!(
+            accessor
+                .load_binary("初音ミク\\テクスチャ\\顔.png")
+                .unwrap(),
+            b"texture"
+        );
+        assert_eq!(
+            accessor.load_binary("モーション/ダンス.vmd").unwrap(),
+            b"motion"
+        );
+        assert_eq!(accessor.load_string("README.TXT").unwrap(), "readme");
+        assert!(!accessor.contains("Shift-JIS"));
+        assert!(accessor.load_binary("missing.pmx").is_err());
+    }
+
+    #[test]
+    fn trusts_the_flag_over_valid_shift_jis() {
+        // `ダ` is 0xE3 0x83 0x80 in UTF-8, which is valid Shift-JIS too.
+        let accessor = ZipFsAccessor::from_bytes(
+            "utf8.zip",
+            archive(&[("ダンス.vmd".as_bytes(), UTF8, b"motion")]),
+        )
+        .unwrap();
+        assert_eq!(accessor.filenames().collect::<Vec<_>>(), ["ダンス.vmd"]);
+    }
+
+    #[test]
+    fn falls_back_to_utf8_for_unflagged_names_that_arent_shift_jis() {
+        // `顔` is 0xE9 0xA1 0x94 in UTF-8, and 0x94 can't follow 0xA1 in
+        // Shift-JIS.
+        let accessor = ZipFsAccessor::from_bytes(
+            "unflagged.zip",
+            archive(&[
+                ("テクスチャ/顔.png".as_bytes(), 0, b"texture"),
+                (&shift_jis("初音ミク.pmx"), 0, b"model"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            accessor.filenames().collect::<Vec<_>>(),
+            ["テクスチャ/顔.png", "初音ミク.pmx"]
+        );
+        assert_eq!(
+            accessor.load_binary("テクスチャ/顔.png").unwrap(),
+            b"texture"
+        );
+    }
+
+    #[test]
+    fn lists_model_files_in_archive_order() {
+        let accessor = ZipFsAccessor::from_bytes(
+            "models.zip",
+            archive(&[
+                (&shift_jis("ミク/ミク.PMX"), 0, b""),
+                (b"ReadMe.txt", 0, b""),
+                (b"box/box.glb", UTF8, b""),
+                (b"motion.vmd", 0, b""),
+                (b"cube.obj", 0, b""),
+                (b"cube.mtl", 0, b""),
+                (b"scene/scene.gltf", 0, b""),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            accessor.model_filenames().collect::<Vec<_>>(),
+            [
+                ("ミク/ミク.PMX", ModelLoaderKind::Pmx),
+                ("box/box.glb", ModelLoaderKind::Gltf),
+                ("cube.obj", ModelLoaderKind::Obj),
+                ("scene/scene.gltf", ModelLoaderKind::Gltf),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_what_isnt_an_archive() {
+        let error = ZipFsAccessor::from_bytes("broken.zip", b"PK not really".to_vec())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("broken.zip"), "{}", error);
+    }
+}
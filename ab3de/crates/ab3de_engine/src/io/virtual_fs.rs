@@ -0,0 +1,164 @@
+use crate::io::fs_accessors::FsAccessor;
+
+/// [`FsAccessor`]s mounted under prefixes of URIs, such as `builtin://sky/`
+/// or `models/aoi/`. The rest of a URI after a mount's prefix is a filename
+/// in that mount. Where several mounts have a file, the one mounted last
+/// wins, so that e.g. a user directory can shadow built-in resources.
+#[derive(Default)]
+pub struct VirtualFs {
+    /// In the order they were mounted.
+    mounts: Vec<Mount>,
+}
+
+struct Mount {
+    /// Empty, or ending with `/`.
+    prefix: String,
+    accessor: Box<dyn FsAccessor>,
+}
+
+impl VirtualFs {
+    /// A `/` is added to prefixes that don't end with one, so that mounts
+    /// only ever match whole path segments. An empty prefix matches every
+    /// URI.
+    pub fn mount(&mut self, prefix: impl Into<String>, accessor: impl FsAccessor + 'static) {
+        let mut prefix = prefix.into();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        self.mounts.push(Mount {
+            prefix,
+            accessor: Box::new(accessor),
+        });
+    }
+
+    /// The mount that has `uri`, and the filename in it. If no mount has it,
+    /// the last one whose prefix matches is returned anyway, so that reading
+    /// from it fails with an error naming the file.
+    pub fn resolve<'a>(&'a self, uri: &'a str) -> anyhow::Result<(&'a dyn FsAccessor, &'a str)> {
+        let mut candidates = self.mounts.iter().rev().filter_map(|mount| {
+            let filename = uri.strip_prefix(mount.prefix.as_str())?;
+            Some((mount.accessor.as_ref(), filename))
+        });
+        let last_mounted = candidates
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Nothing is mounted at {}", uri))?;
+        if last_mounted.0.contains(last_mounted.1) {
+            return Ok(last_mounted);
+        }
+        Ok(candidates
+            .find(|(accessor, filename)| accessor.contains(filename))
+            .unwrap_or(last_mounted))
+    }
+}
+
+impl FsAccessor for VirtualFs {
+    fn name(&self) -> &str {
+        "vfs"
+    }
+
+    fn contains(&self, uri: &str) -> bool {
+        self.resolve(uri)
+            .is_ok_and(|(accessor, filename)| accessor.contains(filename))
+    }
+
+    fn load_binary(&self, uri: &str) -> anyhow::Result<Vec<u8>> {
+        let (accessor, filename) = self.resolve(uri)?;
+        accessor.load_binary(filename)
+    }
+
+    fn load_string(&self, uri: &str) -> anyhow::Result<String> {
+        let (accessor, filename) = self.resolve(uri)?;
+        accessor.load_string(filename)
+    }
+
+    /// As URIs, including those of files shadowed by other mounts.
+    fn take_changed_filenames(&self) -> Vec<String> {
+        self.mounts
+            .iter()
+            .flat_map(|mount| {
+                mount
+                    .accessor
+                    .take_changed_filenames()
+                    .into_iter()
+                    .map(|filename| format!("{}{}", mount.prefix, filename))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fs_accessors::testing::MemoryFsAccessor;
+
+    fn load(vfs: &VirtualFs, uri: &str) -> String {
+        vfs.load_string(uri).unwrap()
+    }
+
+    #[test]
+    fn the_last_mount_wins() {
+        let mut vfs = VirtualFs::default();
+        vfs.mount(
+            "builtin://sky/",
+            MemoryFsAccessor::new([("sky.hdr", b"built-in".as_slice()), ("sun.hdr", b"sun")]),
+        );
+        vfs.mount(
+            "builtin://sky",
+            MemoryFsAccessor::new([("sky.hdr", b"user".as_slice())]),
+        );
+
+        assert_eq!(load(&vfs, "builtin://sky/sky.hdr"), "user");
+        // falls back to earlier mounts that have the file.
+        assert_eq!(load(&vfs, "builtin://sky/sun.hdr"), "sun");
+        assert!(vfs.contains("builtin://sky/sun.hdr"));
+        assert!(!vfs.contains("builtin://sky/moon.hdr"));
+    }
+
+    #[test]
+    fn prefixes_match_whole_segments() {
+        let mut vfs = VirtualFs::default();
+        vfs.mount(
+            "",
+            MemoryFsAccessor::new([("models/aoix/aoi.pmx", b"root".as_slice())]),
+        );
+        vfs.mount(
+            "models/aoi",
+            MemoryFsAccessor::new([("aoi.pmx", b"aoi".as_slice()), ("x/aoi.pmx", b"aoi/x")]),
+        );
+
+        assert_eq!(load(&vfs, "models/aoi/aoi.pmx"), "aoi");
+        assert_eq!(load(&vfs, "models/aoi/x/aoi.pmx"), "aoi/x");
+        assert_eq!(load(&vfs, "models/aoix/aoi.pmx"), "root");
+
+        let (_, filename) = vfs.resolve("models/aoix/aoi.pmx").unwrap();
+        assert_eq!(filename, "models/aoix/aoi.pmx");
+    }
+
+    #[test]
+    fn missing_files_are_looked_for_in_the_last_matching_mount() {
+        let mut vfs = VirtualFs::default();
+        vfs.mount("a/", MemoryFsAccessor::new([("x", b"x".as_slice())]));
+        vfs.mount("a/b/", MemoryFsAccessor::new([("y", b"y".as_slice())]));
+
+        let (_, filename) = vfs.resolve("a/b/z").unwrap();
+        assert_eq!(filename, "z");
+        let error = vfs.load_binary("a/b/z").unwrap_err();
+        assert_eq!(error.to_string(), "Resource not found: #memory/z");
+    }
+
+    #[test]
+    fn nothing_mounted_is_an_error() {
+        let vfs = VirtualFs::default();
+        let error = vfs.resolve("builtin://sky/sky.hdr").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Nothing is mounted at builtin://sky/sky.hdr"
+        );
+
+        let mut vfs = VirtualFs::default();
+        vfs.mount("models/", MemoryFsAccessor::new([]));
+        assert!(vfs.resolve("textures/a.png").is_err());
+        assert!(!vfs.contains("textures/a.png"));
+        assert!(vfs.load_binary("textures/a.png").is_err());
+    }
+}
@@ -90,7 +90,8 @@ impl WeblikeManualWindowHandler {
         });
 
         self.ctx.request_redraw();
-        self.engine.update(self.update_time_ms, dt_s);
+        self.engine.timeline_mut().advance(dt_s);
+        self.engine.update();
         match self.engine.render(&self.viewport) {
             Ok(_) => {}
             Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -1,15 +1,50 @@
 #![feature(cfg_select)]
 #![feature(decl_macro)]
 
+mod animation;
+mod bounds;
 mod drawing;
 mod embedded_demo_resources;
 mod engine;
+mod formats;
+mod handles;
+mod headless;
 mod io;
 mod model_loaders;
+mod scene;
+mod timeline;
 mod utils;
 
+pub use animation::{motion::Motion, skeleton::BoneTransform};
+pub use bounds::{Aabb, BoundingSphere, Frustum};
 pub use drawing::systems::camera_system::CameraData;
-pub use engine::{Engine, Viewport, ViewportConfiguration};
+pub use drawing::systems::model_system::{FrameStats, InstanceTransform};
+pub use drawing::systems::offscreen_system::OffscreenPixelFormat;
+pub use engine::{
+    Engine, ModelInfo, OffscreenPixels, OffscreenViewport, OffscreenViewportConfiguration,
+    Viewport, ViewportConfiguration,
+};
+pub use formats::{pmx, vmd};
+pub use handles::{InstanceHandle, InstanceId, ModelHandle, ModelId};
+pub use headless::{HeadlessDeviceOptions, request_headless_device};
+pub use io::fs_accessors::{
+    FsAccessor, dir_fs_accessor::DirFsAccessor, zip_fs_accessor::ZipFsAccessor,
+};
+pub use io::virtual_fs::VirtualFs;
+pub use model_loaders::model_data::{
+    BlendMode, BoneData, BoneMorphOffsetData, EdgeData, GroupMorphOffsetData, IkData, IkLimitsData,
+    IkLinkData, ImageData, InheritData, JointData, MaterialData, MaterialMorphOffsetData,
+    MaterialMorphOperation, MaterialShading, MeshData, ModelData, MorphData, MorphKind,
+    RigidBodyData, RigidBodyMode, RigidBodyShape, SdefData, SphereMapData, SphereMapMode,
+    TextureData, UvMorphOffsetData, VertexData, VertexMorphOffsetData, VertexSkinData,
+};
+pub use model_loaders::source_space::{Handedness, SourceSpace, UpAxis};
+pub use scene::{
+    CameraDescription, EnvironmentDescription, InstancesDescription, LightDescription,
+    ModelDescription, ModelLoaderKind, MountDescription, OpenedZip, SCENE_FORMAT_VERSION,
+    SceneDescription, TransformDescription,
+};
+pub use timeline::{FrameTime, Timeline};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -1,41 +1,33 @@
-use crate::drawing::models::Model;
+use crate::model_loaders::{model_data::ModelData, source_space::SourceSpace};
 
+pub mod gltf_loader;
+pub mod model_data;
 pub mod obj_loader;
 pub mod pmx_loader;
+pub mod source_space;
 pub mod virtual_loader;
 
-/// ## TODO
-///
-/// - Decouple from [`crate::drawing`]. The returned [`Model`] should be
-///   general, should be (de)serializable, which means it should not depend on
-///   creating things with [`wgpu::Device`].
+/// Reads and converts a model without touching the GPU. The result is
+/// turned into something drawable with [`crate::drawing::models::Model::upload`].
 pub trait ModelLoader {
-    fn load_model(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        layout: &wgpu::BindGroupLayout,
-    ) -> anyhow::Result<Model>;
-}
-
-pub(self) mod utils {
-    use crate::drawing::{models::ModelVertex, textures};
+    /// What the format's coordinates mean.
+    fn source_space(&self) -> SourceSpace;
 
-    pub fn new_flat_normal_texture(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        width: u32,
-        height: u32,
-    ) -> textures::D2NormalTexture {
-        let normal_data = vec![128u8, 128u8, 255u8].repeat((width * height) as usize);
-        let image = image::RgbImage::from_vec(width, height, normal_data).unwrap();
-        let image = image::DynamicImage::ImageRgb8(image);
+    /// As stored in the file, in [`Self::source_space`].
+    fn load_source_model_data(&self, filename: &str) -> anyhow::Result<ModelData>;
 
-        textures::D2NormalTexture::from_image("memory:flat-normal-texture", device, queue, &image)
+    /// In engine space, see [`SourceSpace`].
+    fn load_model_data(&self, filename: &str) -> anyhow::Result<ModelData> {
+        let mut model_data = self.load_source_model_data(filename)?;
+        model_data.convert_to_engine_space(&self.source_space());
+        Ok(model_data)
     }
+}
 
-    pub fn calculate_tangent_and_bitangent(vertices: &mut [ModelVertex], indices: &[u32]) {
+pub(self) mod utils {
+    use crate::model_loaders::model_data::VertexData;
+
+    pub fn calculate_tangent_and_bitangent(vertices: &mut [VertexData], indices: &[u32]) {
         let mut triangles_included = vec![0u32; vertices.len()];
 
         for c in indices.chunks(3) {
@@ -45,13 +37,8 @@ pub(self) mod utils {
             let v1 = &vertices[c1];
             let v2 = &vertices[c2];
 
-            let pos0: glam::Vec3 = v0.position.into();
-            let pos1: glam::Vec3 = v1.position.into();
-            let pos2: glam::Vec3 = v2.position.into();
-
-            let uv0: glam::Vec2 = v0.tex_coords.into();
-            let uv1: glam::Vec2 = v1.tex_coords.into();
-            let uv2: glam::Vec2 = v2.tex_coords.into();
+            let (pos0, pos1, pos2) = (v0.position, v1.position, v2.position);
+            let (uv0, uv1, uv2) = (v0.tex_coords, v1.tex_coords, v2.tex_coords);
 
             let delta_pos1 = pos1 - pos0;
             let delta_pos2 = pos2 - pos0;
@@ -63,12 +50,10 @@ pub(self) mod utils {
             let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
             let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
 
-            vertices[c0].tangent = (tangent + glam::Vec3::from(vertices[c0].tangent)).into();
-            vertices[c0].bitangent = (bitangent + glam::Vec3::from(vertices[c0].bitangent)).into();
-            vertices[c1].tangent = (tangent + glam::Vec3::from(vertices[c1].tangent)).into();
-            vertices[c1].bitangent = (bitangent + glam::Vec3::from(vertices[c1].bitangent)).into();
-            vertices[c2].tangent = (tangent + glam::Vec3::from(vertices[c2].tangent)).into();
-            vertices[c2].bitangent = (bitangent + glam::Vec3::from(vertices[c2].bitangent)).into();
+            for i in [c0, c1, c2] {
+                vertices[i].tangent += tangent;
+                vertices[i].bitangent += bitangent;
+            }
 
             triangles_included[c0] += 1;
             triangles_included[c1] += 1;
@@ -78,8 +63,8 @@ pub(self) mod utils {
         for (i, n) in triangles_included.into_iter().enumerate() {
             let denom = 1.0 / (n as f32);
             let v = &mut vertices[i];
-            v.tangent = (glam::Vec3::from(v.tangent) * denom).into();
-            v.bitangent = (glam::Vec3::from(v.bitangent) * denom).into();
+            v.tangent *= denom;
+            v.bitangent *= denom;
         }
     }
 }
@@ -0,0 +1,701 @@
+use base64::Engine as _;
+
+use crate::{
+    io::fs_accessors::{FsAccessor, relative_to},
+    model_loaders::{
+        ModelLoader,
+        model_data::{
+            BlendMode, ImageData, MaterialData, MeshData, ModelData, TextureData, VertexData,
+        },
+        source_space::SourceSpace,
+        utils::calculate_tangent_and_bitangent,
+    },
+};
+
+/// Loads `.gltf` (with external or data URI buffers) and `.glb` files.
+///
+/// The node hierarchy of the default scene is flattened: every mesh
+/// primitive becomes a [`MeshData`] with its node's world transform baked
+/// into the vertices.
+pub struct GltfLoader<T: FsAccessor> {
+    res_loader: T,
+}
+
+impl<T: FsAccessor> GltfLoader<T> {
+    pub fn new(res_loader: T) -> Self {
+        Self { res_loader }
+    }
+}
+
+impl<T: FsAccessor> ModelLoader for GltfLoader<T> {
+    /// glTF is specified to be in engine space.
+    fn source_space(&self) -> SourceSpace {
+        SourceSpace::ENGINE
+    }
+
+    fn load_source_model_data(&self, filename: &str) -> anyhow::Result<ModelData> {
+        let gltf_data = self.res_loader.load_binary(filename)?;
+        let gltf = gltf::Gltf::from_slice(&gltf_data)?;
+
+        let buffers = gltf
+            .buffers()
+            .map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Bin => gltf.blob.clone().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Missing binary chunk in #{}/{}",
+                        self.res_loader.name(),
+                        filename
+                    )
+                }),
+                gltf::buffer::Source::Uri(uri) => {
+                    self.load_uri(filename, uri).map(|(data, _)| data)
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut materials = gltf
+            .materials()
+            .map(|material| self.load_material(&material, &buffers, filename))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // primitives without a material use the default one, appended last.
+        let default_material_index = materials.len();
+        let mut is_default_material_used = false;
+
+        let mut meshes = Vec::new();
+        let mut visit = |node: &gltf::Node, transform: glam::Mat4| -> anyhow::Result<()> {
+            let Some(mesh) = node.mesh() else {
+                return Ok(());
+            };
+
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    log::warn!(
+                        "[GltfLoader::load_source_model_data] skipping {:?} primitive in mesh {:?} of {}",
+                        primitive.mode(),
+                        mesh.name(),
+                        filename
+                    );
+                    continue;
+                }
+
+                let material_index = match primitive.material().index() {
+                    Some(index) => index,
+                    None => {
+                        is_default_material_used = true;
+                        default_material_index
+                    }
+                };
+
+                let name = format!(
+                    "{}/{}#{}",
+                    node.name().unwrap_or("<node>"),
+                    mesh.name().unwrap_or("<mesh>"),
+                    primitive.index()
+                );
+                meshes.push(Self::load_primitive(
+                    name,
+                    &primitive,
+                    &buffers,
+                    transform,
+                    material_index,
+                )?);
+            }
+
+            Ok(())
+        };
+
+        let root_nodes = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            Some(scene) => scene.nodes().collect::<Vec<_>>(),
+            // no scenes at all, so everything that is not a child is a root.
+            None => {
+                let child_indices = gltf
+                    .nodes()
+                    .flat_map(|node| node.children().map(|child| child.index()))
+                    .collect::<std::collections::HashSet<_>>();
+                gltf.nodes()
+                    .filter(|node| !child_indices.contains(&node.index()))
+                    .collect()
+            }
+        };
+
+        let mut stack = root_nodes
+            .into_iter()
+            .map(|node| (node, glam::Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+        while let Some((node, parent_transform)) = stack.pop() {
+            let transform =
+                parent_transform * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+            visit(&node, transform)?;
+            stack.extend(node.children().map(|child| (child, transform)));
+        }
+
+        if is_default_material_used {
+            materials.push(MaterialData {
+                name: "default".to_string(),
+                ..Default::default()
+            });
+        }
+
+        Ok(ModelData {
+            name: format!("#{}/{}", self.res_loader.name(), filename),
+            meshes,
+            materials,
+            bones: vec![],
+            morphs: vec![],
+            rigid_bodies: vec![],
+            joints: vec![],
+            motion_scale: 1.0,
+        })
+    }
+}
+
+impl<T: FsAccessor> GltfLoader<T> {
+    fn load_primitive(
+        name: String,
+        primitive: &gltf::Primitive,
+        buffers: &[Vec<u8>],
+        transform: glam::Mat4,
+        material_index: usize,
+    ) -> anyhow::Result<MeshData> {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+        let positions = reader
+            .read_positions()
+            .ok_or_else(|| anyhow::anyhow!("Primitive without positions: {}", name))?
+            .collect::<Vec<_>>();
+        let normals = reader
+            .read_normals()
+            .map(|normals| normals.collect::<Vec<_>>());
+        let tex_coord_set = Self::tex_coord_set(&primitive.material());
+        let tex_coords = reader
+            .read_tex_coords(tex_coord_set)
+            .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>());
+        let tex_coords_1 = reader
+            .read_tex_coords(1)
+            .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>());
+        // tangents are ignored without normals, as the spec says.
+        let tangents = match &normals {
+            Some(_) => reader
+                .read_tangents()
+                .map(|tangents| tangents.collect::<Vec<_>>()),
+            None => None,
+        };
+
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        // so that malformed files fail to load instead of panicking below.
+        for (attribute, len) in [
+            ("NORMAL".to_string(), normals.as_ref().map(Vec::len)),
+            (
+                format!("TEXCOORD_{}", tex_coord_set),
+                tex_coords.as_ref().map(Vec::len),
+            ),
+            (
+                "TEXCOORD_1".to_string(),
+                tex_coords_1.as_ref().map(Vec::len),
+            ),
+            ("TANGENT".to_string(), tangents.as_ref().map(Vec::len)),
+        ] {
+            if let Some(len) = len
+                && len != positions.len()
+            {
+                anyhow::bail!(
+                    "{} has {} values instead of {} in primitive {}",
+                    attribute,
+                    len,
+                    positions.len(),
+                    name
+                );
+            }
+        }
+        if !indices.len().is_multiple_of(3) {
+            anyhow::bail!(
+                "{} indices do not make triangles in primitive {}",
+                indices.len(),
+                name
+            );
+        }
+        if let Some(index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            anyhow::bail!("Vertex index {} out of range in primitive {}", index, name);
+        }
+
+        let normal_transform = glam::Mat3::from_mat4(transform).inverse().transpose();
+        let tangent_transform = glam::Mat3::from_mat4(transform);
+        // a mirroring transform flips the handedness of the tangent space.
+        let handedness_sign = transform.determinant().signum();
+
+        let mut vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                let normal = normals.as_ref().map_or(glam::Vec3::ZERO, |normals| {
+                    (normal_transform * glam::Vec3::from(normals[i])).normalize_or_zero()
+                });
+
+                let (tangent, bitangent) = match &tangents {
+                    Some(tangents) => {
+                        let [x, y, z, handedness] = tangents[i];
+                        let tangent = (tangent_transform * glam::vec3(x, y, z)).normalize_or_zero();
+                        let bitangent = normal.cross(tangent) * handedness * handedness_sign;
+                        (tangent, bitangent)
+                    }
+                    None => (glam::Vec3::ZERO, glam::Vec3::ZERO),
+                };
+
+                VertexData {
+                    position: transform.transform_point3(position.into()),
+                    tex_coords: tex_coords
+                        .as_ref()
+                        .map_or(glam::Vec2::ZERO, |tex_coords| tex_coords[i].into()),
+                    tex_coords_1: tex_coords_1
+                        .as_ref()
+                        .map_or(glam::Vec2::ZERO, |tex_coords| tex_coords[i].into()),
+                    normal,
+                    tangent,
+                    bitangent,
+                    edge_scale: 1.0,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut indices = indices;
+        // a mirroring transform turns the triangles inside out.
+        if transform.determinant() < 0.0 {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        // the spec asks for flat normals when there are none, so every
+        // triangle gets vertices of its own.
+        if normals.is_none() {
+            vertices = indices.iter().map(|&i| vertices[i as usize]).collect();
+            indices = (0..vertices.len() as u32).collect();
+            for triangle in vertices.chunks_exact_mut(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i].position);
+                let normal = (b - a).cross(c - a).normalize_or_zero();
+                for vertex in triangle {
+                    vertex.normal = normal;
+                }
+            }
+        }
+
+        if tangents.is_none() {
+            calculate_tangent_and_bitangent(&mut vertices, &indices);
+        }
+
+        Ok(MeshData {
+            name,
+            vertices,
+            indices,
+            material_index,
+            skin: None,
+        })
+    }
+
+    /// The UV set [`VertexData::tex_coords`] is read from. Materials only
+    /// have one for all their textures, so it is that of the base color
+    /// texture, or else of the normal texture.
+    fn tex_coord_set(material: &gltf::Material) -> u32 {
+        let pbr = material.pbr_metallic_roughness();
+        match (pbr.base_color_texture(), material.normal_texture()) {
+            (Some(info), _) => info.tex_coord(),
+            (None, Some(info)) => info.tex_coord(),
+            (None, None) => 0,
+        }
+    }
+
+    fn load_material(
+        &self,
+        material: &gltf::Material,
+        buffers: &[Vec<u8>],
+        model_filename: &str,
+    ) -> anyhow::Result<MaterialData> {
+        let pbr = material.pbr_metallic_roughness();
+        let diffuse_texture = match pbr.base_color_texture() {
+            Some(info) => Some(self.load_texture(&info.texture(), buffers, model_filename)?),
+            None => None,
+        };
+        let normal_texture = match material.normal_texture() {
+            Some(info) => Some(self.load_texture(&info.texture(), buffers, model_filename)?),
+            None => None,
+        };
+        if let (Some(base_color_info), Some(normal_info)) =
+            (pbr.base_color_texture(), material.normal_texture())
+            && base_color_info.tex_coord() != normal_info.tex_coord()
+        {
+            log::warn!(
+                "[GltfLoader::load_material] {:?} samples its normal texture with UV set {}, instead of {}",
+                material.name(),
+                normal_info.tex_coord(),
+                base_color_info.tex_coord()
+            );
+        }
+
+        Ok(MaterialData {
+            name: material
+                .name()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("material#{}", material.index().unwrap_or(0))),
+            diffuse_texture,
+            normal_texture,
+            diffuse_color: pbr.base_color_factor().into(),
+            is_double_sided: material.double_sided(),
+            blend_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => BlendMode::Opaque,
+                gltf::material::AlphaMode::Blend => BlendMode::AlphaBlend,
+                // 0.5 is the spec's default.
+                gltf::material::AlphaMode::Mask => BlendMode::AlphaTest {
+                    cutoff: material.alpha_cutoff().unwrap_or(0.5),
+                },
+            },
+            ..Default::default()
+        })
+    }
+
+    fn load_texture(
+        &self,
+        texture: &gltf::Texture,
+        buffers: &[Vec<u8>],
+        model_filename: &str,
+    ) -> anyhow::Result<TextureData> {
+        match texture.source().source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &buffers[view.buffer().index()];
+                let bytes = buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Image buffer view out of range: {:?}", texture.name())
+                    })?;
+                Ok(TextureData {
+                    path: None,
+                    image: ImageData::Encoded(bytes.to_vec()),
+                })
+            }
+            gltf::image::Source::Uri { uri, .. } => {
+                let (bytes, path) = self.load_uri(model_filename, uri)?;
+                Ok(match path {
+                    Some(path) => TextureData::from_file(&path, bytes),
+                    None => TextureData {
+                        path: None,
+                        image: ImageData::Encoded(bytes),
+                    },
+                })
+            }
+        }
+    }
+
+    /// Returns the data, and the path it was read from unless it was a data
+    /// URI. Other URIs are relative to the model at `model_filename`.
+    fn load_uri(
+        &self,
+        model_filename: &str,
+        uri: &str,
+    ) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+        if let Some(data_uri) = uri.strip_prefix("data:") {
+            let (_mime_type, data) = data_uri
+                .split_once(";base64,")
+                .ok_or_else(|| anyhow::anyhow!("Unsupported data URI: {:.40}", uri))?;
+            return Ok((
+                base64::engine::general_purpose::STANDARD.decode(data)?,
+                None,
+            ));
+        }
+
+        let relative_path = percent_encoding::percent_decode_str(uri).decode_utf8()?;
+        let path = relative_to(model_filename, &relative_path);
+        Ok((self.res_loader.load_binary(&path)?, Some(path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        embedded_demo_resources::ResCube,
+        io::fs_accessors::{embed_fs_accessor::EmbedFsAccessor, testing::MemoryFsAccessor},
+    };
+
+    /// A triangle with two UV sets, [`SET_0`] and [`SET_1`], and a material
+    /// made of `material_json`.
+    fn triangle_gltf(material_json: &str) -> String {
+        triangle_gltf_with_nodes(material_json, r#"[{ "mesh": 0 }]"#)
+    }
+
+    /// As [`triangle_gltf`], with `nodes_json` instead of a single node. The
+    /// scene is made of node 0.
+    fn triangle_gltf_with_nodes(material_json: &str, nodes_json: &str) -> String {
+        let floats: [f32; 21] = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // positions
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, // set 0
+            0.25, 0.75, 0.75, 0.75, 0.25, 0.25, // set 1
+        ];
+        let buffer = base64::engine::general_purpose::STANDARD.encode(
+            floats
+                .iter()
+                .flat_map(|f| f.to_le_bytes())
+                .collect::<Vec<_>>(),
+        );
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": 84, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 24 }},
+                    {{ "buffer": 0, "byteOffset": 60, "byteLength": 24 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" }},
+                    {{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" }}
+                ],
+                "images": [{{ "uri": "tex.png" }}],
+                "textures": [{{ "source": 0 }}],
+                "materials": [{material_json}],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1, "TEXCOORD_1": 2 }},
+                    "material": 0
+                }}] }}],
+                "nodes": {nodes_json},
+                "scenes": [{{ "nodes": [0] }}]
+            }}"#
+        )
+    }
+
+    const SET_0: [glam::Vec2; 3] = [
+        glam::vec2(0.0, 0.0),
+        glam::vec2(1.0, 0.0),
+        glam::vec2(0.0, 1.0),
+    ];
+    const SET_1: [glam::Vec2; 3] = [
+        glam::vec2(0.25, 0.75),
+        glam::vec2(0.75, 0.75),
+        glam::vec2(0.25, 0.25),
+    ];
+
+    fn tex_coords(model_data: &ModelData) -> Vec<glam::Vec2> {
+        model_data.meshes[0]
+            .vertices
+            .iter()
+            .map(|vertex| vertex.tex_coords)
+            .collect()
+    }
+
+    fn load(material_json: &str) -> ModelData {
+        let gltf = triangle_gltf(material_json);
+        let fs = MemoryFsAccessor::new([
+            ("triangle.gltf", gltf.as_bytes()),
+            ("tex.png", b"not decoded by loaders".as_slice()),
+        ]);
+        GltfLoader::new(fs)
+            .load_model_data("triangle.gltf")
+            .unwrap()
+    }
+
+    #[test]
+    fn base_color_factor_tints_untextured_materials() {
+        let model_data =
+            load(r#"{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0.5, 0, 0.25] } }"#);
+        let material = &model_data.materials[0];
+        assert_eq!(material.diffuse_color, glam::vec4(1.0, 0.5, 0.0, 0.25));
+        assert!(material.diffuse_texture.is_none());
+    }
+
+    #[test]
+    fn textures_are_sampled_with_their_uv_set() {
+        let set_0 = load(r#"{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }"#);
+        assert_eq!(tex_coords(&set_0), SET_0);
+        assert_eq!(set_0.materials[0].diffuse_color, glam::Vec4::ONE);
+
+        // TEXCOORD_1 bound to the base color texture.
+        let set_1 = load(
+            r#"{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "texCoord": 1 } } }"#,
+        );
+        assert_eq!(tex_coords(&set_1), SET_1);
+        assert_eq!(
+            set_1.materials[0]
+                .diffuse_texture
+                .as_ref()
+                .unwrap()
+                .path
+                .as_deref(),
+            Some("tex.png")
+        );
+    }
+
+    #[test]
+    fn normal_textures_follow_the_base_color_uv_set() {
+        let model_data = load(
+            r#"{
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "texCoord": 1 } },
+                "normalTexture": { "index": 0 }
+            }"#,
+        );
+        assert_eq!(tex_coords(&model_data), SET_1);
+        assert!(model_data.materials[0].normal_texture.is_some());
+
+        // without a base color texture, the normal texture's set is used.
+        let model_data = load(r#"{ "normalTexture": { "index": 0, "texCoord": 1 } }"#);
+        assert_eq!(tex_coords(&model_data), SET_1);
+    }
+
+    /// Positions of the first mesh's triangles, in index order.
+    fn triangles(model_data: &ModelData) -> Vec<[glam::Vec3; 3]> {
+        let mesh = &model_data.meshes[0];
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position))
+            .collect()
+    }
+
+    #[test]
+    fn primitives_without_normals_get_flat_normals() {
+        let model_data = load("{}");
+        let mesh = &model_data.meshes[0];
+        assert!(mesh.vertices.iter().all(|v| v.normal == glam::Vec3::Z));
+        assert!(mesh.vertices.iter().all(|v| v.tangent.is_finite()));
+    }
+
+    #[test]
+    fn node_hierarchies_are_flattened() {
+        let gltf = triangle_gltf_with_nodes(
+            "{}",
+            r#"[
+                { "translation": [10, 0, 0], "children": [1] },
+                { "mesh": 0, "scale": [-1, 1, 1] }
+            ]"#,
+        );
+        let fs = MemoryFsAccessor::new([("nested.gltf", gltf.as_bytes())]);
+        let model_data = GltfLoader::new(fs).load_model_data("nested.gltf").unwrap();
+
+        assert_eq!(model_data.meshes.len(), 1);
+        // mirrored by the child, then moved by the parent, with the winding
+        // swapped so that the triangle still faces +Z.
+        assert_eq!(
+            triangles(&model_data),
+            [[
+                glam::vec3(10.0, 0.0, 0.0),
+                glam::vec3(10.0, 1.0, 0.0),
+                glam::vec3(9.0, 0.0, 0.0),
+            ]]
+        );
+        assert!(
+            model_data.meshes[0]
+                .vertices
+                .iter()
+                .all(|v| v.normal == glam::Vec3::Z)
+        );
+    }
+
+    #[test]
+    fn bitangents_survive_mirroring_nodes() {
+        let floats: [f32; 36] = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, // positions
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, // normals
+            1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, // tangents
+            0.0, 0.0, 1.0, 0.0, 0.0, 1.0, // UVs
+        ];
+        let buffer = base64::engine::general_purpose::STANDARD.encode(
+            floats
+                .iter()
+                .flat_map(|f| f.to_le_bytes())
+                .collect::<Vec<_>>(),
+        );
+        let gltf = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{ "byteLength": 144, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 72, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 120, "byteLength": 24 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                    {{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" }},
+                    {{ "bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC2" }}
+                ],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "NORMAL": 1, "TANGENT": 2, "TEXCOORD_0": 3 }}
+                }}] }}],
+                "nodes": [{{ "mesh": 0, "scale": [-1, 1, 1] }}],
+                "scenes": [{{ "nodes": [0] }}]
+            }}"#
+        );
+        let fs = MemoryFsAccessor::new([("mirrored.gltf", gltf.as_bytes())]);
+        let model_data = GltfLoader::new(fs)
+            .load_model_data("mirrored.gltf")
+            .unwrap();
+
+        // V still runs along +Y, while U now runs along -X.
+        for vertex in &model_data.meshes[0].vertices {
+            assert_eq!(vertex.normal, glam::Vec3::Z);
+            assert_eq!(vertex.tangent, glam::Vec3::NEG_X);
+            assert_eq!(vertex.bitangent, glam::Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn malformed_primitives_are_errors() {
+        let load_error = |gltf: String| {
+            let fs = MemoryFsAccessor::new([("broken.gltf", gltf.as_bytes())]);
+            GltfLoader::new(fs)
+                .load_model_data("broken.gltf")
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        // TEXCOORD_1 with fewer values than POSITION.
+        let gltf = triangle_gltf("{}").replace(
+            r#"{ "bufferView": 2, "componentType": 5126, "count": 3"#,
+            r#"{ "bufferView": 2, "componentType": 5126, "count": 2"#,
+        );
+        assert_eq!(
+            load_error(gltf),
+            "TEXCOORD_1 has 2 values instead of 3 in primitive <node>/<mesh>#0"
+        );
+
+        // the second position read as indices, the first of which is 1.0f32.
+        let gltf = triangle_gltf("{}")
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 60, "byteLength": 24 }"#,
+                r#"{ "buffer": 0, "byteOffset": 60, "byteLength": 24 },
+                   { "buffer": 0, "byteOffset": 12, "byteLength": 12 }"#,
+            )
+            .replace(
+                r#"{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" }"#,
+                r#"{ "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" },
+                   { "bufferView": 3, "componentType": 5125, "count": 3, "type": "SCALAR" }"#,
+            )
+            .replace(r#""attributes": {"#, r#""indices": 3, "attributes": {"#);
+        assert_eq!(
+            load_error(gltf),
+            "Vertex index 1065353216 out of range in primitive <node>/<mesh>#0"
+        );
+    }
+
+    #[test]
+    fn loads_embedded_box() {
+        let loader = GltfLoader::new(EmbedFsAccessor::<ResCube>::new("cube"));
+        let model_data = loader.load_model_data("box.glb").unwrap();
+        assert!(!model_data.meshes.is_empty());
+        assert_eq!(model_data.materials[0].name, "boxmat");
+        assert!(model_data.materials[0].diffuse_texture.is_some());
+        assert!(model_data.materials[0].normal_texture.is_some());
+
+        // without materials, the default one is added.
+        let model_data = loader.load_model_data("box-untextured.glb").unwrap();
+        assert_eq!(model_data.materials.len(), 1);
+        assert_eq!(model_data.materials[0].diffuse_color, glam::Vec4::ONE);
+    }
+}
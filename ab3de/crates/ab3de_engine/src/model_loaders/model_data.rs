@@ -0,0 +1,440 @@
+use serde::{Deserialize, Serialize};
+
+/// What a [`super::ModelLoader`] produces. Plain data only, so it can be
+/// built on any thread, cached and compared without a GPU. See
+/// [`crate::drawing::models::Model::upload`] for turning it into something
+/// drawable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelData {
+    /// Used for labels and log messages.
+    pub name: String,
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    /// Empty for models without a skeleton.
+    pub bones: Vec<BoneData>,
+    pub morphs: Vec<MorphData>,
+    /// Simulated on top of the skeleton, see [`RigidBodyMode`].
+    pub rigid_bodies: Vec<RigidBodyData>,
+    pub joints: Vec<JointData>,
+    /// Scales the translations of motions made for the file, so that they
+    /// still fit after [`ModelData::fit`]. `1` otherwise.
+    pub motion_scale: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshData {
+    pub name: String,
+    pub vertices: Vec<VertexData>,
+    /// Triangle list.
+    pub indices: Vec<u32>,
+    pub material_index: usize,
+    /// One per vertex, if the mesh is deformed by [`ModelData::bones`].
+    pub skin: Option<Vec<VertexSkinData>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VertexSkinData {
+    /// Indices into [`ModelData::bones`]. Unused slots have a weight of 0.
+    pub bones: [u32; 4],
+    /// Sum up to 1.
+    pub weights: [f32; 4],
+    /// Spherical deform between `bones[0]` and `bones[1]`, instead of
+    /// blending their matrices linearly.
+    pub sdef: Option<SdefData>,
+}
+
+/// Spherical deform parameters, as in PMX.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SdefData {
+    pub c: glam::Vec3,
+    pub r0: glam::Vec3,
+    pub r1: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoneData {
+    pub name: String,
+    /// Always refers to another bone, but not necessarily an earlier one.
+    pub parent: Option<usize>,
+    /// In model space. Bones are not rotated in the rest pose.
+    pub rest_position: glam::Vec3,
+    /// Bones are transformed in ascending layer order, then by index.
+    pub layer: i32,
+    /// Transformed after physics has moved the bodies, in a second pass.
+    pub deforms_after_physics: bool,
+    /// Makes this an IK bone, which pulls [`IkData::target`] towards itself.
+    pub ik: Option<IkData>,
+    pub inherit: Option<InheritData>,
+    /// Rotations are reduced to their twist around this unit axis, in the
+    /// parent's space.
+    pub fixed_axis: Option<glam::Vec3>,
+    /// The orientation editors show the bone in, as an orthonormal basis.
+    /// Poses are not expressed in it, but rotations around it can be
+    /// converted with [`Self::rotation_in_local_axes`].
+    pub local_axes: Option<glam::Mat3>,
+}
+
+impl BoneData {
+    /// Converts a rotation around [`Self::local_axes`] into one for
+    /// [`BoneTransform::rotation`](crate::BoneTransform::rotation).
+    pub fn rotation_in_local_axes(&self, rotation: glam::Quat) -> glam::Quat {
+        match self.local_axes {
+            Some(local_axes) => {
+                let basis = glam::Quat::from_mat3(&local_axes);
+                basis * rotation * basis.inverse()
+            }
+            None => rotation,
+        }
+    }
+}
+
+/// Adds a fraction of another bone's transform on top of the bone's own,
+/// also called "append" or "grant" (付与) in MMD.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InheritData {
+    pub source: usize,
+    /// Can be negative or above `1`.
+    pub weight: f32,
+    pub rotation: bool,
+    pub translation: bool,
+    /// Inherits the source's own pose, instead of what it inherited itself.
+    pub is_local: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IkData {
+    /// The end of the chain, moved onto the IK bone.
+    pub target: usize,
+    pub loop_count: u32,
+    /// Maximum rotation of a link per iteration, in radians.
+    pub limit_angle: f32,
+    /// From the bone next to the target towards the root.
+    pub links: Vec<IkLinkData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IkLinkData {
+    pub bone: usize,
+    /// Euler angle limits in radians, applied in X, Y, Z order.
+    pub limits: Option<IkLimitsData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IkLimitsData {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MorphData {
+    pub name: String,
+    pub kind: MorphKind,
+}
+
+/// What a morph does at a weight of `1`. Everything scales linearly with the
+/// weight, except for rotations, which are interpolated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MorphKind {
+    /// Drives other morphs, adding its weight times theirs to their own.
+    Group(Vec<GroupMorphOffsetData>),
+    Vertex(Vec<VertexMorphOffsetData>),
+    Uv(Vec<UvMorphOffsetData>),
+    /// Applied on top of the pose, before the skeleton is evaluated.
+    Bone(Vec<BoneMorphOffsetData>),
+    Material(Vec<MaterialMorphOffsetData>),
+    /// Kept so that indices match the file, but does nothing.
+    Unsupported,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GroupMorphOffsetData {
+    /// Index into [`ModelData::morphs`].
+    pub morph: usize,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VertexMorphOffsetData {
+    /// Index into [`ModelData::meshes`].
+    pub mesh: usize,
+    /// Index into [`MeshData::vertices`].
+    pub vertex: u32,
+    pub position: glam::Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UvMorphOffsetData {
+    /// Index into [`ModelData::meshes`].
+    pub mesh: usize,
+    /// Index into [`MeshData::vertices`].
+    pub vertex: u32,
+    pub tex_coords: glam::Vec2,
+    pub tex_coords_1: glam::Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoneMorphOffsetData {
+    /// Index into [`ModelData::bones`].
+    pub bone: usize,
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MaterialMorphOffsetData {
+    /// Index into [`ModelData::materials`]. `None` applies to all of them.
+    pub material: Option<usize>,
+    pub operation: MaterialMorphOperation,
+    pub diffuse_color: glam::Vec4,
+    pub specular_color: glam::Vec3,
+    pub specular_power: f32,
+    pub ambient_color: glam::Vec3,
+    pub edge_color: glam::Vec4,
+    pub edge_size: f32,
+    /// Tints of the diffuse texture, the sphere map and the toon ramp. The
+    /// alpha channel fades the tint in.
+    pub texture_tint: glam::Vec4,
+    pub sphere_tint: glam::Vec4,
+    pub toon_tint: glam::Vec4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MaterialMorphOperation {
+    /// Multiplies by `1 + (offset - 1) * weight`.
+    Multiply,
+    /// Adds `offset * weight`.
+    Add,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RigidBodyData {
+    pub name: String,
+    /// Index into [`ModelData::bones`], the bone moving or moved by the body.
+    pub bone: Option<usize>,
+    /// `0..16`.
+    pub group: u8,
+    /// Bit `n` set means the body collides with group `n`.
+    pub collision_mask: u16,
+    pub shape: RigidBodyShape,
+    /// In model space, in the rest pose.
+    pub position: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub mass: f32,
+    /// The fraction of velocity lost per second, `0..=1`, as in Bullet.
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub mode: RigidBodyMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RigidBodyShape {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: glam::Vec3,
+    },
+    /// Along the body's Y axis. `height` is without the caps.
+    Capsule {
+        radius: f32,
+        height: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RigidBodyMode {
+    /// Not simulated, but moved along with its bone.
+    FollowBone,
+    /// Simulated, and moves its bone.
+    Physics,
+    /// Simulated, but only rotates its bone. The bone stays where its parent
+    /// puts it.
+    PhysicsWithBonePosition,
+}
+
+/// A 6-DOF spring joint, as in Bullet. Limits and springs are along and
+/// around the axes of the joint's own frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JointData {
+    pub name: String,
+    /// Indices into [`ModelData::rigid_bodies`].
+    pub rigid_bodies: [usize; 2],
+    /// In model space, in the rest pose.
+    pub position: glam::Vec3,
+    pub rotation: glam::Quat,
+    /// An axis is locked if its minimum equals its maximum, and free if the
+    /// minimum is greater.
+    pub linear_min: glam::Vec3,
+    pub linear_max: glam::Vec3,
+    /// In radians.
+    pub angular_min: glam::Vec3,
+    pub angular_max: glam::Vec3,
+    /// Stiffness of the springs pulling back to the joint's frame.
+    pub linear_spring: glam::Vec3,
+    pub angular_spring: glam::Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VertexData {
+    pub position: glam::Vec3,
+    pub tex_coords: glam::Vec2,
+    /// Second UV set. Only used by [`SphereMapMode::SubTexture`].
+    pub tex_coords_1: glam::Vec2,
+    pub normal: glam::Vec3,
+
+    pub tangent: glam::Vec3,
+    pub bitangent: glam::Vec3,
+    /// Scales the outline at this vertex, see [`EdgeData::size`].
+    pub edge_scale: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialData {
+    pub name: String,
+    /// White if missing.
+    pub diffuse_texture: Option<TextureData>,
+    /// Flat if missing.
+    pub normal_texture: Option<TextureData>,
+
+    /// Multiplies the diffuse texture.
+    pub diffuse_color: glam::Vec4,
+    pub specular_color: glam::Vec3,
+    pub specular_power: f32,
+    /// Added to the lit color, regardless of lights.
+    pub ambient_color: glam::Vec3,
+    /// A vertical lighting ramp, lit at the top. Unshaded if missing. Only
+    /// used by [`MaterialShading::Toon`], as is the sphere map.
+    pub toon_texture: Option<TextureData>,
+    pub sphere_map: Option<SphereMapData>,
+    pub shading: MaterialShading,
+    /// Draws back faces too, instead of culling them.
+    pub is_double_sided: bool,
+    pub blend_mode: BlendMode,
+    /// Off for things that should not hide what is drawn after them.
+    pub writes_depth: bool,
+}
+
+/// White, untextured, opaque and single-sided.
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            diffuse_texture: None,
+            normal_texture: None,
+            diffuse_color: glam::Vec4::ONE,
+            specular_color: glam::Vec3::ONE,
+            specular_power: 32.0,
+            ambient_color: glam::Vec3::ZERO,
+            toon_texture: None,
+            sphere_map: None,
+            shading: MaterialShading::Standard,
+            is_double_sided: false,
+            blend_mode: BlendMode::Opaque,
+            writes_depth: true,
+        }
+    }
+}
+
+/// How a material's alpha is used. Opaque and alpha tested materials are
+/// drawn first, then alpha blended ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Alpha is ignored.
+    Opaque,
+    /// Drawn over what is behind it, in material order within a model and
+    /// back to front between models.
+    AlphaBlend,
+    /// Drawn opaque where alpha is at least `cutoff`, and not at all
+    /// elsewhere.
+    AlphaTest { cutoff: f32 },
+}
+
+/// Which pipeline draws a material. Loaders pick what their format was
+/// made for.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MaterialShading {
+    /// Blinn-Phong with a reflection of the environment.
+    Standard,
+    /// As in MMD: ambient, diffuse and specular colors shaded by the toon
+    /// ramp, with the sphere map on top.
+    Toon { edge: Option<EdgeData> },
+}
+
+/// An outline, drawn by pushing the mesh out along its normals and drawing
+/// only its back faces.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EdgeData {
+    pub color: glam::Vec4,
+    /// Roughly in pixels at 1080p, whatever the distance. Scaled per vertex
+    /// by [`VertexData::edge_scale`].
+    pub size: f32,
+}
+
+/// A texture sampled with the view space normal, as used by MMD.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SphereMapData {
+    pub texture: TextureData,
+    pub mode: SphereMapMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SphereMapMode {
+    Multiply,
+    Add,
+    /// Not a sphere map at all, but a second texture multiplied with the
+    /// diffuse one, sampled with [`VertexData::tex_coords_1`].
+    SubTexture,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextureData {
+    /// The file the image was read from, relative to the loader's
+    /// [`crate::io::fs_accessors::FsAccessor`]. `None` if the image was
+    /// embedded in the model file or generated.
+    pub path: Option<String>,
+    pub image: ImageData,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ImageData {
+    /// An image file (PNG, JPEG, ...), decoded when uploading.
+    Encoded(Vec<u8>),
+    Rgba8 {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+}
+
+impl TextureData {
+    pub fn from_file(path: &str, bytes: Vec<u8>) -> Self {
+        Self {
+            path: Some(path.to_string()),
+            image: ImageData::Encoded(bytes),
+        }
+    }
+
+    pub fn decode(&self) -> anyhow::Result<image::DynamicImage> {
+        match &self.image {
+            ImageData::Encoded(bytes) => Ok(image::load_from_memory(bytes)?),
+            ImageData::Rgba8 {
+                width,
+                height,
+                pixels,
+            } => {
+                let image = image::RgbaImage::from_vec(*width, *height, pixels.clone())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Pixel buffer does not match its size {}x{}: {:?}",
+                            width,
+                            height,
+                            self.path
+                        )
+                    })?;
+                Ok(image::DynamicImage::ImageRgba8(image))
+            }
+        }
+    }
+}
@@ -1,12 +1,13 @@
 use std::io::{BufReader, Cursor};
 
 use crate::{
-    drawing::{
-        models::{Material, Mesh, Model, ModelVertex},
-        textures,
+    io::fs_accessors::{FsAccessor, relative_to},
+    model_loaders::{
+        ModelLoader,
+        model_data::{MaterialData, MeshData, ModelData, TextureData, VertexData},
+        source_space::SourceSpace,
+        utils::calculate_tangent_and_bitangent,
     },
-    io::fs_accessors::FsAccessor,
-    model_loaders::{ModelLoader, utils::calculate_tangent_and_bitangent},
 };
 
 pub struct ObjLoader<T: FsAccessor> {
@@ -20,13 +21,12 @@ impl<T: FsAccessor> ObjLoader<T> {
 }
 
 impl<T: FsAccessor> ModelLoader for ObjLoader<T> {
-    fn load_model(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> anyhow::Result<Model> {
+    /// OBJ has no convention, but most exporters write Y up.
+    fn source_space(&self) -> SourceSpace {
+        SourceSpace::ENGINE
+    }
+
+    fn load_source_model_data(&self, filename: &str) -> anyhow::Result<ModelData> {
         let obj_text = self.res_loader.load_binary(filename)?;
         let obj_cursor = Cursor::new(obj_text);
         let mut obj_reader = BufReader::new(obj_cursor);
@@ -39,87 +39,207 @@ impl<T: FsAccessor> ModelLoader for ObjLoader<T> {
                 ..Default::default()
             },
             move |p| {
-                let mat_text = self.res_loader.load_string(p.to_str().unwrap()).unwrap();
+                let mat_filename = relative_to(filename, &p.to_string_lossy());
+                let mat_text = self.res_loader.load_string(&mat_filename).map_err(|e| {
+                    log::warn!("[ObjLoader::load_model_data] {}", e);
+                    tobj::LoadError::OpenFileFailed
+                })?;
                 tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
             },
         )?;
 
         let mut materials = Vec::new();
         for m in obj_materials? {
-            materials.push(Material::new(
-                device,
-                &m.name,
-                self.load_diffuse_texture(&m.diffuse_texture.unwrap(), device, queue)?,
-                self.load_normal_texture(&m.normal_texture.unwrap(), device, queue)?,
-                texture_bind_group_layout,
-            ));
+            materials.push(MaterialData {
+                diffuse_texture: self.load_texture(filename, m.diffuse_texture.as_deref())?,
+                normal_texture: self.load_texture(filename, m.normal_texture.as_deref())?,
+                name: m.name,
+                ..Default::default()
+            });
         }
 
+        // meshes without a material use the default one, appended last.
+        let default_material_index = materials.len();
+        let mut is_default_material_used = false;
+
         let meshes = models
             .into_iter()
             .map(|m| {
                 let mut vertices = (0..m.mesh.positions.len() / 3)
-                    .map(|i| ModelVertex {
+                    .map(|i| VertexData {
                         position: [
                             m.mesh.positions[i * 3],
                             m.mesh.positions[i * 3 + 1],
                             m.mesh.positions[i * 3 + 2],
                         ]
                         .into(),
-                        tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]]
-                            .into(),
-                        normal: if m.mesh.normals.is_empty() {
-                            glam::Vec3::ZERO
-                        } else {
-                            [
-                                m.mesh.normals[i * 3],
-                                m.mesh.normals[i * 3 + 1],
-                                m.mesh.normals[i * 3 + 2],
-                            ]
-                            .into()
+                        // `vt` and `vn` lines are optional.
+                        tex_coords: match m.mesh.texcoords.get(i * 2..i * 2 + 2) {
+                            Some(&[u, v]) => glam::vec2(u, 1.0 - v),
+                            _ => glam::Vec2::ZERO,
+                        },
+                        tex_coords_1: glam::Vec2::ZERO,
+                        normal: match m.mesh.normals.get(i * 3..i * 3 + 3) {
+                            Some(normal) => glam::Vec3::from_slice(normal),
+                            None => glam::Vec3::ZERO,
                         },
                         tangent: glam::Vec3::ZERO,
                         bitangent: glam::Vec3::ZERO,
+                        edge_scale: 1.0,
                     })
                     .collect::<Vec<_>>();
 
                 calculate_tangent_and_bitangent(&mut vertices, &m.mesh.indices);
 
-                Mesh::new(
-                    &format!("#{}/{}", self.res_loader.name(), filename),
-                    device,
-                    &vertices,
-                    &m.mesh.indices,
-                    m.mesh.material_id.unwrap_or(0),
-                )
+                MeshData {
+                    name: m.name,
+                    vertices,
+                    indices: m.mesh.indices,
+                    material_index: m.mesh.material_id.unwrap_or_else(|| {
+                        is_default_material_used = true;
+                        default_material_index
+                    }),
+                    skin: None,
+                }
             })
             .collect::<Vec<_>>();
 
-        Ok(Model::new(meshes, materials))
+        if is_default_material_used {
+            materials.push(MaterialData {
+                name: "default".to_string(),
+                ..Default::default()
+            });
+        }
+
+        Ok(ModelData {
+            name: format!("#{}/{}", self.res_loader.name(), filename),
+            meshes,
+            materials,
+            bones: vec![],
+            morphs: vec![],
+            rigid_bodies: vec![],
+            joints: vec![],
+            motion_scale: 1.0,
+        })
     }
 }
 
 impl<T: FsAccessor> ObjLoader<T> {
-    fn load_diffuse_texture(
+    /// `path` is relative to the model at `model_filename`.
+    fn load_texture(
         &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) -> anyhow::Result<textures::D2DiffuseTexture> {
-        let data = self.res_loader.load_binary(filename)?;
-        let texture =
-            textures::D2DiffuseTexture::from_image_in_memory(filename, device, queue, &data)?;
-        Ok(texture)
+        model_filename: &str,
+        path: Option<&str>,
+    ) -> anyhow::Result<Option<TextureData>> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let filename = relative_to(model_filename, path);
+        let data = self.res_loader.load_binary(&filename)?;
+        Ok(Some(TextureData::from_file(&filename, data)))
     }
-    fn load_normal_texture(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) -> anyhow::Result<textures::D2NormalTexture> {
-        let data = self.res_loader.load_binary(filename)?;
-        let texture =
-            textures::D2NormalTexture::from_image_in_memory(filename, device, queue, &data)?;
-        Ok(texture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        embedded_demo_resources::ResCube,
+        io::fs_accessors::{embed_fs_accessor::EmbedFsAccessor, testing::MemoryFsAccessor},
+    };
+
+    #[test]
+    fn loads_embedded_cube() {
+        let loader = ObjLoader::new(EmbedFsAccessor::<ResCube>::new("cube"));
+        let model_data = loader.load_model_data("cube.obj").unwrap();
+
+        assert_eq!(model_data.meshes.len(), 1);
+        let mesh = &model_data.meshes[0];
+        assert_eq!(mesh.indices.len(), 12 * 3);
+        assert!(
+            mesh.indices
+                .iter()
+                .all(|&i| (i as usize) < mesh.vertices.len())
+        );
+        assert!(mesh.skin.is_none());
+
+        // front faces are counter-clockwise, facing where the normals do.
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let face_normal = (b.position - a.position).cross(c.position - a.position);
+            assert!(face_normal.dot(a.normal) > 0.0, "{:?}", triangle);
+        }
+
+        assert_eq!(model_data.materials.len(), 1);
+        let material = &model_data.materials[0];
+        assert_eq!(material.name, "Material.001");
+        assert_eq!(
+            material.diffuse_texture.as_ref().unwrap().path.as_deref(),
+            Some("cube-diffuse.png")
+        );
+        assert_eq!(
+            material.normal_texture.as_ref().unwrap().path.as_deref(),
+            Some("cube-normal.png")
+        );
+    }
+
+    #[test]
+    fn resolves_mtl_and_textures_next_to_the_obj() {
+        let fs = MemoryFsAccessor::new([
+            (
+                "models/tri.obj",
+                b"mtllib tri.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nusemtl red\nf 1/1 2/2 3/3\n"
+                    .as_slice(),
+            ),
+            ("models/tri.mtl", b"newmtl red\nmap_Kd tex/red.png\n"),
+            ("models/tex/red.png", b"not decoded by loaders"),
+        ]);
+        let model_data = ObjLoader::new(fs)
+            .load_model_data("models/tri.obj")
+            .unwrap();
+
+        assert_eq!(model_data.name, "#memory/models/tri.obj");
+        assert_eq!(model_data.meshes[0].indices, [0, 1, 2]);
+        assert_eq!(model_data.meshes[0].vertices[1].position, glam::Vec3::X);
+        // V is flipped, as textures are stored top row first.
+        assert_eq!(
+            model_data.meshes[0].vertices[2].tex_coords,
+            glam::Vec2::ZERO
+        );
+        assert_eq!(
+            model_data.materials[0].diffuse_texture,
+            Some(TextureData::from_file(
+                "models/tex/red.png",
+                b"not decoded by loaders".to_vec()
+            ))
+        );
+    }
+
+    #[test]
+    fn loads_obj_without_tex_coords_or_normals() {
+        let fs = MemoryFsAccessor::new([(
+            "tri.obj",
+            b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".as_slice(),
+        )]);
+        let model_data = ObjLoader::new(fs).load_model_data("tri.obj").unwrap();
+
+        let vertices = &model_data.meshes[0].vertices;
+        assert_eq!(vertices.len(), 3);
+        assert_eq!(vertices[1].position, glam::Vec3::X);
+        assert!(vertices.iter().all(|v| v.tex_coords == glam::Vec2::ZERO));
+        assert!(vertices.iter().all(|v| v.normal == glam::Vec3::ZERO));
+
+        // without a `usemtl`, the mesh uses the default material.
+        assert_eq!(model_data.meshes[0].material_index, 0);
+        assert_eq!(model_data.materials.len(), 1);
+    }
+
+    #[test]
+    fn missing_mtl_is_an_error() {
+        let fs = MemoryFsAccessor::new([(
+            "tri.obj",
+            b"mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n".as_slice(),
+        )]);
+        assert!(ObjLoader::new(fs).load_model_data("tri.obj").is_err());
     }
 }
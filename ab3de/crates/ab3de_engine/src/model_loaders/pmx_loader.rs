@@ -1,14 +1,24 @@
-use std::io::{BufReader, Cursor};
+use glam::Vec4Swizzles as _;
 
 use crate::{
-    drawing::{
-        models::{Material, Mesh, Model, ModelVertex},
-        textures,
+    formats::pmx::{
+        PmxBoneFlags, PmxIk, PmxJointKind, PmxMaterialFlags, PmxMaterialMorphOperation, PmxModel,
+        PmxMorphOffsets, PmxRigidBodyMode, PmxRigidBodyShape, PmxSphereMode, PmxToon,
+        PmxVertexDeform, parse_pmx,
     },
-    io::fs_accessors::FsAccessor,
+    io::fs_accessors::{FsAccessor, relative_to},
     model_loaders::{
         ModelLoader,
-        utils::{calculate_tangent_and_bitangent, new_flat_normal_texture},
+        model_data::{
+            BlendMode, BoneData, BoneMorphOffsetData, EdgeData, GroupMorphOffsetData, IkData,
+            IkLimitsData, IkLinkData, ImageData, InheritData, JointData, MaterialData,
+            MaterialMorphOffsetData, MaterialMorphOperation, MaterialShading, MeshData, ModelData,
+            MorphData, MorphKind, RigidBodyData, RigidBodyMode, RigidBodyShape, SdefData,
+            SphereMapData, SphereMapMode, TextureData, UvMorphOffsetData, VertexData,
+            VertexMorphOffsetData, VertexSkinData,
+        },
+        source_space::SourceSpace,
+        utils::calculate_tangent_and_bitangent,
     },
 };
 
@@ -23,138 +33,636 @@ impl<T: FsAccessor> PmxLoader<T> {
 }
 
 impl<T: FsAccessor> ModelLoader for PmxLoader<T> {
-    fn load_model(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        texture_bind_group_layout: &wgpu::BindGroupLayout,
-    ) -> anyhow::Result<Model> {
-        use mmd::pmx::reader::*;
+    fn source_space(&self) -> SourceSpace {
+        SourceSpace::MMD
+    }
 
+    fn load_source_model_data(&self, filename: &str) -> anyhow::Result<ModelData> {
         let pmx_data = self.res_loader.load_binary(filename)?;
-        let pmx_cursor = Cursor::new(pmx_data);
-        let pmx_reader = BufReader::new(pmx_cursor);
-
-        let pmx_header = mmd::HeaderReader::new(pmx_reader)?;
-
-        let mut pmx_vertices_r = VertexReader::<_>::new(pmx_header)?;
-        let pmx_vertices = pmx_vertices_r
-            .iter::<mmd::DefaultConfig>()
-            .collect::<mmd::Result<Vec<_>>>()?;
+        let pmx = parse_pmx(&pmx_data)
+            .map_err(|e| anyhow::anyhow!("{} (#{}/{})", e, self.res_loader.name(), filename))?;
 
-        let mut pmx_surfaces_r = SurfaceReader::<_>::new(pmx_vertices_r)?;
-        let pmx_surfaces = pmx_surfaces_r
-            .iter::<mmd::DefaultConfig>()
-            .collect::<mmd::Result<Vec<_>>>()?;
+        let bone_count = pmx.bones.len();
+        let check_bone = |bone: Option<u32>| match bone {
+            Some(bone) if bone as usize >= bone_count => {
+                Err(anyhow::anyhow!("Bone index out of range: {}", bone))
+            }
+            bone => Ok(bone.map(|bone| bone as usize)),
+        };
 
-        let mut pmx_textures_r = TextureReader::new(pmx_surfaces_r)?;
-        let texture_list = pmx_textures_r.iter().collect::<mmd::Result<Vec<_>>>()?;
+        let bones = pmx
+            .bones
+            .iter()
+            .map(|bone| {
+                let ik = match (&bone.ik, bone.flags.contains(PmxBoneFlags::IK)) {
+                    (Some(ik), true) => ik_data(ik, check_bone)?,
+                    _ => None,
+                };
 
-        let mut pmx_materials_r = MaterialReader::<_>::new(pmx_textures_r)?;
-        let pmx_materials = pmx_materials_r
-            .iter::<mmd::DefaultConfig>()
-            .collect::<mmd::Result<Vec<_>>>()?;
+                let inherit = match &bone.inherit {
+                    Some(inherit) => check_bone(inherit.parent)?.map(|source| InheritData {
+                        source,
+                        weight: inherit.weight,
+                        rotation: bone.flags.contains(PmxBoneFlags::INHERIT_ROTATION),
+                        translation: bone.flags.contains(PmxBoneFlags::INHERIT_TRANSLATION),
+                        is_local: bone.flags.contains(PmxBoneFlags::INHERIT_LOCAL),
+                    }),
+                    None => None,
+                };
 
-        let mut pmx_bones_r = BoneReader::<_>::new(pmx_materials_r)?;
-        let _pmx_bones = pmx_bones_r
-            .iter::<mmd::DefaultConfig>()
-            .collect::<mmd::Result<Vec<_>>>()?;
+                let local_axes = bone.local_axes.and_then(|axes| {
+                    let x = axes.x.try_normalize()?;
+                    let y = axes.z.cross(x).try_normalize()?;
+                    Some(glam::Mat3::from_cols(x, y, x.cross(y)))
+                });
 
-        let mut pmx_morphs_r = MorphReader::<_>::new(pmx_bones_r)?;
-        let _pmx_morphs = pmx_morphs_r
-            .iter::<mmd::DefaultConfig>()
-            .collect::<mmd::Result<Vec<_>>>()?;
+                Ok(BoneData {
+                    name: bone.name.local.clone(),
+                    parent: check_bone(bone.parent)?,
+                    rest_position: bone.position,
+                    layer: bone.layer,
+                    deforms_after_physics: bone.flags.contains(PmxBoneFlags::PHYSICS_AFTER_DEFORM),
+                    ik,
+                    inherit,
+                    fixed_axis: bone.fixed_axis.and_then(glam::Vec3::try_normalize),
+                    local_axes,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
         let mut materials = Vec::new();
         let mut meshes = Vec::new();
-        let mut triangle_index_offset = 0;
-        for (m_i, m) in pmx_materials.iter().enumerate() {
-            let diffuse_texture =
-                self.load_diffuse_texture(&texture_list[m.texture_index as usize], device, queue)?;
-            let size = diffuse_texture.size();
+        let mut face_offset = 0;
+        // where each PMX vertex ended up, as (mesh, vertex) pairs. Vertices
+        // shared between materials are duplicated.
+        let mut vertex_locations = vec![Vec::new(); pmx.vertices.len()];
+        for (m_i, m) in pmx.materials.iter().enumerate() {
+            let diffuse_texture = match m.texture {
+                Some(texture_index) => {
+                    match self.load_texture(filename, Self::texture_path(&pmx, texture_index)?) {
+                        Ok(texture) => Some(texture),
+                        Err(e) => {
+                            log::warn!(
+                                "[PmxLoader::load_source_model_data] no texture for {:?}: {}",
+                                m.name.local,
+                                e
+                            );
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
 
-            let normal_texture = new_flat_normal_texture(device, queue, size.width, size.height);
+            let toon_texture = match m.toon {
+                PmxToon::Texture(None) => None,
+                PmxToon::Texture(Some(texture_index)) => {
+                    let path = Self::texture_path(&pmx, texture_index)?;
+                    // models often refer to the shared toons by file name.
+                    let shared_index = (1..=10u8)
+                        .find(|n| path.eq_ignore_ascii_case(&format!("toon{:02}.bmp", n)));
+                    match (self.load_texture(filename, path), shared_index) {
+                        (Ok(texture), _) => Some(texture),
+                        (Err(_), Some(n)) => Some(shared_toon_texture(n - 1)),
+                        (Err(e), None) => {
+                            log::warn!(
+                                "[PmxLoader::load_source_model_data] no toon for {:?}: {}",
+                                m.name.local,
+                                e
+                            );
+                            None
+                        }
+                    }
+                }
+                PmxToon::Shared(index) => {
+                    // a copy next to the model wins, as in MMD.
+                    let path = format!("toon{:02}.bmp", index as u32 + 1);
+                    Some(
+                        self.load_texture(filename, &path)
+                            .unwrap_or_else(|_| shared_toon_texture(index)),
+                    )
+                }
+            };
 
-            materials.push(Material::new(
-                device,
-                &m.local_name,
+            let sphere_mode = match m.sphere_mode {
+                PmxSphereMode::Disabled => None,
+                PmxSphereMode::Multiply => Some(SphereMapMode::Multiply),
+                PmxSphereMode::Add => Some(SphereMapMode::Add),
+                PmxSphereMode::SubTexture => Some(SphereMapMode::SubTexture),
+            };
+            let sphere_map = match (m.sphere_texture, sphere_mode) {
+                (Some(texture_index), Some(mode)) => {
+                    match self.load_texture(filename, Self::texture_path(&pmx, texture_index)?) {
+                        Ok(texture) => Some(SphereMapData { texture, mode }),
+                        Err(e) => {
+                            log::warn!(
+                                "[PmxLoader::load_source_model_data] no sphere map for {:?}: {}",
+                                m.name.local,
+                                e
+                            );
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            materials.push(MaterialData {
+                name: m.name.local.clone(),
                 diffuse_texture,
-                normal_texture,
-                texture_bind_group_layout,
-            ));
+                normal_texture: None,
+                diffuse_color: m.diffuse,
+                specular_color: m.specular,
+                specular_power: m.specular_strength,
+                ambient_color: m.ambient,
+                toon_texture,
+                sphere_map,
+                shading: MaterialShading::Toon {
+                    edge: m
+                        .flags
+                        .contains(PmxMaterialFlags::HAS_EDGE)
+                        .then_some(EdgeData {
+                            color: m.edge_color,
+                            size: m.edge_size,
+                        }),
+                },
+                is_double_sided: m.flags.contains(PmxMaterialFlags::NO_CULL),
+                // MMD blends every material, in material order.
+                blend_mode: BlendMode::AlphaBlend,
+                writes_depth: true,
+            });
+
+            let face_count = (m.index_count / 3) as usize;
+            let faces = pmx
+                .faces
+                .get(face_offset..face_offset + face_count)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Faces out of range in material {:?}", m.name.local)
+                })?;
 
             let mut global_to_local_vertex_index_map = std::collections::HashMap::new();
             let mut vertices = Vec::new();
+            let mut skin = Vec::new();
             let mut indices = Vec::new();
 
-            // `surface` here means vertex, not triangle. what?
-            let triangle_count = (m.surface_count / 3) as usize;
-
-            for i in 0..triangle_count {
-                let surface = &pmx_surfaces[triangle_index_offset + i];
-                for &global_vertex_index in &surface[..] {
-                    let local_vertex_index = if let Some(&local_index) =
-                        global_to_local_vertex_index_map.get(&global_vertex_index)
-                    {
-                        local_index
-                    } else {
-                        let v = &pmx_vertices[global_vertex_index as usize];
-                        let vertex = ModelVertex {
-                            position: v.position.into(),
-                            tex_coords: [v.uv[0], v.uv[1]].into(),
-                            normal: v.normal.into(),
-                            tangent: glam::Vec3::ZERO,
-                            bitangent: glam::Vec3::ZERO,
-                        };
-                        let local_index = vertices.len() as u32;
-                        vertices.push(vertex);
-                        global_to_local_vertex_index_map.insert(global_vertex_index, local_index);
-                        local_index
+            for &global_vertex_index in faces.iter().flatten() {
+                let local_vertex_index = if let Some(&local_index) =
+                    global_to_local_vertex_index_map.get(&global_vertex_index)
+                {
+                    local_index
+                } else {
+                    let v = pmx
+                        .vertices
+                        .get(global_vertex_index as usize)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("Vertex index out of range: {}", global_vertex_index)
+                        })?;
+                    let vertex = VertexData {
+                        position: v.position,
+                        tex_coords: v.uv,
+                        tex_coords_1: v.additional_uvs[0].xy(),
+                        normal: v.normal,
+                        tangent: glam::Vec3::ZERO,
+                        bitangent: glam::Vec3::ZERO,
+                        edge_scale: v.edge_scale,
                     };
-                    indices.push(local_vertex_index);
-                }
+                    let local_index = vertices.len() as u32;
+                    vertices.push(vertex);
+                    skin.push(vertex_skin(&v.deform, check_bone)?);
+                    global_to_local_vertex_index_map.insert(global_vertex_index, local_index);
+                    vertex_locations[global_vertex_index as usize].push((m_i, local_index));
+                    local_index
+                };
+                indices.push(local_vertex_index);
             }
 
             calculate_tangent_and_bitangent(&mut vertices, &indices);
 
-            meshes.push(Mesh::new(
-                &format!("#{}/{}", self.res_loader.name(), filename),
-                device,
-                &vertices,
-                &indices,
-                m_i,
-            ));
+            meshes.push(MeshData {
+                name: m.name.local.clone(),
+                vertices,
+                indices,
+                material_index: m_i,
+                skin: (bone_count > 0).then_some(skin),
+            });
+
+            face_offset += face_count;
+        }
+
+        let morphs = pmx
+            .morphs
+            .iter()
+            .map(|morph| {
+                Ok(MorphData {
+                    name: morph.name.local.clone(),
+                    kind: morph_kind(&pmx, &morph.offsets, &vertex_locations, check_bone)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let rigid_bodies = pmx
+            .rigid_bodies
+            .iter()
+            .map(|body| {
+                Ok(RigidBodyData {
+                    name: body.name.local.clone(),
+                    bone: check_bone(body.bone)?,
+                    group: body.group.min(15),
+                    collision_mask: body.collision_mask,
+                    shape: match body.shape {
+                        PmxRigidBodyShape::Sphere => RigidBodyShape::Sphere {
+                            radius: body.size.x,
+                        },
+                        PmxRigidBodyShape::Box => RigidBodyShape::Box {
+                            half_extents: body.size,
+                        },
+                        PmxRigidBodyShape::Capsule => RigidBodyShape::Capsule {
+                            radius: body.size.x,
+                            height: body.size.y,
+                        },
+                    },
+                    position: body.position,
+                    rotation: from_euler_yxz(body.rotation),
+                    mass: body.mass,
+                    linear_damping: body.linear_damping,
+                    angular_damping: body.angular_damping,
+                    restitution: body.restitution,
+                    friction: body.friction,
+                    mode: match body.mode {
+                        PmxRigidBodyMode::FollowBone => RigidBodyMode::FollowBone,
+                        PmxRigidBodyMode::Physics => RigidBodyMode::Physics,
+                        PmxRigidBodyMode::PhysicsWithBonePosition => {
+                            RigidBodyMode::PhysicsWithBonePosition
+                        }
+                    },
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-            triangle_index_offset += triangle_count;
+        let mut joints = Vec::with_capacity(pmx.joints.len());
+        for joint in &pmx.joints {
+            if !matches!(joint.kind, PmxJointKind::Spring6Dof | PmxJointKind::SixDof) {
+                log::warn!(
+                    "[PmxLoader::load_source_model_data] unsupported {:?} joint {:?}",
+                    joint.kind,
+                    joint.name.local
+                );
+                continue;
+            }
+
+            let [Some(rigid_body_a), Some(rigid_body_b)] = joint.rigid_bodies else {
+                continue;
+            };
+            let rigid_bodies = [rigid_body_a as usize, rigid_body_b as usize];
+            if let Some(&index) = rigid_bodies
+                .iter()
+                .find(|&&index| index >= pmx.rigid_bodies.len())
+            {
+                anyhow::bail!("Rigid body index out of range: {}", index);
+            }
+
+            joints.push(JointData {
+                name: joint.name.local.clone(),
+                rigid_bodies,
+                position: joint.position,
+                rotation: from_euler_yxz(joint.rotation),
+                linear_min: joint.linear_min,
+                linear_max: joint.linear_max,
+                angular_min: joint.angular_min,
+                angular_max: joint.angular_max,
+                linear_spring: joint.linear_spring,
+                angular_spring: joint.angular_spring,
+            });
         }
 
-        Ok(Model::new(meshes, materials))
+        Ok(ModelData {
+            name: format!("#{}/{}", self.res_loader.name(), filename),
+            meshes,
+            materials,
+            bones,
+            morphs,
+            rigid_bodies,
+            joints,
+            motion_scale: 1.0,
+        })
     }
 }
 
 impl<T: FsAccessor> PmxLoader<T> {
-    fn load_diffuse_texture(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) -> anyhow::Result<textures::D2DiffuseTexture> {
-        let data = self.res_loader.load_binary(filename)?;
-        let texture =
-            textures::D2DiffuseTexture::from_image_in_memory(filename, device, queue, &data)?;
-        Ok(texture)
+    fn texture_path(pmx: &PmxModel, texture_index: u32) -> anyhow::Result<&str> {
+        pmx.textures
+            .get(texture_index as usize)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow::anyhow!("Texture index out of range: {}", texture_index))
     }
-    #[allow(unused)]
-    fn load_normal_texture(
-        &self,
-        filename: &str,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) -> anyhow::Result<textures::D2NormalTexture> {
-        let data = self.res_loader.load_binary(filename)?;
-        let texture =
-            textures::D2NormalTexture::from_image_in_memory(filename, device, queue, &data)?;
-        Ok(texture)
+
+    /// `path` is relative to the model at `model_filename`.
+    fn load_texture(&self, model_filename: &str, path: &str) -> anyhow::Result<TextureData> {
+        let filename = relative_to(model_filename, path);
+        let data = self.res_loader.load_binary(&filename)?;
+        Ok(TextureData::from_file(&filename, data))
+    }
+}
+
+/// `None` for chains without a target, which MMD ignores.
+fn ik_data(
+    ik: &PmxIk,
+    check_bone: impl Fn(Option<u32>) -> anyhow::Result<Option<usize>>,
+) -> anyhow::Result<Option<IkData>> {
+    let Some(target) = check_bone(ik.target)? else {
+        return Ok(None);
+    };
+
+    let mut links = Vec::with_capacity(ik.links.len());
+    for link in &ik.links {
+        if let Some(bone) = check_bone(link.bone)? {
+            links.push(IkLinkData {
+                bone,
+                limits: link.limits.map(|limits| IkLimitsData {
+                    min: limits.min,
+                    max: limits.max,
+                }),
+            });
+        }
+    }
+
+    Ok(Some(IkData {
+        target,
+        loop_count: ik.loop_count.max(0) as u32,
+        limit_angle: ik.limit_angle,
+        links,
+    }))
+}
+
+/// Flip and impulse morphs are [`MorphKind::Unsupported`], as are UV morphs
+/// of the additional UV channels beyond the first.
+fn morph_kind(
+    pmx: &PmxModel,
+    offsets: &PmxMorphOffsets,
+    vertex_locations: &[Vec<(usize, u32)>],
+    check_bone: impl Fn(Option<u32>) -> anyhow::Result<Option<usize>>,
+) -> anyhow::Result<MorphKind> {
+    let locations = |vertex: u32| {
+        vertex_locations
+            .get(vertex as usize)
+            .ok_or_else(|| anyhow::anyhow!("Vertex index out of range: {}", vertex))
+    };
+
+    let kind = match offsets {
+        PmxMorphOffsets::Group(offsets) => {
+            let mut group_offsets = Vec::with_capacity(offsets.len());
+            for offset in offsets {
+                match offset.morph {
+                    Some(morph) if morph as usize >= pmx.morphs.len() => {
+                        anyhow::bail!("Morph index out of range: {}", morph)
+                    }
+                    Some(morph) => group_offsets.push(GroupMorphOffsetData {
+                        morph: morph as usize,
+                        weight: offset.weight,
+                    }),
+                    None => {}
+                }
+            }
+            MorphKind::Group(group_offsets)
+        }
+        PmxMorphOffsets::Vertex(offsets) => {
+            let mut vertex_offsets = Vec::with_capacity(offsets.len());
+            for offset in offsets {
+                for &(mesh, vertex) in locations(offset.vertex)? {
+                    vertex_offsets.push(VertexMorphOffsetData {
+                        mesh,
+                        vertex,
+                        position: offset.translation,
+                    });
+                }
+            }
+            MorphKind::Vertex(vertex_offsets)
+        }
+        PmxMorphOffsets::Uv { channel, offsets } if *channel <= 1 => {
+            let mut uv_offsets = Vec::with_capacity(offsets.len());
+            for offset in offsets {
+                let (tex_coords, tex_coords_1) = match channel {
+                    0 => (offset.offset.xy(), glam::Vec2::ZERO),
+                    _ => (glam::Vec2::ZERO, offset.offset.xy()),
+                };
+                for &(mesh, vertex) in locations(offset.vertex)? {
+                    uv_offsets.push(UvMorphOffsetData {
+                        mesh,
+                        vertex,
+                        tex_coords,
+                        tex_coords_1,
+                    });
+                }
+            }
+            MorphKind::Uv(uv_offsets)
+        }
+        PmxMorphOffsets::Bone(offsets) => {
+            let mut bone_offsets = Vec::with_capacity(offsets.len());
+            for offset in offsets {
+                if let Some(bone) = check_bone(offset.bone)? {
+                    bone_offsets.push(BoneMorphOffsetData {
+                        bone,
+                        translation: offset.translation,
+                        rotation: offset.rotation.normalize(),
+                    });
+                }
+            }
+            MorphKind::Bone(bone_offsets)
+        }
+        PmxMorphOffsets::Material(offsets) => {
+            let mut material_offsets = Vec::with_capacity(offsets.len());
+            for offset in offsets {
+                let material = match offset.material {
+                    Some(material) if material as usize >= pmx.materials.len() => {
+                        anyhow::bail!("Material index out of range: {}", material)
+                    }
+                    material => material.map(|material| material as usize),
+                };
+                material_offsets.push(MaterialMorphOffsetData {
+                    material,
+                    operation: match offset.operation {
+                        PmxMaterialMorphOperation::Multiply => MaterialMorphOperation::Multiply,
+                        PmxMaterialMorphOperation::Add => MaterialMorphOperation::Add,
+                    },
+                    diffuse_color: offset.diffuse,
+                    specular_color: offset.specular,
+                    specular_power: offset.specular_strength,
+                    ambient_color: offset.ambient,
+                    edge_color: offset.edge_color,
+                    edge_size: offset.edge_size,
+                    texture_tint: offset.texture_tint,
+                    sphere_tint: offset.sphere_tint,
+                    toon_tint: offset.toon_tint,
+                });
+            }
+            MorphKind::Material(material_offsets)
+        }
+        PmxMorphOffsets::Uv { .. } | PmxMorphOffsets::Flip(_) | PmxMorphOffsets::Impulse(_) => {
+            MorphKind::Unsupported
+        }
+    };
+
+    Ok(kind)
+}
+
+/// PMX rotates rigid bodies and joints around Y, then X, then Z.
+fn from_euler_yxz(angles: glam::Vec3) -> glam::Quat {
+    glam::Quat::from_euler(glam::EulerRot::YXZ, angles.y, angles.x, angles.z)
+}
+
+/// QDEF is skinned like BDEF4.
+fn vertex_skin(
+    deform: &PmxVertexDeform,
+    check_bone: impl Fn(Option<u32>) -> anyhow::Result<Option<usize>>,
+) -> anyhow::Result<VertexSkinData> {
+    let (bones, weights, sdef) = match *deform {
+        PmxVertexDeform::Bdef1 { bone } => ([bone, None, None, None], [1.0, 0.0, 0.0, 0.0], None),
+        PmxVertexDeform::Bdef2 { bones, weight } => (
+            [bones[0], bones[1], None, None],
+            [weight, 1.0 - weight, 0.0, 0.0],
+            None,
+        ),
+        PmxVertexDeform::Bdef4 { bones, weights } | PmxVertexDeform::Qdef { bones, weights } => {
+            (bones, weights, None)
+        }
+        PmxVertexDeform::Sdef {
+            bones,
+            weight,
+            c,
+            r0,
+            r1,
+        } => (
+            [bones[0], bones[1], None, None],
+            [weight, 1.0 - weight, 0.0, 0.0],
+            Some(SdefData { c, r0, r1 }),
+        ),
+    };
+
+    let mut skin = VertexSkinData {
+        bones: [0; 4],
+        weights: [0.0; 4],
+        sdef,
+    };
+    for i in 0..4 {
+        if let Some(bone) = check_bone(bones[i])? {
+            skin.bones[i] = bone as u32;
+            skin.weights[i] = weights[i];
+        }
+    }
+
+    let weight_sum = skin.weights.iter().sum::<f32>();
+    if weight_sum > 0.0 {
+        for weight in &mut skin.weights {
+            *weight /= weight_sum;
+        }
+    }
+
+    Ok(skin)
+}
+
+/// Shadow colors of the toon textures MMD ships as `toon01.bmp` to
+/// `toon10.bmp`. Those files can't be redistributed, so these are eyeballed.
+const SHARED_TOON_SHADOW_COLORS: [[u8; 3]; 10] = [
+    [205, 205, 205],
+    [245, 218, 208],
+    [178, 178, 178],
+    [242, 234, 204],
+    [224, 224, 224],
+    [216, 204, 198],
+    [153, 153, 153],
+    [236, 216, 198],
+    [242, 242, 242],
+    [229, 219, 214],
+];
+
+/// A 1x32 ramp from white to the shadow color, with a soft edge in the middle.
+fn shared_toon_texture(index: u8) -> TextureData {
+    const HEIGHT: u32 = 32;
+
+    let shadow = SHARED_TOON_SHADOW_COLORS
+        .get(index as usize)
+        .copied()
+        .unwrap_or([255, 255, 255]);
+
+    let pixels = (0..HEIGHT)
+        .flat_map(|y| {
+            let t = y as f32 / (HEIGHT - 1) as f32;
+            let shadow_amount = ((t - 0.45) / 0.15).clamp(0.0, 1.0);
+            let mut rgba = [255; 4];
+            for (c, shadow_c) in rgba.iter_mut().zip(shadow) {
+                *c = (255.0 + (shadow_c as f32 - 255.0) * shadow_amount).round() as u8;
+            }
+            rgba
+        })
+        .collect();
+
+    TextureData {
+        path: None,
+        image: ImageData::Rgba8 {
+            width: 1,
+            height: HEIGHT,
+            pixels,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::fs_accessors::testing::MemoryFsAccessor;
+
+    /// A UTF-8 PMX 2.0 file with 1 byte indices, holding nothing but
+    /// unattached sphere bodies in `group`s with `collision_mask`s.
+    fn pmx_with_bodies(bodies: &[(u8, u16)]) -> Vec<u8> {
+        let mut bytes = b"PMX ".to_vec();
+        bytes.extend(2.0_f32.to_le_bytes());
+        bytes.extend([8, 1, 0, 1, 1, 1, 1, 1, 1]);
+        let empty_text = 0_i32.to_le_bytes();
+        // names and comments.
+        for _ in 0..4 {
+            bytes.extend(empty_text);
+        }
+        // vertices, faces, textures, materials, bones, morphs and display
+        // frames.
+        for _ in 0..7 {
+            bytes.extend(0_i32.to_le_bytes());
+        }
+        bytes.extend((bodies.len() as i32).to_le_bytes());
+        for &(group, collision_mask) in bodies {
+            bytes.extend(empty_text);
+            bytes.extend(empty_text);
+            bytes.push(0xFF); // no bone.
+            bytes.push(group);
+            bytes.extend(collision_mask.to_le_bytes());
+            bytes.push(0); // sphere.
+            // size, position, rotation, mass, dampings, restitution and
+            // friction.
+            for value in [[1.0, 0.0, 0.0], [0.0; 3], [0.0; 3]].concat() {
+                bytes.extend(f32::to_le_bytes(value));
+            }
+            for value in [1.0, 0.5, 0.5, 0.0, 0.5] {
+                bytes.extend(f32::to_le_bytes(value));
+            }
+            bytes.push(1); // physics.
+        }
+        // joints.
+        bytes.extend(0_i32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn collision_masks_are_the_groups_bodies_collide_with() {
+        let pmx = pmx_with_bodies(&[(0, 0xFFFE), (3, 0xFFFF), (20, 0)]);
+        let fs = MemoryFsAccessor::new([("bodies.pmx", pmx.as_slice())]);
+        let model_data = PmxLoader::new(fs).load_model_data("bodies.pmx").unwrap();
+
+        assert_eq!(
+            model_data
+                .rigid_bodies
+                .iter()
+                .map(|body| (body.group, body.collision_mask))
+                .collect::<Vec<_>>(),
+            [(0, 0xFFFE), (3, 0xFFFF), (15, 0)]
+        );
     }
 }
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bounds::Aabb,
+    model_loaders::model_data::{ModelData, MorphKind, RigidBodyShape},
+};
+
+/// The coordinate system a format stores its models in. Engine space is
+/// right-handed and Y up, in meters, with counter-clockwise front faces.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SourceSpace {
+    pub handedness: Handedness,
+    pub up_axis: UpAxis,
+    /// Meters per unit.
+    pub unit_scale: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Handedness {
+    Right,
+    /// Converted by flipping the depth axis, the one that is neither X nor
+    /// up. Front faces turn from counter-clockwise to clockwise with it.
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UpAxis {
+    Y,
+    /// With Y pointing away from the viewer, as in Blender.
+    Z,
+}
+
+impl SourceSpace {
+    pub const ENGINE: Self = Self {
+        handedness: Handedness::Right,
+        up_axis: UpAxis::Y,
+        unit_scale: 1.0,
+    };
+
+    /// PMX and VMD files, in which a unit is about 8cm.
+    pub const MMD: Self = Self {
+        handedness: Handedness::Left,
+        up_axis: UpAxis::Y,
+        unit_scale: 0.08,
+    };
+
+    pub(crate) fn conversion(&self) -> SpaceConversion {
+        let up = match self.up_axis {
+            UpAxis::Y => glam::Mat3::IDENTITY,
+            UpAxis::Z => glam::Mat3::from_cols(glam::Vec3::X, glam::Vec3::NEG_Z, glam::Vec3::Y),
+        };
+        let mirror = match (self.handedness, self.up_axis) {
+            (Handedness::Right, _) => glam::Mat3::IDENTITY,
+            (Handedness::Left, UpAxis::Y) => glam::Mat3::from_diagonal(glam::vec3(1.0, 1.0, -1.0)),
+            (Handedness::Left, UpAxis::Z) => glam::Mat3::from_diagonal(glam::vec3(1.0, -1.0, 1.0)),
+        };
+        SpaceConversion::new(up * mirror, self.unit_scale, glam::Vec3::ZERO)
+    }
+}
+
+/// Maps points to `scale * basis * point + offset`, where `basis` is a
+/// rotation, possibly combined with a mirror.
+pub(crate) struct SpaceConversion {
+    basis: glam::Mat3,
+    /// Acts on rotations as `basis` does. A mirror is a rotation followed by
+    /// negating every axis, which leaves rotations alone.
+    rotation: glam::Quat,
+    scale: f32,
+    offset: glam::Vec3,
+}
+
+impl SpaceConversion {
+    fn new(basis: glam::Mat3, scale: f32, offset: glam::Vec3) -> Self {
+        let rotation = if basis.determinant() < 0.0 {
+            glam::Quat::from_mat3(&-basis)
+        } else {
+            glam::Quat::from_mat3(&basis)
+        };
+        Self {
+            basis,
+            rotation,
+            scale,
+            offset,
+        }
+    }
+
+    fn is_mirror(&self) -> bool {
+        self.basis.determinant() < 0.0
+    }
+
+    fn point(&self, point: glam::Vec3) -> glam::Vec3 {
+        self.vector(point) + self.offset
+    }
+
+    /// For offsets between points.
+    pub fn vector(&self, vector: glam::Vec3) -> glam::Vec3 {
+        self.scale * (self.basis * vector)
+    }
+
+    /// For normals and axes, which are not scaled.
+    fn direction(&self, direction: glam::Vec3) -> glam::Vec3 {
+        self.basis * direction
+    }
+
+    pub fn rotation(&self, rotation: glam::Quat) -> glam::Quat {
+        self.rotation * rotation * self.rotation.inverse()
+    }
+
+    /// Converts per-axis ranges of Euler angles, as those of IK links. The
+    /// order the angles are applied in only stays the same if the up axis
+    /// does.
+    fn angle_range(&self, min: glam::Vec3, max: glam::Vec3) -> (glam::Vec3, glam::Vec3) {
+        let mirror_sign = if self.is_mirror() { -1.0 } else { 1.0 };
+        let mut range = (glam::Vec3::ZERO, glam::Vec3::ZERO);
+        for axis in 0..3 {
+            let column = self.basis.col(axis);
+            let converted_axis = column.abs().max_position();
+            if column[converted_axis] * mirror_sign > 0.0 {
+                range.0[converted_axis] = min[axis];
+                range.1[converted_axis] = max[axis];
+            } else {
+                range.0[converted_axis] = -max[axis];
+                range.1[converted_axis] = -min[axis];
+            }
+        }
+        range
+    }
+}
+
+impl ModelData {
+    /// Converts everything from `source_space` to engine space, flipping
+    /// the winding of triangles if the conversion mirrors them.
+    pub fn convert_to_engine_space(&mut self, source_space: &SourceSpace) {
+        if *source_space != SourceSpace::ENGINE {
+            self.convert(&source_space.conversion());
+        }
+    }
+
+    /// Moves the center of the bounding box of all vertices to the origin
+    /// and scales the model uniformly so that its longest side is `size`.
+    /// Does nothing to models without vertices.
+    pub fn fit(&mut self, size: f32) {
+        let bounds = Aabb::from_points(
+            self.meshes
+                .iter()
+                .flat_map(|mesh| &mesh.vertices)
+                .map(|vertex| vertex.position),
+        );
+        if bounds.is_empty() {
+            return;
+        }
+
+        let longest_side = bounds.size().max_element();
+        let scale = if longest_side > 0.0 {
+            size / longest_side
+        } else {
+            1.0
+        };
+        self.convert(&SpaceConversion::new(
+            glam::Mat3::IDENTITY,
+            scale,
+            -bounds.center() * scale,
+        ));
+        self.motion_scale *= scale;
+    }
+
+    fn convert(&mut self, conversion: &SpaceConversion) {
+        for mesh in &mut self.meshes {
+            for vertex in &mut mesh.vertices {
+                vertex.position = conversion.point(vertex.position);
+                vertex.normal = conversion.direction(vertex.normal);
+                vertex.tangent = conversion.direction(vertex.tangent);
+                vertex.bitangent = conversion.direction(vertex.bitangent);
+            }
+            if conversion.is_mirror() {
+                for triangle in mesh.indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+            for skin in mesh.skin.iter_mut().flatten() {
+                if let Some(sdef) = &mut skin.sdef {
+                    sdef.c = conversion.point(sdef.c);
+                    sdef.r0 = conversion.point(sdef.r0);
+                    sdef.r1 = conversion.point(sdef.r1);
+                }
+            }
+        }
+
+        for bone in &mut self.bones {
+            bone.rest_position = conversion.point(bone.rest_position);
+            bone.fixed_axis = bone.fixed_axis.map(|axis| conversion.direction(axis));
+            bone.local_axes = bone.local_axes.map(|axes| {
+                let rotation = glam::Mat3::from_quat(conversion.rotation);
+                rotation * axes * rotation.transpose()
+            });
+            for link in bone.ik.iter_mut().flat_map(|ik| &mut ik.links) {
+                if let Some(limits) = &mut link.limits {
+                    (limits.min, limits.max) = conversion.angle_range(limits.min, limits.max);
+                }
+            }
+        }
+
+        for morph in &mut self.morphs {
+            match &mut morph.kind {
+                MorphKind::Vertex(offsets) => {
+                    for offset in offsets {
+                        offset.position = conversion.vector(offset.position);
+                    }
+                }
+                MorphKind::Bone(offsets) => {
+                    for offset in offsets {
+                        offset.translation = conversion.vector(offset.translation);
+                        offset.rotation = conversion.rotation(offset.rotation);
+                    }
+                }
+                MorphKind::Group(_)
+                | MorphKind::Uv(_)
+                | MorphKind::Material(_)
+                | MorphKind::Unsupported => {}
+            }
+        }
+
+        // bodies and joints keep their own frames, only rotated, so that
+        // shapes and joint limits along their axes stay valid. Shapes are
+        // point symmetric, so a mirror doesn't change them.
+        for body in &mut self.rigid_bodies {
+            body.position = conversion.point(body.position);
+            body.rotation = conversion.rotation * body.rotation;
+            body.shape = match body.shape {
+                RigidBodyShape::Sphere { radius } => RigidBodyShape::Sphere {
+                    radius: radius * conversion.scale,
+                },
+                RigidBodyShape::Box { half_extents } => RigidBodyShape::Box {
+                    half_extents: half_extents * conversion.scale,
+                },
+                RigidBodyShape::Capsule { radius, height } => RigidBodyShape::Capsule {
+                    radius: radius * conversion.scale,
+                    height: height * conversion.scale,
+                },
+            };
+        }
+        for joint in &mut self.joints {
+            joint.position = conversion.point(joint.position);
+            joint.rotation = conversion.rotation * joint.rotation;
+            // in a joint's frame, a mirror negates every axis.
+            (joint.linear_min, joint.linear_max) = if conversion.is_mirror() {
+                (-joint.linear_max, -joint.linear_min)
+            } else {
+                (joint.linear_min, joint.linear_max)
+            };
+            joint.linear_min *= conversion.scale;
+            joint.linear_max *= conversion.scale;
+            // torque per radian grows with the square of lengths, as the
+            // inertia of the bodies does. Force per length doesn't change.
+            joint.angular_spring *= conversion.scale * conversion.scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loaders::model_data::{MeshData, VertexData};
+
+    /// A triangle in the XY plane, counter-clockwise seen from +Z, with its
+    /// normals facing +Z.
+    fn triangle_model_data() -> ModelData {
+        let vertex = |position: glam::Vec3| VertexData {
+            position,
+            tex_coords: glam::Vec2::ZERO,
+            tex_coords_1: glam::Vec2::ZERO,
+            normal: glam::Vec3::Z,
+            tangent: glam::Vec3::X,
+            bitangent: glam::Vec3::Y,
+            edge_scale: 1.0,
+        };
+        ModelData {
+            name: "triangle".to_string(),
+            meshes: vec![MeshData {
+                name: "triangle".to_string(),
+                vertices: vec![
+                    vertex(glam::vec3(0.0, 0.0, 1.0)),
+                    vertex(glam::vec3(1.0, 0.0, 1.0)),
+                    vertex(glam::vec3(0.0, 1.0, 1.0)),
+                ],
+                indices: vec![0, 1, 2],
+                material_index: 0,
+                skin: None,
+            }],
+            materials: vec![],
+            bones: vec![],
+            morphs: vec![],
+            rigid_bodies: vec![],
+            joints: vec![],
+            motion_scale: 1.0,
+        }
+    }
+
+    /// The normal of the first triangle as given by its winding, assuming
+    /// counter-clockwise front faces.
+    fn winding_normal(model_data: &ModelData) -> glam::Vec3 {
+        let mesh = &model_data.meshes[0];
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[mesh.indices[i] as usize].position);
+        (b - a).cross(c - a).normalize()
+    }
+
+    #[test]
+    fn engine_space_is_left_alone() {
+        let mut model_data = triangle_model_data();
+        model_data.convert_to_engine_space(&SourceSpace::ENGINE);
+        assert_eq!(model_data, triangle_model_data());
+    }
+
+    #[test]
+    fn left_handed_flips_depth_and_winding() {
+        let mut model_data = triangle_model_data();
+        model_data.convert_to_engine_space(&SourceSpace::MMD);
+
+        let mesh = &model_data.meshes[0];
+        assert_eq!(mesh.vertices[1].position, glam::vec3(0.08, 0.0, -0.08));
+        assert_eq!(mesh.vertices[0].normal, glam::Vec3::NEG_Z);
+        assert_eq!(mesh.indices, [0, 2, 1]);
+        // still facing where its normals do.
+        assert!(winding_normal(&model_data).abs_diff_eq(glam::Vec3::NEG_Z, 1e-6));
+    }
+
+    #[test]
+    fn z_up_rotates_without_flipping_winding() {
+        let mut model_data = triangle_model_data();
+        model_data.convert_to_engine_space(&SourceSpace {
+            handedness: Handedness::Right,
+            up_axis: UpAxis::Z,
+            unit_scale: 1.0,
+        });
+
+        let mesh = &model_data.meshes[0];
+        // source up (+Z) becomes engine up, source +Y points away from the
+        // viewer, which is engine -Z.
+        assert!(mesh.vertices[0].position.abs_diff_eq(glam::Vec3::Y, 1e-6));
+        assert!(
+            mesh.vertices[2]
+                .position
+                .abs_diff_eq(glam::vec3(0.0, 1.0, -1.0), 1e-6)
+        );
+        assert!(mesh.vertices[0].normal.abs_diff_eq(glam::Vec3::Y, 1e-6));
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert!(winding_normal(&model_data).abs_diff_eq(glam::Vec3::Y, 1e-6));
+    }
+
+    #[test]
+    fn left_handed_z_up_is_a_mirror() {
+        let mut model_data = triangle_model_data();
+        model_data.convert_to_engine_space(&SourceSpace {
+            handedness: Handedness::Left,
+            up_axis: UpAxis::Z,
+            unit_scale: 1.0,
+        });
+
+        let mesh = &model_data.meshes[0];
+        assert!(
+            mesh.vertices[2]
+                .position
+                .abs_diff_eq(glam::vec3(0.0, 1.0, 1.0), 1e-6)
+        );
+        assert_eq!(mesh.indices, [0, 2, 1]);
+        assert!(winding_normal(&model_data).abs_diff_eq(mesh.vertices[0].normal, 1e-6));
+    }
+}
@@ -1,13 +1,10 @@
-use crate::drawing::models::{Mesh, ModelVertex};
+use crate::model_loaders::model_data::{MeshData, VertexData};
 
 pub struct VirtualLoader;
 
 impl VirtualLoader {
     /// Author: GitHub Copilot.
-    pub fn make_cube_mesh_with_minimal_effort_for_light_source_indicators(
-        device: &wgpu::Device,
-        size: f32,
-    ) -> Mesh {
+    pub fn make_cube_mesh_with_minimal_effort_for_light_source_indicators(size: f32) -> MeshData {
         let half = size / 2.0;
 
         // 8 corner positions
@@ -22,19 +19,23 @@ impl VirtualLoader {
             glam::Vec3::new(half, -half, half),   // 7: h (bottom north-east)
         ];
 
-        let vertices: Vec<ModelVertex> = positions
+        let vertices: Vec<VertexData> = positions
             .iter()
             .map(|&pos| {
-                ModelVertex {
+                VertexData {
                     position: pos,
                     // not used for light source indicators.
                     tex_coords: (0.0, 0.0).into(),
                     // not used for light source indicators.
+                    tex_coords_1: (0.0, 0.0).into(),
+                    // not used for light source indicators.
                     normal: glam::Vec3::ZERO,
                     // not used for light source indicators.
                     tangent: glam::Vec3::ZERO,
                     // not used for light source indicators.
                     bitangent: glam::Vec3::ZERO,
+                    // not used for light source indicators.
+                    edge_scale: 1.0,
                 }
             })
             .collect();
@@ -48,12 +49,13 @@ impl VirtualLoader {
             0, 3, 7, 0, 7, 4, // North face (z = half)
         ];
 
-        Mesh::new(
-            "VirtualLoader::make_cube_mesh_with_minimal_effort_for_light_source_indicators",
-            device,
-            &vertices,
-            &indices,
-            /* not used for light source indicators. */ 0,
-        )
+        MeshData {
+            name: "VirtualLoader::make_cube_mesh_with_minimal_effort_for_light_source_indicators"
+                .to_string(),
+            vertices,
+            indices,
+            /* not used for light source indicators. */ material_index: 0,
+            skin: None,
+        }
     }
 }
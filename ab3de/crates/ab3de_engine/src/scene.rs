@@ -0,0 +1,469 @@
+//! The on-disk scene format. See `ab3de/scenes/` for examples.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    drawing::systems::{camera_system::CameraData, model_system::InstanceTransform},
+    io::fs_accessors::{FsAccessor, zip_fs_accessor::ZipFsAccessor},
+};
+
+pub const SCENE_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    /// Must be [`SCENE_FORMAT_VERSION`]. Version 1 scenes are converted when
+    /// loaded, see [`Self::from_json_str`].
+    pub version: u32,
+    /// Resources are addressed by URIs, which are looked up in these and in
+    /// the built-in resource sets, mounted under `builtin://<set>/`. Later
+    /// mounts shadow earlier ones and the built-in sets.
+    #[serde(default)]
+    pub mounts: Vec<MountDescription>,
+    pub environment: EnvironmentDescription,
+    #[serde(default)]
+    pub models: Vec<ModelDescription>,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+    #[serde(default)]
+    pub camera: Option<CameraDescription>,
+}
+
+impl SceneDescription {
+    /// Also accepts version 1 scenes, whose resource locations are turned
+    /// into URIs: built-in ones into `builtin://<set>/<path>`, and those on
+    /// disk into URIs under a mount added for each directory or archive.
+    pub fn from_json_str(json: &str) -> anyhow::Result<Self> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if value.get("version") == Some(&serde_json::Value::from(1)) {
+            upgrade_from_v1(&mut value)?;
+            log::warn!(
+                "[SceneDescription::from_json_str] Converted a version 1 scene, save it to keep the result"
+            );
+        }
+        let scene: Self = serde_json::from_value(value)?;
+        if scene.version != SCENE_FORMAT_VERSION {
+            anyhow::bail!(
+                "Unsupported scene format version {} (expected {})",
+                scene.version,
+                SCENE_FORMAT_VERSION
+            );
+        }
+        Ok(scene)
+    }
+
+    pub fn to_json_string(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The scene the engine used to hardcode: a grid of bobbing cubes lit by
+    /// a single orbiting light.
+    pub fn demo() -> Self {
+        Self {
+            version: SCENE_FORMAT_VERSION,
+            mounts: vec![],
+            environment: EnvironmentDescription {
+                map: "builtin://sky/pure-sky.hdr".to_string(),
+                cube_size: EnvironmentDescription::DEFAULT_CUBE_SIZE,
+            },
+            models: vec![ModelDescription {
+                name: Some("cube".to_string()),
+                loader: ModelLoaderKind::Obj,
+                resource: "builtin://cube/cube.obj".to_string(),
+                instances: InstancesDescription::DemoGrid { per_row: 10 },
+                motion: None,
+                fit_size: None,
+            }],
+            lights: vec![LightDescription {
+                position: glam::vec3(2.0, 2.0, 2.0),
+                color: glam::Vec3::ONE,
+                orbit_period_s: Some(1.0),
+            }],
+            camera: None,
+        }
+    }
+
+    pub fn camera_data(&self) -> CameraData {
+        match &self.camera {
+            Some(camera) => camera.to_camera_data(),
+            None => CameraData::default(),
+        }
+    }
+}
+
+/// Where version 1 scenes took resources from, before they were addressed
+/// by URIs.
+#[derive(Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case", deny_unknown_fields)]
+enum ResourceLocationV1 {
+    /// `set` is one of `cube`, `aoi` or `sky`.
+    Builtin { set: String, path: String },
+    Directory {
+        root: std::path::PathBuf,
+        path: String,
+    },
+    Zip {
+        archive: std::path::PathBuf,
+        path: String,
+    },
+}
+
+/// Rewrites a version 1 scene as version 2 in place. Each directory and
+/// archive gets its own mount, `directory-<n>://` or `zip-<n>://`.
+fn upgrade_from_v1(scene: &mut serde_json::Value) -> anyhow::Result<()> {
+    let mut mounts: Vec<MountDescription> = vec![];
+    let mut to_uri = |location: &mut serde_json::Value| -> anyhow::Result<()> {
+        let (mount, path) = match ResourceLocationV1::deserialize(location.take())? {
+            ResourceLocationV1::Builtin { set, path } => {
+                *location = format!("builtin://{}/{}", set, path).into();
+                return Ok(());
+            }
+            ResourceLocationV1::Directory { root, path } => (
+                MountDescription::Directory {
+                    prefix: format!("directory-{}://", mounts.len()),
+                    root,
+                    watch: false,
+                },
+                path,
+            ),
+            ResourceLocationV1::Zip { archive, path } => (
+                MountDescription::Zip {
+                    prefix: format!("zip-{}://", mounts.len()),
+                    archive,
+                    opened: None,
+                },
+                path,
+            ),
+        };
+        let same_source = |existing: &MountDescription| match (existing, &mount) {
+            (
+                MountDescription::Directory { root: a, .. },
+                MountDescription::Directory { root: b, .. },
+            ) => a == b,
+            (
+                MountDescription::Zip { archive: a, .. },
+                MountDescription::Zip { archive: b, .. },
+            ) => a == b,
+            _ => false,
+        };
+        let prefix = match mounts.iter().find(|existing| same_source(existing)) {
+            Some(existing) => existing.prefix().to_string(),
+            None => {
+                let prefix = mount.prefix().to_string();
+                mounts.push(mount);
+                prefix
+            }
+        };
+        *location = format!("{}{}", prefix, path).into();
+        Ok(())
+    };
+
+    if let Some(map) = scene.pointer_mut("/environment/map") {
+        to_uri(map)?;
+    }
+    if let Some(models) = scene.get_mut("models").and_then(|m| m.as_array_mut()) {
+        for model in models {
+            if let Some(resource) = model.get_mut("resource") {
+                to_uri(resource)?;
+            }
+            if let Some(motion) = model.get_mut("motion").filter(|m| !m.is_null()) {
+                to_uri(motion)?;
+            }
+        }
+    }
+
+    scene["version"] = SCENE_FORMAT_VERSION.into();
+    scene["mounts"] = serde_json::to_value(mounts)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDescription {
+    /// The URI of an equirectangular `.hdr` image.
+    pub map: String,
+    /// Edge length of each face of the cubemap the map is converted into.
+    #[serde(default = "EnvironmentDescription::default_cube_size")]
+    pub cube_size: u32,
+}
+
+impl EnvironmentDescription {
+    pub const DEFAULT_CUBE_SIZE: u32 = 1080;
+
+    fn default_cube_size() -> u32 {
+        Self::DEFAULT_CUBE_SIZE
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDescription {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub loader: ModelLoaderKind,
+    /// A URI. Textures are looked up relative to it, in the same mount.
+    pub resource: String,
+    pub instances: InstancesDescription,
+    /// The URI of a `.vmd` motion to play on the model's bones and morphs.
+    #[serde(default)]
+    pub motion: Option<String>,
+    /// Recenters the model on the origin and scales it so that its longest
+    /// side is this long, see [`ModelData::fit`](crate::ModelData::fit).
+    #[serde(default)]
+    pub fit_size: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelLoaderKind {
+    Obj,
+    Pmx,
+    /// `.gltf` or `.glb`.
+    Gltf,
+}
+
+impl ModelLoaderKind {
+    /// Guesses the loader from the extension of `path`, ignoring case.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(Self::Obj),
+            "pmx" => Some(Self::Pmx),
+            "gltf" | "glb" => Some(Self::Gltf),
+            _ => None,
+        }
+    }
+}
+
+/// Files to make available under `prefix`, e.g. `project://` or
+/// `models/aoi/`. Relative paths on disk are resolved against the working
+/// directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MountDescription {
+    /// A directory on disk. Files outside it can't be reached.
+    Directory {
+        prefix: String,
+        root: std::path::PathBuf,
+        /// Reloads what was loaded from the directory when its files
+        /// change, see [`Engine::reload_changed`](crate::Engine::reload_changed).
+        #[serde(default)]
+        watch: bool,
+    },
+    /// A `.zip` archive on disk. Names stored as Shift-JIS are decoded, so
+    /// URIs are always UTF-8.
+    Zip {
+        prefix: String,
+        archive: std::path::PathBuf,
+        /// The archive, if it was already read, e.g. to list the models in
+        /// it. Mounted instead of reading `archive` again. Not saved.
+        #[serde(skip)]
+        opened: Option<OpenedZip>,
+    },
+}
+
+impl MountDescription {
+    pub fn prefix(&self) -> &str {
+        match self {
+            Self::Directory { prefix, .. } | Self::Zip { prefix, .. } => prefix,
+        }
+    }
+}
+
+/// A [`ZipFsAccessor`] shared by a [`MountDescription::Zip`] and the engines
+/// built from it. Only equal to its clones.
+#[derive(Clone)]
+pub struct OpenedZip(pub Arc<ZipFsAccessor>);
+
+impl From<ZipFsAccessor> for OpenedZip {
+    fn from(accessor: ZipFsAccessor) -> Self {
+        Self(Arc::new(accessor))
+    }
+}
+
+impl std::fmt::Debug for OpenedZip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OpenedZip").field(&self.0.name()).finish()
+    }
+}
+
+impl PartialEq for OpenedZip {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for OpenedZip {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum InstancesDescription {
+    /// The animated demo grid of `per_row * per_row` instances.
+    DemoGrid { per_row: usize },
+    /// Fixed instances.
+    Transforms {
+        transforms: Vec<TransformDescription>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformDescription {
+    #[serde(default)]
+    pub position: glam::Vec3,
+    /// Euler angles in degrees, applied in Y, X, Z order.
+    #[serde(default)]
+    pub rotation_degrees: glam::Vec3,
+    #[serde(default = "TransformDescription::default_scale")]
+    pub scale: glam::Vec3,
+}
+
+impl TransformDescription {
+    fn default_scale() -> glam::Vec3 {
+        glam::Vec3::ONE
+    }
+
+    pub fn to_instance_transform(&self) -> InstanceTransform {
+        InstanceTransform {
+            position: self.position,
+            rotation: self.rotation(),
+            scale: self.scale,
+        }
+    }
+
+    pub fn rotation(&self) -> glam::Quat {
+        let r = self.rotation_degrees;
+        glam::Quat::from_euler(
+            glam::EulerRot::YXZ,
+            r.y.to_radians(),
+            r.x.to_radians(),
+            r.z.to_radians(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LightDescription {
+    pub position: glam::Vec3,
+    pub color: glam::Vec3,
+    /// If set, the light orbits the Y axis once per this many seconds.
+    #[serde(default)]
+    pub orbit_period_s: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: glam::Vec3,
+    pub yaw_degrees: f32,
+    pub pitch_degrees: f32,
+}
+
+impl CameraDescription {
+    pub fn to_camera_data(&self) -> CameraData {
+        CameraData {
+            position: self.position,
+            yaw_radians: self.yaw_degrees.to_radians(),
+            pitch_radians: self.pitch_degrees.to_radians(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_demo_scene_back() {
+        let json = SceneDescription::demo().to_json_string().unwrap();
+        let scene = SceneDescription::from_json_str(&json).unwrap();
+        assert_eq!(scene.environment.map, "builtin://sky/pure-sky.hdr");
+        assert_eq!(scene.models[0].resource, "builtin://cube/cube.obj");
+    }
+
+    #[test]
+    fn converts_version_1_locations_into_uris() {
+        let json = r#"{
+            "version": 1,
+            "environment": { "map": { "source": "builtin", "set": "sky", "path": "pure-sky.hdr" } },
+            "models": [
+                {
+                    "loader": "pmx",
+                    "resource": { "source": "directory", "root": "models", "path": "aoi\\aoi.pmx" },
+                    "instances": { "kind": "demo_grid", "per_row": 1 },
+                    "motion": { "source": "zip", "archive": "motions.zip", "path": "dance.vmd" }
+                },
+                {
+                    "loader": "obj",
+                    "resource": { "source": "directory", "root": "models", "path": "cube.obj" },
+                    "instances": { "kind": "demo_grid", "per_row": 1 },
+                    "motion": null
+                }
+            ]
+        }"#;
+        let scene = SceneDescription::from_json_str(json).unwrap();
+
+        assert_eq!(scene.version, SCENE_FORMAT_VERSION);
+        assert_eq!(scene.environment.map, "builtin://sky/pure-sky.hdr");
+        assert_eq!(
+            scene.mounts,
+            [
+                MountDescription::Directory {
+                    prefix: "directory-0://".to_string(),
+                    root: "models".into(),
+                    watch: false,
+                },
+                MountDescription::Zip {
+                    prefix: "zip-1://".to_string(),
+                    archive: "motions.zip".into(),
+                    opened: None,
+                },
+            ]
+        );
+        assert_eq!(scene.models[0].resource, "directory-0://aoi\\aoi.pmx");
+        assert_eq!(scene.models[0].motion.as_deref(), Some("zip-1://dance.vmd"));
+        assert_eq!(scene.models[1].resource, "directory-0://cube.obj");
+        assert_eq!(scene.models[1].motion, None);
+    }
+
+    #[test]
+    fn opened_archives_are_shared_but_not_saved() {
+        // an archive with no files is just its end of central directory.
+        let mut bytes = 0x0605_4b50_u32.to_le_bytes().to_vec();
+        bytes.resize(22, 0);
+        let mount = MountDescription::Zip {
+            prefix: "args://0/".to_string(),
+            archive: "model.zip".into(),
+            opened: Some(
+                ZipFsAccessor::from_bytes("model.zip", bytes)
+                    .unwrap()
+                    .into(),
+            ),
+        };
+
+        assert_eq!(mount.clone(), mount);
+        let json = serde_json::to_value(&mount).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "source": "zip",
+                "prefix": "args://0/",
+                "archive": "model.zip",
+            })
+        );
+        let loaded: MountDescription = serde_json::from_value(json).unwrap();
+        assert!(matches!(loaded, MountDescription::Zip { opened: None, .. }));
+        assert_ne!(loaded, mount);
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let mut scene = serde_json::to_value(SceneDescription::demo()).unwrap();
+        scene["version"] = 3.into();
+        let error = SceneDescription::from_json_str(&scene.to_string()).unwrap_err();
+        assert!(error.to_string().contains("version 3"), "{}", error);
+    }
+}
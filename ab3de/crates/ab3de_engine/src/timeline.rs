@@ -0,0 +1,168 @@
+/// The single source of time for everything animated in the engine.
+///
+/// Time is stored as a frame index plus a fractional sub-frame, so that
+/// sampling frame `N` always yields exactly the same [`FrameTime`], no matter
+/// how the timeline got there (real-time playback, seeking, stepping).
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    fps: u32,
+    frame_index: u64,
+    /// The fraction of a frame accumulated during real-time playback, in
+    /// `[0, 1)`. Always `0` right after seeking.
+    subframe: f64,
+
+    is_paused: bool,
+    time_scale: f64,
+}
+
+impl Timeline {
+    pub const DEFAULT_FPS: u32 = 60;
+
+    pub fn new(fps: u32) -> Self {
+        assert!(fps > 0, "fps must be positive");
+
+        Self {
+            fps,
+            frame_index: 0,
+            subframe: 0.0,
+            is_paused: false,
+            time_scale: 1.0,
+        }
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// Changes the frame rate while keeping the current frame boundary in
+    /// time as close as possible.
+    pub fn set_fps(&mut self, fps: u32) {
+        assert!(fps > 0, "fps must be positive");
+
+        let frames = self.time_s() * fps as f64;
+        self.fps = fps;
+        self.frame_index = frames.floor() as u64;
+        self.subframe = frames.fract();
+    }
+
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    pub fn time_s(&self) -> f64 {
+        (self.frame_index as f64 + self.subframe) / self.fps as f64
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Scales how fast [`Self::advance`] moves the timeline. Does not affect
+    /// seeking or stepping.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn seek_frame(&mut self, frame_index: u64) {
+        self.frame_index = frame_index;
+        self.subframe = 0.0;
+    }
+
+    /// Seeks to the frame containing `time_s`.
+    pub fn seek_time_s(&mut self, time_s: f64) {
+        self.seek_frame((time_s.max(0.0) * self.fps as f64).floor() as u64);
+    }
+
+    /// Moves by whole frames, ignoring pause and time scale.
+    pub fn step_frames(&mut self, delta: i64) {
+        self.seek_frame(self.frame_index.saturating_add_signed(delta));
+    }
+
+    /// Advances by wall-clock time, honoring pause and time scale. This is
+    /// what interactive hosts call once per displayed frame.
+    pub fn advance(&mut self, wall_dt_s: f32) {
+        if self.is_paused {
+            return;
+        }
+
+        let frames = self.subframe + wall_dt_s.max(0.0) as f64 * self.time_scale * self.fps as f64;
+        self.frame_index += frames.floor() as u64;
+        self.subframe = frames.fract();
+    }
+
+    /// Returns the time systems should animate with. Depends only on the
+    /// current position, never on what was sampled before.
+    pub fn sample(&self) -> FrameTime {
+        FrameTime {
+            frame_index: self.frame_index,
+            time_s: self.time_s(),
+        }
+    }
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_FPS)
+    }
+}
+
+/// A snapshot of a [`Timeline`], handed to systems when updating.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    pub frame_index: u64,
+    pub time_s: f64,
+}
+
+impl FrameTime {
+    pub const ZERO: Self = Self {
+        frame_index: 0,
+        time_s: 0.0,
+    };
+
+    pub fn time_ms(&self) -> u64 {
+        (self.time_s * 1000.0).round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_is_independent_of_history() {
+        let mut played = Timeline::new(30);
+        for _ in 0..45 {
+            played.advance(1.0 / 30.0);
+            played.sample();
+        }
+        played.seek_frame(40);
+
+        let mut seeked = Timeline::new(30);
+        seeked.seek_frame(90);
+        seeked.sample();
+        seeked.step_frames(-50);
+
+        assert_eq!(played.sample(), seeked.sample());
+        assert_eq!(seeked.sample().frame_index, 40);
+    }
+
+    #[test]
+    fn advance_honors_pause_and_time_scale() {
+        let mut timeline = Timeline::new(10);
+        timeline.set_time_scale(0.5);
+        timeline.advance(1.0);
+        assert_eq!(timeline.frame_index(), 5);
+
+        timeline.set_paused(true);
+        timeline.advance(1.0);
+        assert_eq!(timeline.frame_index(), 5);
+    }
+}
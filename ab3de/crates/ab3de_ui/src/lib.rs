@@ -4,7 +4,9 @@ use ab3de_internal_shared::{
     inputting,
 };
 
-pub struct AppUi;
+pub struct AppUi {
+    initial_camera_data: CameraData,
+}
 
 impl AppUi {
     pub fn new(
@@ -12,6 +14,7 @@ impl AppUi {
         format: wgpu::TextureFormat,
         type_map: &mut type_map::concurrent::TypeMap,
         proxy: Box<dyn EngineViewportProxy + Send + Sync>,
+        initial_camera_data: CameraData,
     ) -> Self {
         let shader = device.create_shader_module(wgpu::include_wgsl!("./copying.wgsl"));
 
@@ -83,19 +86,25 @@ impl AppUi {
             size: None,
         });
 
-        Self
+        Self {
+            initial_camera_data,
+        }
     }
 
     pub fn show(&self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            MainViewport::default().ui(ui);
+            MainViewport {
+                initial_camera_data: &self.initial_camera_data,
+            }
+            .ui(ui);
             ctx.request_repaint();
         });
     }
 }
 
-#[derive(Default)]
-struct MainViewport;
+struct MainViewport<'a> {
+    initial_camera_data: &'a CameraData,
+}
 
 #[derive(Clone)]
 struct MainViewportData {
@@ -105,7 +114,7 @@ struct MainViewportData {
     last_frame_is_shift_down: bool,
 }
 
-impl MainViewport {
+impl MainViewport<'_> {
     fn ui(&self, ui: &mut egui::Ui) {
         let rect = ui.max_rect();
         let (rect, _response) =
@@ -189,7 +198,7 @@ impl MainViewport {
                 .data
                 .get_temp_mut_or_insert_with(ui.id(), || MainViewportData {
                     camera_controller: CameraController::default(),
-                    camera_data: CameraData::default(),
+                    camera_data: self.initial_camera_data.clone(),
                     last_frame_is_shift_down: false,
                 });
 